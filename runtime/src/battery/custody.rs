@@ -0,0 +1,377 @@
+//! Battery lifecycle: registration, custody changes, staking and decommissioning.
+
+use super::*;
+use support::storage;
+use runtime_primitives::traits::As;
+
+impl<T: Trait> Module<T> {
+    /// Checks `store_to_station` signed by `who` would pass, without changing
+    /// anything. Returns the station that would take the battery.
+    pub fn can_store(who: &T::AccountId, id: T::Hash) -> rstd::result::Result<T::AccountId, &'static str> {
+        Self::ensure_migrated()?;
+        Self::ensure_not_in_flight(id)?;
+        let station = Self::custodial_station(who.clone())?;
+
+        ensure!(<Batteries<T>>::exists(id), "Battery does not exist");
+        let battery = Self::batteries(id);
+        ensure!(battery.station().is_none(), "Station of the battery must be None");
+        ensure!(!Self::is_frozen_by_challenge(id), "Battery ownership is being challenged");
+        ensure!(!<BridgeRequestOf<T>>::exists(id), "Battery is locked in the bridge");
+        ensure!(T::AccountStatus::is_alive(&battery.owner), "Owner account is inactive");
+        ensure!(Self::is_open(&station), "Station is closed");
+        ensure!(Self::battery_age_days(&battery) <= Self::max_age_days(&station), "Battery is older than the station accepts");
+        Self::ensure_below_probation_cap(&station)?;
+        Ok(station)
+    }
+
+    /// Checks `fetch_from_station` signed by `who` would pass, without changing
+    /// anything. Returns the station holding the battery.
+    pub fn can_fetch(who: &T::AccountId, id: T::Hash) -> rstd::result::Result<T::AccountId, &'static str> {
+        Self::ensure_migrated()?;
+        Self::ensure_not_in_flight(id)?;
+
+        ensure!(<Batteries<T>>::exists(id), "Battery does not exist");
+        let battery = Self::batteries(id);
+        ensure!(Self::is_owner_or_co_owner(&battery, id, who), "You are not the owner of this battery");
+        let station = battery.station().cloned().ok_or("No station for this battery")?;
+        ensure!(Self::is_open(&station), "Station is closed");
+        Ok(station)
+    }
+
+    /// Moves battery `id`, which `can_store` cleared, into `station` on behalf of `sender`
+    /// and drops every other station's store request for it.
+    pub(super) fn do_store(sender: &T::AccountId, station: T::AccountId, id: T::Hash) {
+        let mut battery = Self::batteries(id);
+        let relist = Self::auto_relist(id);
+        let before = battery.custody.clone();
+        battery.custody = if relist { Custody::Tradable(station.clone()) } else { Custody::Station(station.clone()) };
+
+        Self::put_battery(id, battery.clone());
+        Self::note_custody_change(&before, &battery.custody);
+        if !relist {
+            Self::start_staking(id);
+        }
+        Self::add_to_station(&station, id);
+        Self::clear_store_requests(id);
+
+        Self::record_swap(&station);
+        Self::audit(&station, Some(sender.clone()), StationAction::BatteryStored(id));
+        Self::bump_nonce(id);
+
+        Self::deposit_event(RawEvent::StoreToStation(id, battery.owner, station));
+        if relist {
+            Self::deposit_event(RawEvent::SwitchTradable(id, true));
+        }
+    }
+
+    pub(super) fn clear_store_requests(id: T::Hash) {
+        for station in <PendingStoreStations<T>>::take(id) {
+            <PendingStores<T>>::remove((id, station));
+        }
+    }
+
+    /// Stores `battery` under `id` and refreshes its passport hash.
+    pub(super) fn put_battery(id: T::Hash, battery: Battery<T::AccountId, T::BlockNumber>) {
+        let previous = Self::batteries(id);
+        if previous.owner != battery.owner || previous.station() != battery.station() {
+            <CustodyPeriod<T>>::mutate(id, |period| *period += 1);
+        }
+        <Batteries<T>>::insert(id, battery);
+        Self::update_passport_hash(id);
+    }
+
+    /// Everything the chain knows about battery `id`, including its maintenance history.
+    pub fn battery_passport(id: T::Hash) -> Option<BatteryPassport<T::Hash, T::AccountId, T::BlockNumber>> {
+        if !<Batteries<T>>::exists(id) {
+            return None;
+        }
+        let battery = Self::batteries(id);
+        Some(BatteryPassport {
+            id,
+            owner: battery.owner,
+            custody: battery.custody,
+            registered_at: battery.registered_at,
+            model: Self::battery_model(id),
+            non_transferable: Self::is_non_transferable(id),
+            maintenance: (0..Self::maintenance_log_count(id))
+                .filter_map(|index| Self::maintenance_log((id, index)))
+                .collect(),
+        })
+    }
+
+    /// State trie key of battery `id` in `Batteries`; the value under it is the encoded `Battery`.
+    pub fn battery_storage_key(id: T::Hash) -> Vec<u8> {
+        runtime_io::blake2_256(&<Batteries<T>>::key_for(id)).to_vec()
+    }
+
+    pub(super) fn update_passport_hash(id: T::Hash) {
+        match Self::battery_passport(id) {
+            Some(passport) => <PassportHash<T>>::insert(id, <T as system::Trait>::Hashing::hash_of(&passport)),
+            None => <PassportHash<T>>::remove(id),
+        }
+    }
+
+    pub(super) fn do_register_battery(station: T::AccountId, owner: T::AccountId, non_transferable: bool) -> Result {
+        ensure!(<StationsIndex<T>>::exists(station.clone()), "Not a station!");
+        Self::ensure_below_probation_cap(&station)?;
+
+        let payload = (
+            <system::Module<T>>::random_seed(),
+            &owner,
+            <system::Module<T>>::extrinsic_index(),
+            <system::Module<T>>::block_number(),
+            Self::all_batteries_count(),
+        );
+        let id = T::IdGenerator::generate_id(&owner, payload.using_encoded(<T as system::Trait>::Hashing::hash));
+
+        ensure!(!<Batteries<T>>::exists(id), "Battery already exists!");
+        ensure!(!<DecommissionedBatteries<T>>::exists(id), "Battery was decommissioned");
+        let new_battery = Battery {
+            owner: owner.clone(),
+            custody: Custody::Station(station.clone()),
+            registered_at: <system::Module<T>>::block_number(),
+        };
+
+        // change state
+        <Stats<T>>::mutate(|stats| stats.add_battery(&new_battery.custody));
+        if non_transferable {
+            <NonTransferable<T>>::insert(id, true);
+        }
+        Self::put_battery(id, new_battery);
+        Self::start_staking(id);
+        <AllBatteriesArray<T>>::insert(Self::all_batteries_count(), id);
+        <AllBatteriesCount<T>>::mutate(|n| *n += 1);
+        <MerkleRootOutdated<T>>::put(true);
+        Self::add_owned(&owner, id);
+        Self::add_to_station(&station, id);
+        Self::audit(&station, Some(station.clone()), StationAction::BatteryRegistered(id));
+
+        Self::deposit_event(RawEvent::RegistryBattery(station, id, owner));
+        if non_transferable {
+            Self::deposit_event(RawEvent::NonTransferableRegistered(id));
+        }
+        Ok(())
+    }
+
+    /// Governance tokens battery `id` has earned since it was staked or last claimed.
+    pub fn pending_staking_rewards(id: T::Hash) -> GovBalanceOf<T> {
+        match Self::stake_start(id) {
+            Some(start) => {
+                let blocks = (<system::Module<T>>::block_number() - start).as_();
+                Self::rewards_per_block() * <GovBalanceOf<T> as As<u64>>::sa(blocks)
+            }
+            None => Zero::zero(),
+        }
+    }
+
+    /// (Re)starts accruing staking rewards for `id` from the current block.
+    pub(super) fn start_staking(id: T::Hash) {
+        <StakeStart<T>>::insert(id, <system::Module<T>>::block_number());
+    }
+
+    /// Stops accruing rewards for `id`, paying out what it earned to `owner`.
+    pub(super) fn stop_staking(id: T::Hash, owner: &T::AccountId) {
+        if <StakeStart<T>>::exists(id) {
+            let reward = Self::pending_staking_rewards(id);
+            <StakeStart<T>>::remove(id);
+            Self::mint_rewards(id, owner, reward);
+        }
+    }
+
+    pub(super) fn mint_rewards(id: T::Hash, owner: &T::AccountId, reward: GovBalanceOf<T>) {
+        if !reward.is_zero() {
+            // Dropping the imbalance adds the minted tokens to the total issuance.
+            let _ = T::GovCurrency::deposit_creating(owner, reward);
+            Self::deposit_event(RawEvent::StakingRewardsClaimed(id, owner.clone(), reward));
+        }
+    }
+
+    pub(super) fn do_decommission(id: T::Hash, reason: DecommissionReason) {
+        let battery = Self::batteries(id);
+        let owner = battery.owner.clone();
+
+        if let Some(station) = battery.station() {
+            Self::remove_from_station(station, id);
+            let actor = if reason == DecommissionReason::Duplicate { None } else { Some(owner.clone()) };
+            Self::audit(station, actor, StationAction::BatteryRemoved(id));
+        }
+        Self::stop_staking(id, &owner);
+        Self::remove_owned(&owner, id);
+        if let Some(challenge_id) = Self::challenge_of(id) {
+            <PendingChallenges<T>>::remove(challenge_id);
+            <ChallengeOf<T>>::remove(id);
+            <ChallengeEvidence<T>>::remove(challenge_id);
+        }
+        for index in 0..<DocumentCount<T>>::take(id) {
+            <Documents<T>>::remove((id, index));
+        }
+        <CustodyPeriod<T>>::remove(id);
+        <CustodyDocuments<T>>::remove(id);
+        if let Some(compensation) = <PendingCompensations<T>>::take(id) {
+            T::Currency::unreserve(&compensation.manufacturer, compensation.amount);
+        }
+        <Recalled<T>>::remove(id);
+        <NonTransferable<T>>::remove(id);
+        Self::clear_store_requests(id);
+        <BatteryModelOf<T>>::remove(id);
+        <Batteries<T>>::remove(id);
+        <PassportHash<T>>::remove(id);
+        <BatteryNonce<T>>::remove(id);
+        <MerkleRootOutdated<T>>::put(true);
+        <Stats<T>>::mutate(|stats| {
+            stats.remove_battery(&battery.custody);
+            if reason != DecommissionReason::Duplicate {
+                stats.decommissioned += 1;
+            }
+        });
+        if reason != DecommissionReason::Duplicate {
+            <DecommissionedBatteries<T>>::insert(id, (reason, <timestamp::Module<T>>::get(), owner.clone()));
+        }
+
+        Self::run_hooks(id, || match reason {
+            DecommissionReason::Recycled => T::OnDecommission::on_recycled(&id, &owner),
+            DecommissionReason::Damaged => T::OnDecommission::on_damaged(&id, &owner),
+            _ => (),
+        });
+
+        Self::deposit_event(RawEvent::BatteryDecommissioned(id, owner, reason));
+    }
+
+    /// Appends `id` to the batteries held by `station`.
+    pub(super) fn add_to_station(station: &T::AccountId, id: T::Hash) {
+        let count = Self::batteries_count_in_station(station);
+        <BatteriesArrayInStation<T>>::insert((station.clone(), count), id);
+        <BatteriesIndexInStation<T>>::insert(id, count);
+        <BatteriesCountInStation<T>>::insert(station.clone(), count + 1);
+    }
+
+    /// Removes `id` from the batteries held by `station`, moving the last one into its slot.
+    pub(super) fn remove_from_station(station: &T::AccountId, id: T::Hash) {
+        let battery_index = Self::battery_index_in_station(id);
+        let batteries_count = Self::batteries_count_in_station(station);
+
+        // not the last one of the station
+        if batteries_count != battery_index + 1 {
+            let last_battery_id = Self::battery_of_station_by_index((station.clone(), batteries_count - 1));
+            <BatteriesArrayInStation<T>>::insert((station.clone(), battery_index), last_battery_id);
+            <BatteriesIndexInStation<T>>::insert(last_battery_id, battery_index);
+        }
+        <BatteriesArrayInStation<T>>::remove((station.clone(), batteries_count - 1));
+        <BatteriesIndexInStation<T>>::remove(id);
+        <BatteriesCountInStation<T>>::insert(station.clone(), batteries_count - 1);
+        <TargetSoc<T>>::remove(id);
+    }
+
+    pub(super) fn note_custody_change(before: &Custody<T::AccountId>, after: &Custody<T::AccountId>) {
+        <Stats<T>>::mutate(|stats| {
+            stats.remove_battery(before);
+            stats.add_battery(after);
+        });
+    }
+
+    pub(super) fn ensure_not_in_flight(id: T::Hash) -> Result {
+        ensure!(!<InFlight<T>>::exists(id), "Operation on this battery is already in progress");
+        Ok(())
+    }
+
+    /// Runs `hooks` with `id` marked in flight, so they can't call back into the
+    /// module for the battery they are told about. Callers run it after their last
+    /// storage write, so hooks only ever see finished state.
+    pub(super) fn run_hooks<F: FnOnce()>(id: T::Hash, hooks: F) {
+        <InFlight<T>>::insert(id, ());
+        hooks();
+        <InFlight<T>>::remove(id);
+    }
+
+    pub fn is_feature_enabled(feature: Feature) -> bool {
+        Self::feature_flag(feature).unwrap_or_else(|| feature.enabled_by_default())
+    }
+
+    pub(super) fn ensure_feature_enabled(feature: Feature) -> Result {
+        ensure!(Self::is_feature_enabled(feature), "Feature is disabled");
+        Ok(())
+    }
+
+    /// Records that `who` is still around, cancelling any escheat notice against it.
+    pub(super) fn note_activity(who: &T::AccountId) {
+        <LastActivity<T>>::insert(who.clone(), <system::Module<T>>::block_number());
+        if <EscheatNotices<T>>::exists(who) {
+            <EscheatNotices<T>>::remove(who);
+            Self::deposit_event(RawEvent::EscheatCancelled(who.clone()));
+        }
+    }
+
+    /// Passes battery `id` from `owner` to `pool`. Listings are withdrawn; the
+    /// battery stays where it is. The caller runs the transfer hooks.
+    pub(super) fn escheat_battery(id: T::Hash, owner: &T::AccountId, pool: &T::AccountId) {
+        let mut battery = Self::batteries(id);
+        battery.owner = pool.clone();
+        let before = battery.custody.clone();
+        if let Custody::Tradable(station) = battery.custody {
+            battery.custody = Custody::Station(station);
+        }
+        Self::note_custody_change(&before, &battery.custody);
+        Self::stop_staking(id, owner);
+        if battery.station().is_some() {
+            Self::start_staking(id);
+        }
+        Self::put_battery(id, battery);
+        Self::remove_owned(owner, id);
+        Self::add_owned(pool, id);
+        Self::bump_nonce(id);
+    }
+
+    pub(super) fn bump_nonce(id: T::Hash) {
+        <BatteryNonce<T>>::mutate(id, |nonce| *nonce += 1);
+    }
+
+    pub(super) fn log_maintenance(id: T::Hash, station: T::AccountId, kind: MaintenanceKind<T::Hash>) {
+        let index = Self::maintenance_log_count(id);
+        <MaintenanceLogs<T>>::insert((id, index), MaintenanceRecord {
+            station,
+            at: <system::Module<T>>::block_number(),
+            kind,
+        });
+        <MaintenanceLogCount<T>>::insert(id, index + 1);
+        Self::update_passport_hash(id);
+    }
+
+    /// Batteries not converted yet read as the default battery, so every call that
+    /// reads or writes batteries waits until the migration is done.
+    pub(super) fn ensure_migrated() -> Result {
+        ensure!(Self::storage_version() >= STORAGE_VERSION, "Battery storage is being migrated");
+        Ok(())
+    }
+
+    /// Re-encodes the next `MIGRATION_BATCH_SIZE` batteries still stored in the legacy
+    /// layout, bumping `StorageVersion` once all are done. The original registration
+    /// timestamp is kept in `LegacyRegistryTime` since it can't be turned into a block
+    /// number.
+    pub(super) fn migrate_to_compact_batteries() {
+        if Self::storage_version() >= STORAGE_VERSION {
+            return;
+        }
+
+        let now = <system::Module<T>>::block_number();
+        let start = Self::migration_cursor();
+        let end = rstd::cmp::min(start.saturating_add(MIGRATION_BATCH_SIZE), Self::all_batteries_count());
+        for index in start..end {
+            let id = Self::battery_by_index(index);
+            let key = Self::battery_storage_key(id);
+            if let Some(old) = storage::unhashed::get::<LegacyBattery<T::Hash, T::Moment, T::AccountId>>(&key) {
+                <LegacyRegistryTime<T>>::insert(id, old.registry_time.clone());
+                let mut battery = Battery::from(old);
+                battery.registered_at = now;
+                Self::set_ownership_leaf(id, Some(&battery.owner));
+                Self::put_battery(id, battery);
+            }
+        }
+
+        if end == Self::all_batteries_count() {
+            <MigrationCursor<T>>::kill();
+            <StorageVersion<T>>::put(STORAGE_VERSION);
+        } else {
+            <MigrationCursor<T>>::put(end);
+        }
+    }
+}
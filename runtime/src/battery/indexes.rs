@@ -0,0 +1,401 @@
+//! Owner and model indexes, account queries and the Merkle commitments over all batteries.
+
+use super::*;
+use runtime_primitives::traits::As;
+
+/// Encodes an account's entry in one storage for `export_account_data`.
+pub(super) type AccountExporter<T> = fn(&<T as system::Trait>::AccountId) -> Vec<u8>;
+
+impl<T: Trait> Module<T> {
+    pub(super) fn update_merkle_root() {
+        if <MerkleRootOutdated<T>>::take() {
+            <BatteryMerkleRoot<T>>::put(Self::merkle_root_of(Self::merkle_leaves()));
+        }
+    }
+
+    pub(super) fn checkpoint_ownership(n: T::BlockNumber) {
+        let per_checkpoint = Self::blocks_per_checkpoint();
+        if per_checkpoint.is_zero() || !(n % per_checkpoint).is_zero() {
+            return;
+        }
+        let checkpoint = (n / per_checkpoint).as_();
+        <OwnershipCheckpoints<T>>::insert(checkpoint, Self::ownership_node((OWNERSHIP_TREE_DEPTH, 0)));
+        <LatestOwnershipCheckpoint<T>>::put(checkpoint);
+    }
+
+    /// Points the ownership leaf of battery `id` at `owner`, or empties it, and rehashes
+    /// the path up to the root.
+    pub(super) fn set_ownership_leaf(id: T::Hash, owner: Option<&T::AccountId>) {
+        let slot = match Self::ownership_slot(id) {
+            Some(slot) => slot,
+            None => {
+                let slot = Self::ownership_slot_count();
+                <OwnershipSlots<T>>::insert(id, slot);
+                <OwnershipSlotCount<T>>::put(slot + 1);
+                slot
+            }
+        };
+        let mut node = owner.map(|owner| Self::ownership_leaf(id, owner)).unwrap_or_default();
+        let mut index = slot;
+        for level in 0..OWNERSHIP_TREE_DEPTH {
+            Self::put_ownership_node((level, index), node);
+            node = Self::ownership_parent(node, Self::ownership_node((level, index ^ 1)));
+            index /= 2;
+        }
+        Self::put_ownership_node((OWNERSHIP_TREE_DEPTH, index), node);
+    }
+
+    /// Saves the node as of the latest checkpoint the first time it changes after it,
+    /// so proofs against that checkpoint can still be built.
+    fn put_ownership_node(position: (u32, u64), node: T::Hash) {
+        if let Some(checkpoint) = Self::latest_ownership_checkpoint() {
+            let key = (checkpoint, position.0, position.1);
+            if !<OwnershipNodesAt<T>>::exists(key) {
+                <OwnershipNodesAt<T>>::insert(key, Self::ownership_node(position));
+            }
+        }
+        <OwnershipNodes<T>>::insert(position, node);
+    }
+
+    /// The node at `position` when `checkpoint` was taken: the first value saved at or after
+    /// that checkpoint, or the current one if it hasn't changed since.
+    fn ownership_node_at(checkpoint: u64, position: (u32, u64)) -> T::Hash {
+        let latest = Self::latest_ownership_checkpoint().unwrap_or(checkpoint);
+        (checkpoint..=latest)
+            .filter_map(|saved_at| Self::ownership_node_snapshot((saved_at, position.0, position.1)))
+            .next()
+            .unwrap_or_else(|| Self::ownership_node(position))
+    }
+
+    /// Empty subtrees hash to the default hash, everything else like `merkle_parent`.
+    fn ownership_parent(a: T::Hash, b: T::Hash) -> T::Hash {
+        if a == T::Hash::default() && b == T::Hash::default() {
+            T::Hash::default()
+        } else {
+            Self::merkle_parent(a, b)
+        }
+    }
+
+    pub(super) fn live_batteries() -> impl Iterator<Item = T::Hash> {
+        (0..Self::all_batteries_count())
+            .map(Self::battery_by_index)
+            .filter(|id| <Batteries<T>>::exists(id))
+    }
+
+    pub(super) fn merkle_leaves() -> Vec<T::Hash> {
+        Self::live_batteries()
+            .map(|id| (0u8, id).using_encoded(<T as system::Trait>::Hashing::hash))
+            .collect()
+    }
+
+    pub(super) fn ownership_leaf(id: T::Hash, owner: &T::AccountId) -> T::Hash {
+        (2u8, id, owner).using_encoded(<T as system::Trait>::Hashing::hash)
+    }
+
+    pub(super) fn merkle_root_of(mut level: Vec<T::Hash>) -> T::Hash {
+        if level.is_empty() {
+            return T::Hash::default();
+        }
+        while level.len() > 1 {
+            level = Self::merkle_level_up(&level);
+        }
+        level[0]
+    }
+
+    pub(super) fn merkle_proof(mut level: Vec<T::Hash>, leaf: T::Hash) -> Option<Vec<T::Hash>> {
+        let mut index = level.iter().position(|node| *node == leaf)?;
+        let mut proof = Vec::new();
+        while level.len() > 1 {
+            if let Some(sibling) = level.get(index ^ 1) {
+                proof.push(*sibling);
+            }
+            level = Self::merkle_level_up(&level);
+            index /= 2;
+        }
+        Some(proof)
+    }
+
+    /// Pairs up nodes, hashing the smaller one first so proofs need no left/right
+    /// flags. A node left without a partner moves up unchanged.
+    pub(super) fn merkle_level_up(level: &[T::Hash]) -> Vec<T::Hash> {
+        level.chunks(2).map(|pair| match pair {
+            [a, b] => Self::merkle_parent(*a, *b),
+            _ => pair[0],
+        }).collect()
+    }
+
+    pub(super) fn merkle_parent(a: T::Hash, b: T::Hash) -> T::Hash {
+        let (left, right) = if a <= b { (a, b) } else { (b, a) };
+        (1u8, left, right).using_encoded(<T as system::Trait>::Hashing::hash)
+    }
+
+    /// Sibling hashes from the leaf of battery `id` up to `merkle_root`. Leaves are
+    /// `hash(0u8, id)` and inner nodes `hash(1u8, smaller_child, larger_child)`.
+    /// Outside of block execution the root always matches the stored batteries.
+    pub fn battery_inclusion_proof(id: T::Hash) -> Option<Vec<T::Hash>> {
+        let leaf = (0u8, id).using_encoded(<T as system::Trait>::Hashing::hash);
+        Self::merkle_proof(Self::merkle_leaves(), leaf)
+    }
+
+    /// Whether `proof` shows battery `id` is included in `merkle_root`.
+    pub fn verify_battery_inclusion(id: T::Hash, proof: Vec<T::Hash>) -> bool {
+        let leaf = (0u8, id).using_encoded(<T as system::Trait>::Hashing::hash);
+        let root = proof.into_iter().fold(leaf, Self::merkle_parent);
+        root != T::Hash::default() && root == Self::merkle_root()
+    }
+
+    /// Sibling hashes from the `hash(2u8, id, owner)` leaf of battery `id` up to the root
+    /// taken at `checkpoint`, if `owner` owned the battery then.
+    pub fn ownership_proof(checkpoint: u64, id: T::Hash, owner: &T::AccountId) -> Option<Vec<T::Hash>> {
+        Self::ownership_checkpoint(checkpoint)?;
+        let mut index = Self::ownership_slot(id)?;
+        if Self::ownership_node_at(checkpoint, (0, index)) != Self::ownership_leaf(id, owner) {
+            return None;
+        }
+        let proof = (0..OWNERSHIP_TREE_DEPTH).map(|level| {
+            let sibling = Self::ownership_node_at(checkpoint, (level, index ^ 1));
+            index /= 2;
+            sibling
+        }).collect();
+        Some(proof)
+    }
+
+    /// Whether `proof` shows `owner` owned battery `id` when `checkpoint` was taken.
+    pub fn verify_ownership(checkpoint: u64, id: T::Hash, owner: &T::AccountId, proof: Vec<T::Hash>) -> bool {
+        let root = proof.into_iter().fold(Self::ownership_leaf(id, owner), Self::merkle_parent);
+        Self::ownership_checkpoint(checkpoint) == Some(root)
+    }
+
+    /// Appends `id` to the batteries owned by `owner`.
+    pub(super) fn add_owned(owner: &T::AccountId, id: T::Hash) {
+        let count = Self::owned_batteries_count(owner);
+        <OwnedBatteriesArray<T>>::insert((owner.clone(), count), id);
+        <OwnedBatteriesIndex<T>>::insert(id, count);
+        <OwnedBatteriesCount<T>>::insert(owner.clone(), count + 1);
+        let seq = Self::next_acquisition_seq(owner);
+        <AcquisitionSeq<T>>::insert(id, seq);
+        <NextAcquisitionSeq<T>>::insert(owner.clone(), seq + 1);
+        if let Some(model_id) = Self::battery_model(id) {
+            Self::add_owner_model(owner, model_id, id);
+        }
+        Self::set_ownership_leaf(id, Some(owner));
+    }
+
+    /// Drops battery `id` from `owner`'s lists, along with everything keyed by `id` that only
+    /// holds while `owner` owns it. Every path that takes a battery away from its owner goes
+    /// through here.
+    pub(super) fn remove_owned(owner: &T::AccountId, id: T::Hash) {
+        let last_index = Self::owned_batteries_count(owner) - 1;
+        let index = Self::owned_battery_index(id);
+        if index != last_index {
+            let last_id = Self::battery_of_owner_by_index((owner.clone(), last_index));
+            <OwnedBatteriesArray<T>>::insert((owner.clone(), index), last_id);
+            <OwnedBatteriesIndex<T>>::insert(last_id, index);
+        }
+        <OwnedBatteriesArray<T>>::remove((owner.clone(), last_index));
+        <OwnedBatteriesCount<T>>::insert(owner.clone(), last_index);
+        <OwnedBatteriesIndex<T>>::remove(id);
+        <AcquisitionSeq<T>>::remove(id);
+        <AutoRelist<T>>::remove(id);
+        <WarrantyCertificates<T>>::remove((id, owner.clone()));
+        Self::clear_co_owners(id);
+        if let Some(model_id) = Self::battery_model(id) {
+            Self::remove_owner_model(owner, model_id, id);
+        }
+        Self::set_ownership_leaf(id, None);
+    }
+
+    /// Sets the model of battery `id`, owned by `owner`, keeping the owner-model index in sync.
+    pub(super) fn set_model(id: T::Hash, owner: &T::AccountId, model_id: T::Hash) {
+        if let Some(old_model_id) = Self::battery_model(id) {
+            Self::remove_owner_model(owner, old_model_id, id);
+        }
+        <BatteryModelOf<T>>::insert(id, model_id);
+        Self::add_owner_model(owner, model_id, id);
+        Self::update_passport_hash(id);
+    }
+
+    pub(super) fn add_owner_model(owner: &T::AccountId, model_id: T::Hash, id: T::Hash) {
+        let count = Self::owner_model_count((owner.clone(), model_id));
+        <OwnerModelBatteriesArray<T>>::insert((owner.clone(), model_id, count), id);
+        <OwnerModelBatteriesIndex<T>>::insert(id, count);
+        <OwnerModelBatteriesCount<T>>::insert((owner.clone(), model_id), count + 1);
+    }
+
+    pub(super) fn remove_owner_model(owner: &T::AccountId, model_id: T::Hash, id: T::Hash) {
+        let last_index = Self::owner_model_count((owner.clone(), model_id)) - 1;
+        let index = Self::owner_model_index(id);
+        if index != last_index {
+            let last_id = Self::battery_by_owner_model((owner.clone(), model_id, last_index));
+            <OwnerModelBatteriesArray<T>>::insert((owner.clone(), model_id, index), last_id);
+            <OwnerModelBatteriesIndex<T>>::insert(last_id, index);
+        }
+        <OwnerModelBatteriesArray<T>>::remove((owner.clone(), model_id, last_index));
+        <OwnerModelBatteriesIndex<T>>::remove(id);
+        <OwnerModelBatteriesCount<T>>::insert((owner.clone(), model_id), last_index);
+    }
+
+    /// Up to `limit` batteries of `owner` starting at `start`, oldest acquisition first.
+    pub fn owned_batteries_sorted(owner: &T::AccountId, start: u64, limit: u64) -> Vec<T::Hash> {
+        let mut owned: Vec<(u64, T::Hash)> = (0..Self::owned_batteries_count(owner))
+            .map(|index| {
+                let id = Self::battery_of_owner_by_index((owner.clone(), index));
+                (Self::acquisition_seq(id), id)
+            })
+            .collect();
+        owned.sort_by_key(|(seq, _)| *seq);
+        owned.into_iter().skip(start as usize).take(limit as usize).map(|(_, id)| id).collect()
+    }
+
+    /// Up to `limit` (at most `MAX_EXPORT_PAGE`) of `account`'s batteries starting at
+    /// `cursor`, and on the first page the account's entry in every storage registered
+    /// in `account_exporters`.
+    pub fn export_account_data(
+        account: &T::AccountId,
+        cursor: u64,
+        limit: u64,
+    ) -> AccountExport<T::Hash, T::AccountId, T::BlockNumber, BalanceOf<T>> {
+        let owned = Self::owned_batteries_count(account);
+        let end = rstd::cmp::min(cursor.saturating_add(rstd::cmp::min(limit, MAX_EXPORT_PAGE)), owned);
+        let batteries = (cursor..end)
+            .map(|index| Self::battery_of_owner_by_index((account.clone(), index)))
+            .filter_map(|id| Some(BatteryExport {
+                passport: Self::battery_passport(id)?,
+                co_owners: Self::co_owners(id),
+                co_owner_threshold: Self::co_owner_threshold(id),
+                auto_relist: Self::auto_relist(id),
+                recall_compensation: Self::compensation(id).map(|compensation| compensation.amount),
+                documents: (0..Self::document_count(id)).filter_map(|index| Self::document((id, index))).collect(),
+                warranty: Self::warranty_cert((id, account.clone())),
+                pending_stores: Self::pending_store_stations(id)
+                    .into_iter()
+                    .filter_map(|station| Some((station.clone(), Self::pending_store((id, station))?)))
+                    .collect(),
+            }))
+            .collect();
+
+        let records = if cursor == 0 {
+            Self::account_exporters()
+                .into_iter()
+                .filter_map(|(name, exporter)| exporter.map(|export| (name.as_bytes().to_vec(), export(account))))
+                .collect()
+        } else {
+            Vec::new()
+        };
+
+        AccountExport {
+            batteries,
+            next_cursor: if end < owned { Some(end) } else { None },
+            records,
+        }
+    }
+
+    /// Every storage keyed by account, with the function that encodes an account's entry
+    /// for `export_account_data`. Storage without an exporter goes out with the batteries
+    /// instead. A storage keyed by account has to be listed here, or the tests fail.
+    pub(super) fn account_exporters() -> Vec<(&'static str, Option<AccountExporter<T>>)> {
+        let exporters: &[(&'static str, Option<AccountExporter<T>>)] = &[
+            ("OwnedBatteriesCount", Some(|who| Self::owned_batteries_count(who).encode())),
+            ("OwnedBatteriesArray", None),
+            ("NextAcquisitionSeq", Some(|who| Self::next_acquisition_seq(who).encode())),
+            ("StationsIndex", Some(|who| {
+                let index = if <StationsIndex<T>>::exists(who) { Some(Self::station_index(who)) } else { None };
+                index.encode()
+            })),
+            ("BatteriesCountInStation", Some(|who| Self::batteries_count_in_station(who).encode())),
+            ("BatteriesArrayInStation", Some(|who| {
+                (0..Self::batteries_count_in_station(who))
+                    .map(|index| Self::battery_of_station_by_index((who.clone(), index)))
+                    .collect::<Vec<_>>()
+                    .encode()
+            })),
+            ("IsChargingStation", Some(|who| Self::is_charging_station(who).encode())),
+            ("StationSwapCapacity", Some(|who| Self::swap_capacity(who).encode())),
+            ("SwapThroughput", Some(|who| Self::swap_throughput(who).encode())),
+            ("PendingStores", None),
+            ("OpeningHours", Some(|who| Self::opening_hours(who).encode())),
+            ("StationAgePolicy", Some(|who| Self::station_age_policy(who).encode())),
+            ("StationLocation", Some(|who| Self::station_location(who).encode())),
+            ("SessionKeys", Some(|who| Self::session_key(who).encode())),
+            ("KeyToStation", Some(|who| Self::key_station(who).encode())),
+            ("WarrantyCertificates", None),
+            ("OwnerModelBatteriesCount", None),
+            ("OwnerModelBatteriesArray", None),
+            ("PreOrdersOf", Some(|who| {
+                Self::preorders_of(who).into_iter().filter_map(Self::preorder).collect::<Vec<_>>().encode()
+            })),
+            ("TrustLevels", Some(|who| Self::trust_level(who).encode())),
+            ("StationPromotionVotes", Some(|who| Self::votes(who).encode())),
+            ("HasVoted", Some(|who| {
+                (0..Self::stations_count())
+                    .map(Self::station_by_index)
+                    .filter(|station| Self::has_voted((who.clone(), station.clone())))
+                    .collect::<Vec<_>>()
+                    .encode()
+            })),
+            ("StationStatuses", Some(|who| Self::station_status(who).encode())),
+            ("StationAuditCount", Some(|who| Self::audit_count(who).encode())),
+            ("StationAuditLog", None),
+            ("StationReports", Some(|who| Self::station_reports(who).encode())),
+            ("ReportedStake", Some(|who| {
+                let era = Self::report_era();
+                (era, Self::reported_stake((who.clone(), era))).encode()
+            })),
+            ("ReportsFiled", Some(|who| {
+                let era = Self::report_era();
+                (era, Self::reports_filed((who.clone(), era))).encode()
+            })),
+            ("UnderReview", Some(|who| Self::is_under_review(who).encode())),
+            ("OpenAudit", Some(|who| Self::open_audit(who).encode())),
+            ("LastAuditRound", Some(|who| Self::last_audit_round(who).encode())),
+            ("StationBonds", Some(|who| Self::bond_of(who).encode())),
+            ("NotificationPreferences", Some(|who| Self::notification_prefs(who).encode())),
+            ("BatteryOwnerClaims", Some(|who| Self::identity_claim(who).encode())),
+            ("ProcessedKeys", Some(|who| {
+                let now = <system::Module<T>>::block_number();
+                let retention = Self::key_retention_blocks();
+                let from: u64 = if now > retention { (now - retention).as_() + 1 } else { 0 };
+                let to: u64 = now.as_();
+                (from..=to)
+                    .flat_map(|n| Self::keys_processed_at(T::BlockNumber::sa(n)))
+                    .filter(|(station, _)| station == who)
+                    .filter_map(|entry| Some((entry.1, Self::processed_key(entry)?)))
+                    .collect::<Vec<_>>()
+                    .encode()
+            })),
+            ("LastActivity", Some(|who| Self::last_activity(who).encode())),
+            ("EscheatNotices", Some(|who| Self::escheat_notice(who).encode())),
+        ];
+        exporters.to_vec()
+    }
+
+    /// All batteries of `model_id` owned by `owner`, in no particular order.
+    pub fn batteries_by_owner_and_model(owner: &T::AccountId, model_id: T::Hash) -> Vec<T::Hash> {
+        (0..Self::owner_model_count((owner.clone(), model_id)))
+            .map(|index| Self::battery_by_owner_model((owner.clone(), model_id, index)))
+            .collect()
+    }
+
+    pub fn account_overview(account: &T::AccountId) -> AccountOverview<BalanceOf<T>> {
+        let owned = Self::owned_batteries_count(account);
+        let mut in_stations = 0;
+        let mut listed = 0;
+        for index in 0..owned {
+            let battery = Self::batteries(Self::battery_of_owner_by_index((account.clone(), index)));
+            if battery.station().is_some() {
+                in_stations += 1;
+            }
+            if battery.is_tradable() {
+                listed += 1;
+            }
+        }
+
+        AccountOverview {
+            owned,
+            in_stations,
+            listed,
+            held_as_station: Self::batteries_count_in_station(account),
+            ..Default::default()
+        }
+    }
+}
@@ -0,0 +1,1909 @@
+use rstd::prelude::*;
+use support::{
+    decl_storage, decl_module, decl_event, StorageValue, StorageMap, dispatch::Result, ensure,
+    traits::{Currency, ReservableCurrency, EnsureOrigin},
+};
+use system::{ensure_signed, ensure_root, ensure_inherent};
+use runtime_primitives::traits::{Hash, Zero};
+use parity_codec::{Encode, Decode};
+
+mod types;
+mod stations;
+mod custody;
+mod trading;
+mod indexes;
+
+pub use self::types::*;
+
+type BalanceOf<T> = <<T as Trait>::Currency as Currency<<T as system::Trait>::AccountId>>::Balance;
+type GovBalanceOf<T> = <<T as Trait>::GovCurrency as Currency<<T as system::Trait>::AccountId>>::Balance;
+
+/// Tells whether an account still exists. On chains that reap accounts whose balance
+/// falls below the existential deposit, batteries can be left with a dead owner.
+pub trait AccountLiveness<AccountId> {
+    fn is_alive(who: &AccountId) -> bool;
+}
+
+/// Treats every account as alive.
+impl<AccountId> AccountLiveness<AccountId> for () {
+    fn is_alive(_who: &AccountId) -> bool {
+        true
+    }
+}
+
+/// Reacts to decommissioned batteries, e.g. to issue recycling certificates or
+/// open insurance claims.
+pub trait OnDecommission<Hash, AccountId> {
+    fn on_recycled(id: &Hash, last_owner: &AccountId);
+    fn on_damaged(id: &Hash, last_owner: &AccountId);
+}
+
+impl<Hash, AccountId> OnDecommission<Hash, AccountId> for () {
+    fn on_recycled(_id: &Hash, _last_owner: &AccountId) {}
+    fn on_damaged(_id: &Hash, _last_owner: &AccountId) {}
+}
+
+/// Told about every change of battery ownership, after all storage writes are done.
+/// Calls back into this module for the same battery fail with a reentrancy error.
+pub trait OnBatteryTransfer<Hash, AccountId> {
+    fn on_battery_transfer(id: &Hash, from: &AccountId, to: &AccountId);
+}
+
+impl<Hash, AccountId> OnBatteryTransfer<Hash, AccountId> for () {
+    fn on_battery_transfer(_id: &Hash, _from: &AccountId, _to: &AccountId) {}
+}
+
+/// Picks the id of a newly registered battery, given the one derived from the random
+/// seed, owner, extrinsic index, block number and battery count.
+pub trait BatteryIdGenerator<AccountId, Hash> {
+    fn generate_id(owner: &AccountId, random_id: Hash) -> Hash;
+}
+
+/// Keeps the derived id.
+impl<AccountId, Hash> BatteryIdGenerator<AccountId, Hash> for () {
+    fn generate_id(_owner: &AccountId, random_id: Hash) -> Hash {
+        random_id
+    }
+}
+
+/// Puts an unsigned `schedule_audit` call for `station` into the transaction pool.
+/// Only the off-chain worker uses it.
+pub trait SubmitAudit<AccountId> {
+    fn submit_schedule_audit(station: AccountId);
+}
+
+impl<AccountId> SubmitAudit<AccountId> for () {
+    fn submit_schedule_audit(_station: AccountId) {}
+}
+
+pub trait Trait: timestamp::Trait {
+    type Event: From<Event<Self>> + Into<<Self as system::Trait>::Event>;
+
+    /// Currency pre-order funds are locked in.
+    type Currency: ReservableCurrency<Self::AccountId>;
+
+    /// Governance token minted as staking rewards.
+    type GovCurrency: Currency<Self::AccountId>;
+
+    /// Checked before batteries are handed to or stored for an account.
+    type AccountStatus: AccountLiveness<Self::AccountId>;
+
+    /// Origin that settles ownership challenges.
+    type ArbitratorOrigin: EnsureOrigin<Self::Origin>;
+
+    /// Origin of relayers confirming bridge transfers.
+    type BridgeOrigin: EnsureOrigin<Self::Origin>;
+
+    /// Origin that may escheat the batteries of abandoned accounts.
+    type AdminOrigin: EnsureOrigin<Self::Origin>;
+
+    /// Side effects of decommissioning a battery.
+    type OnDecommission: OnDecommission<Self::Hash, Self::AccountId>;
+
+    type OnBatteryTransfer: OnBatteryTransfer<Self::Hash, Self::AccountId>;
+
+    /// Ids of newly registered batteries, `()` for the derived ones.
+    type IdGenerator: BatteryIdGenerator<Self::AccountId, Self::Hash>;
+
+    /// Sends the audits the off-chain worker draws, `()` on nodes that don't audit.
+    type AuditSubmitter: SubmitAudit<Self::AccountId>;
+}
+
+decl_event!(
+    pub enum Event<T>
+    where
+        AccountId = <T as system::Trait>::AccountId,
+        Hash = <T as system::Trait>::Hash,
+        Balance = BalanceOf<T>,
+        GovBalance = GovBalanceOf<T>,
+        BlockNumber = <T as system::Trait>::BlockNumber,
+    {
+        RegistryStation(AccountId),
+        RegistryBattery(AccountId, Hash, AccountId),
+        SwitchTradable(Hash, bool),
+        StoreToStation(Hash, AccountId, AccountId),
+        FetchFromStation(Hash, AccountId, AccountId),
+        /// Battery id, seller, buyer, station and the receipt hash.
+        Trade(Hash, AccountId, AccountId, AccountId, Hash),
+        /// Station moved to the given latitude and longitude, in microdegrees.
+        StationLocationSet(AccountId, i64, i64),
+        /// Station designated a new session key.
+        SessionKeySet(AccountId, AccountId),
+        /// A manufacturer published a battery model.
+        ModelRegistered(Hash, AccountId),
+        /// A battery was tagged with its model.
+        BatteryModelSet(Hash, Hash),
+        /// Pre-order id, buyer, model id and the funds locked for it.
+        PreOrderPlaced(Hash, AccountId, Hash, Balance),
+        /// Pre-order id and the battery delivered for it.
+        PreOrderFulfilled(Hash, Hash),
+        PreOrderCancelled(Hash),
+        BatteryRecalled(Hash, Balance),
+        RecallCompensationClaimed(Hash, AccountId, Balance),
+        /// Battery id, its owner and the governance tokens minted to them.
+        StakingRewardsClaimed(Hash, AccountId, GovBalance),
+        /// A deprecated extrinsic was dispatched.
+        DeprecatedCallUsed(DeprecatedCall),
+        TrustLevelSet(AccountId, TrustLevel),
+        /// Battery id and the model it was refurbished to.
+        BatteryModelUpgraded(Hash, Hash),
+        /// Battery id, the inactive owner it was taken from and its new owner.
+        OrphanRescued(Hash, AccountId, AccountId),
+        CoOwnerAdded(Hash, AccountId),
+        CoOwnerThresholdSet(Hash, u32),
+        CoOwnerRemoved(Hash, AccountId),
+        /// Battery id, approving co-owner and the action hash approved.
+        ActionApproved(Hash, AccountId, Hash),
+        /// Challenge id and the challenger.
+        ChallengeFiled(Hash, AccountId),
+        /// Challenge id and the account ruled to be the owner.
+        ChallengeResolved(Hash, AccountId),
+        /// Battery id, document index and the anchoring account.
+        DocumentAnchored(Hash, u32, AccountId),
+        /// Challenge id and the index of the cited document.
+        EvidenceSubmitted(Hash, u32),
+        /// Battery id, its last owner and the reason.
+        BatteryDecommissioned(Hash, AccountId, DecommissionReason),
+        StationOpeningHoursSet(AccountId),
+        /// Station and the oldest battery age in days it accepts.
+        StationAgePolicySet(AccountId, u64),
+        StatsRebuilt(NetworkStats),
+        SwapCapacitySet(AccountId, u32),
+        /// Bridge request id and battery id.
+        BridgeLocked(Hash, Hash),
+        /// Bridge request id and the minting transaction hash on the destination chain.
+        BridgeConfirmed(Hash, Vec<u8>),
+        BridgeUnlocked(Hash),
+        /// The account's batteries pass to the escheat pool at the given block unless it
+        /// makes any call before then.
+        EscheatNoticed(AccountId, BlockNumber),
+        EscheatCancelled(AccountId),
+        /// A call was resubmitted under an idempotency key the station already used for it.
+        IdempotentReplay(AccountId, IdempotencyKey),
+        FeatureEnabledSet(Feature, bool),
+        IdentityClaimLinked(AccountId, Hash),
+        BatteriesEscheated(AccountId, u32),
+        ChargingStationRegistered(AccountId),
+        StationGraduated(AccountId),
+        /// Station, reporter, category and stake.
+        StationReported(AccountId, AccountId, ReportCategory, Balance),
+        StationUnderReview(AccountId),
+        /// Station and whether the reports against it were upheld.
+        ReportsResolved(AccountId, bool),
+        /// Battery id, charging station and target state of charge in percent.
+        ChargingStarted(Hash, AccountId, u8),
+        AutoRelistSet(Hash, bool),
+        /// A battery was registered that can never be traded or listed.
+        NonTransferableRegistered(Hash),
+        TransferRestrictionLifted(Hash),
+        /// Voter and station.
+        VoteCast(AccountId, AccountId),
+        VoteRetracted(AccountId, AccountId),
+        StationPromoted(AccountId),
+        /// Proposal id, source and destination station.
+        RebalanceProposed(Hash, AccountId, AccountId),
+        RebalanceAccepted(Hash),
+        RebalanceRejected(Hash),
+        RebalanceExpired(Hash),
+        /// Audit id and the station selected for it.
+        AuditScheduled(Hash, AccountId),
+        AuditSubmitted(Hash, AccountId),
+        AuditMissed(Hash, AccountId),
+        /// Battery id and the station asked to store it.
+        StoreRequested(Hash, AccountId),
+        StoreRequestCancelled(Hash, AccountId),
+        NotificationPrefsUpdated(AccountId),
+        /// Battery id and the new owner holding the certificate.
+        WarrantyCertificateIssued(Hash, AccountId),
+        /// Model id and its warranty period in blocks.
+        ModelWarrantySet(Hash, BlockNumber),
+        /// Station and the bond it lost for missing an audit.
+        StationBondSlashed(AccountId, Balance),
+    }
+);
+
+decl_storage! {
+    trait Store for Module<T: Trait> as Battery {
+        /// Layout version of `Batteries`, bumped by the migration in `on_initialize`. New
+        /// chains start with the current layout.
+        StorageVersion get(storage_version) build(|_: &GenesisConfig<T>| STORAGE_VERSION): u32;
+        /// Next `AllBatteriesArray` index the migration converts, while it is under way.
+        MigrationCursor get(migration_cursor): u64;
+
+        Batteries get(batteries): map T::Hash => Battery<T::AccountId, T::BlockNumber>;
+        /// Registration timestamp of batteries carried over from the legacy layout.
+        LegacyRegistryTime get(legacy_registry_time): map T::Hash => Option<T::Moment>;
+
+        /// Every battery ever registered, including decommissioned ones.
+        AllBatteriesCount get(all_batteries_count): u64;
+        AllBatteriesArray get(battery_by_index): map u64 => T::Hash;
+        /// Merkle root over the ids of all live batteries, in registration order. See
+        /// `battery_inclusion_proof` for the tree layout.
+        BatteryMerkleRoot get(merkle_root): T::Hash;
+        /// Roots of the ownership tree, taken every `BlocksPerCheckpoint` blocks and keyed
+        /// by block number divided by it.
+        OwnershipCheckpoints get(ownership_checkpoint): map u64 => Option<T::Hash>;
+        LatestOwnershipCheckpoint get(latest_ownership_checkpoint): Option<u64>;
+        /// Leaf position of each battery in the ownership tree. Positions are never reused.
+        OwnershipSlots get(ownership_slot): map T::Hash => Option<u64>;
+        OwnershipSlotCount get(ownership_slot_count): u64;
+        /// Sparse Merkle tree over `(id, owner)` of all owned batteries, by level and position.
+        /// Leaves are at level 0 and the root at `OWNERSHIP_TREE_DEPTH`; empty subtrees are
+        /// the default hash. Kept up to date by `add_owned` and `remove_owned`.
+        OwnershipNodes get(ownership_node): map (u32, u64) => T::Hash;
+        /// Nodes as they were when a checkpoint was taken, by checkpoint, level and position.
+        OwnershipNodesAt get(ownership_node_snapshot): map (u64, u32, u64) => Option<T::Hash>;
+        /// Set to zero to stop taking ownership checkpoints.
+        BlocksPerCheckpoint get(blocks_per_checkpoint) config(): T::BlockNumber;
+        /// Set when batteries were added or removed, so the root is rebuilt at the end of the block.
+        MerkleRootOutdated: bool;
+        /// Batteries whose hooks are running. Nothing may touch them until the hooks return.
+        InFlight: map T::Hash => ();
+        /// Network-wide totals, kept up to date by every call that changes them.
+        Stats get(network_stats): NetworkStats;
+        /// Progress of `rebuild_stats`: the next `AllBatteriesArray` index to scan and the
+        /// totals gathered so far.
+        StatsRebuild: Option<(u64, NetworkStats)>;
+        /// Tombstones of decommissioned batteries, as `(reason, moment, last_owner)`.
+        /// Their ids can't be registered again.
+        DecommissionedBatteries get(decommissioned): map T::Hash => Option<(DecommissionReason, T::Moment, T::AccountId)>;
+
+        /// Removals swap the last battery into the freed slot, so the order of this
+        /// index changes over time. Use `owned_batteries_sorted` for a stable order.
+        OwnedBatteriesCount get(owned_batteries_count): map T::AccountId => u64;
+        OwnedBatteriesArray get(battery_of_owner_by_index): map (T::AccountId, u64) => T::Hash;
+        OwnedBatteriesIndex get(owned_battery_index): map T::Hash => u64;
+        /// When the current owner acquired each battery, counted per owner.
+        AcquisitionSeq get(acquisition_seq): map T::Hash => u64;
+        NextAcquisitionSeq get(next_acquisition_seq): map T::AccountId => u64;
+
+        StationsCount get(stations_count): u64;
+        StationsArray get(station_by_index): map u64 => T::AccountId;
+        StationsIndex get(station_index): map T::AccountId => u64;
+
+        BatteriesCountInStation get(batteries_count_in_station): map T::AccountId => u64;
+        BatteriesArrayInStation get(battery_of_station_by_index): map (T::AccountId, u64) => T::Hash;
+        BatteriesIndexInStation get(battery_index_in_station): map T::Hash => u64;
+
+        /// Stations that can also charge the batteries they hold.
+        IsChargingStation get(is_charging_station): map T::AccountId => bool;
+        /// State of charge in percent a charging station is charging a battery to.
+        /// Cleared when the battery leaves the station.
+        TargetSoc get(target_soc): map T::Hash => Option<u8>;
+
+        PendingBridgeRequests get(bridge_request): map T::Hash => Option<BridgeRequest<T::Hash, T::AccountId, u64, Vec<u8>>>;
+        /// The bridge request a battery is locked under, if any.
+        BridgeRequestOf get(bridge_request_of): map T::Hash => Option<T::Hash>;
+
+        /// Swaps per hour the station's hardware supports. Stations that never declared
+        /// a capacity are treated as never overwhelmed.
+        StationSwapCapacity get(swap_capacity): map T::AccountId => Option<u32>;
+        /// Sum of all declared swap capacities.
+        NetworkSwapCapacity get(network_swap_capacity): u32;
+        /// Stores and fetches at a station during the given hour since the unix epoch.
+        SwapThroughput get(swap_throughput): map T::AccountId => (u64, u32);
+
+        /// Block after which each `(battery, station)` store request can no longer be confirmed.
+        PendingStores get(pending_store): map (T::Hash, T::AccountId) => Option<T::BlockNumber>;
+        /// Stations each battery has an open store request at.
+        PendingStoreStations get(pending_store_stations): map T::Hash => Vec<T::AccountId>;
+        StoreRequestExpiryBlocks get(store_request_expiry_blocks) config(): T::BlockNumber;
+
+        /// Batteries listed for trade as soon as they are stored in a station. Cleared
+        /// when the battery changes owner.
+        AutoRelist get(auto_relist): map T::Hash => bool;
+        /// Batteries handed out under programs that prohibit resale. Set at registration
+        /// only; custody changes and decommissioning stay allowed.
+        NonTransferable get(is_non_transferable): map T::Hash => bool;
+
+        /// Seven days starting on Monday, each a list of `(start_minute, end_minute)`
+        /// windows in UTC. Stations without opening hours are always open.
+        OpeningHours get(opening_hours): map T::AccountId => Option<Vec<Vec<(u32, u32)>>>;
+        /// Oldest battery, in days since registration, the station accepts for storage.
+        /// Stations without a policy accept batteries of any age, see `max_age_days`.
+        StationAgePolicy get(station_age_policy): map T::AccountId => Option<u64>;
+        BlocksPerDay get(blocks_per_day) config(): T::BlockNumber;
+
+        /// Latitude and longitude of a station, in microdegrees.
+        StationLocation get(station_location): map T::AccountId => Option<(i64, i64)>;
+
+        RebalanceNonce get(rebalance_nonce): u64;
+        RebalanceProposals get(rebalance_proposal): map T::Hash => Option<RebalanceProposal<T::AccountId, T::Hash, T::BlockNumber>>;
+        /// Rebalance proposals by the block they expire after.
+        RebalancesExpiringAt get(rebalances_expiring_at): map T::BlockNumber => Vec<T::Hash>;
+        RebalanceExpiryBlocks get(rebalance_expiry_blocks) config(): T::BlockNumber;
+
+        /// Hot key a station lets sign custody-only operations on its behalf.
+        SessionKeys get(session_key): map T::AccountId => Option<T::AccountId>;
+        /// Reverse lookup of `SessionKeys`.
+        KeyToStation get(key_station): map T::AccountId => Option<T::AccountId>;
+
+        Models get(model): map T::Hash => Option<BatteryModel<T::AccountId>>;
+        /// Model of a battery, for batteries whose manufacturer has tagged them.
+        BatteryModelOf get(battery_model): map T::Hash => Option<T::Hash>;
+        /// Blocks after registration a model's batteries stay under warranty, set by its
+        /// manufacturer. Models without a warranty period carry no warranty.
+        ModelWarranty get(model_warranty): map T::Hash => T::BlockNumber;
+        /// Warranty certificates by battery and holder. Only the current owner holds one.
+        WarrantyCertificates get(warranty_cert): map (T::Hash, T::AccountId) => Option<WarrantyCertificate<T::Hash, T::AccountId, T::BlockNumber>>;
+
+        /// Batteries of each model per owner, for fleet queries. Unordered, like the other indexes.
+        OwnerModelBatteriesCount get(owner_model_count): map (T::AccountId, T::Hash) => u64;
+        OwnerModelBatteriesArray get(battery_by_owner_model): map (T::AccountId, T::Hash, u64) => T::Hash;
+        OwnerModelBatteriesIndex get(owner_model_index): map T::Hash => u64;
+
+        PreOrders get(preorder): map T::Hash => Option<PreOrder<T::AccountId, T::Hash, BalanceOf<T>, T::BlockNumber>>;
+        /// Open pre-orders of each buyer.
+        PreOrdersOf get(preorders_of): map T::AccountId => Vec<T::Hash>;
+
+        Recalled get(is_recalled): map T::Hash => bool;
+        /// Compensation for a recalled battery its owner hasn't claimed yet.
+        PendingCompensations get(compensation): map T::Hash => Option<RecallCompensation<T::AccountId, BalanceOf<T>>>;
+        /// Used to derive unique pre-order ids.
+        PreOrderNonce get(preorder_nonce): u64;
+        /// How long pre-order funds stay locked before the buyer may cancel.
+        PreOrderLockPeriod get(preorder_lock_period) config(): T::BlockNumber;
+
+        /// Governance tokens a staked battery earns per block. A battery is staked while
+        /// a station holds it without it being open for trade.
+        RewardsPerBlock get(rewards_per_block) config(): GovBalanceOf<T>;
+        /// Block since which a staked battery accrues rewards.
+        StakeStart get(stake_start): map T::Hash => Option<T::BlockNumber>;
+
+        TrustLevels get(trust_level): map T::AccountId => TrustLevel;
+        /// Community votes for promoting each station from `Basic` to `Verified`.
+        StationPromotionVotes get(votes): map T::AccountId => u64;
+        /// Whether `(voter, station)` has a vote standing.
+        HasVoted get(has_voted): map (T::AccountId, T::AccountId) => bool;
+        /// A `Basic` station is promoted once it has more votes than this.
+        PromotionThreshold get(promotion_threshold) config(): u64;
+
+        StationStatuses get(station_status): map T::AccountId => StationStatus;
+
+        StationAuditCount get(audit_count): map T::AccountId => u64;
+        /// Every custody operation and configuration change involving a station, in order.
+        StationAuditLog get(audit_entry): map (T::AccountId, u64) => Option<StationAuditEntry<T::AccountId, T::BlockNumber, T::Hash>>;
+        /// Trades a station on probation must complete to graduate.
+        ProbationTradeThreshold get(probation_trade_threshold) config(): u32;
+
+        /// Misconduct reports against each station awaiting an AdminOrigin ruling.
+        StationReports get(station_reports): map T::AccountId => Vec<StationReport<T::AccountId, T::Hash, BalanceOf<T>>>;
+        /// Stake reported against each station per report era.
+        ReportedStake get(reported_stake): map (T::AccountId, u64) => BalanceOf<T>;
+        /// Reports each account filed per report era.
+        ReportsFiled get(reports_filed): map (T::AccountId, u64) => u32;
+        /// Stations that may not trade until their reports are ruled on.
+        UnderReview get(is_under_review): map T::AccountId => bool;
+        ReportEraLength get(report_era_length) config(): T::BlockNumber;
+        /// Stake reported against a station within one era that places it under review.
+        ReviewStakeThreshold get(review_stake_threshold) config(): BalanceOf<T>;
+
+        AuditNonce get(audit_nonce): u64;
+        /// Open compliance audits. A station has at most one at a time.
+        ComplianceAudits get(compliance_audit): map T::Hash => Option<AuditSchedule<T::AccountId, T::BlockNumber>>;
+        OpenAudit get(open_audit): map T::AccountId => Option<T::Hash>;
+        /// Audits by the block whose end they must be answered by.
+        AuditsDueAt get(audits_due_at): map T::BlockNumber => Vec<T::Hash>;
+        /// `(battery id, health in percent)` a station answered each audit with.
+        AuditReports get(audit_reports): map T::Hash => Vec<(T::Hash, u8)>;
+        /// Health in percent of each battery from the latest audit that covered it.
+        BatteryHealth get(battery_health): map T::Hash => Option<u8>;
+        /// Stations are sampled for audits every this many blocks, never if zero.
+        AuditInterval get(audit_interval) config(): T::BlockNumber;
+        AuditSampleSize get(audit_sample_size) config(): u32;
+        /// Blocks a station has to answer an audit. Missing it slashes the station's bond
+        /// and places the station under review.
+        AuditDeadlineBlocks get(audit_deadline_blocks) config(): T::BlockNumber;
+        /// Block the latest audit round started at and the random seed its stations are
+        /// drawn from.
+        AuditRound get(audit_round): Option<(T::BlockNumber, T::Hash)>;
+        /// Round each station was last audited in.
+        LastAuditRound get(last_audit_round): map T::AccountId => Option<T::BlockNumber>;
+        /// Reserved from an account when it registers as a station.
+        StationBond get(station_bond) config(): BalanceOf<T>;
+        /// Bond each station has reserved.
+        StationBonds get(bond_of): map T::AccountId => BalanceOf<T>;
+
+        /// Accounts sharing a battery with its owner. They may fetch it and list it for
+        /// trade, but ownership only moves once enough of them approved the transfer.
+        CoOwners get(co_owners): map T::Hash => Vec<T::AccountId>;
+        /// The one action each co-owner currently approves, as `(co_owner, action_hash)`.
+        Approvals get(approvals): map T::Hash => Vec<(T::AccountId, T::Hash)>;
+        /// Co-owner approvals an ownership transfer needs, capped at the number of co-owners.
+        CoOwnerApprovalThreshold get(co_owner_approval_threshold) config(): u32;
+        /// Overrides `CoOwnerApprovalThreshold` for a single battery.
+        CoOwnerThreshold get(co_owner_threshold): map T::Hash => Option<u32>;
+
+        PendingChallenges get(challenge): map T::Hash => Option<OwnershipChallenge<T::Hash, T::AccountId, T::BlockNumber>>;
+        /// The open challenge against a battery, if any.
+        ChallengeOf get(challenge_of): map T::Hash => Option<T::Hash>;
+        ChallengePeriodBlocks get(challenge_period_blocks) config(): T::BlockNumber;
+        /// Indexes of the battery's documents cited in each challenge.
+        ChallengeEvidence get(challenge_evidence): map T::Hash => Vec<u32>;
+
+        DocumentCount get(document_count): map T::Hash => u32;
+        /// Documents anchored for each battery, by battery and index. They are never changed
+        /// once written, and only removed together with a decommissioned battery.
+        Documents get(document): map (T::Hash, u32) => Option<DocumentAnchor<T::Hash, T::AccountId, T::BlockNumber>>;
+        /// Counts the times a battery changed owner or station.
+        CustodyPeriod get(custody_period): map T::Hash => u32;
+        /// Custody period the battery's latest document was anchored in, and the
+        /// documents anchored within it.
+        CustodyDocuments get(custody_documents): map T::Hash => (u32, u32);
+
+        /// Alerts each account opted in to, see `NotificationPrefs`.
+        NotificationPreferences get(notification_prefs): map T::AccountId => NotificationPrefs;
+        /// Hash of the real-world identity claim each account linked, as issued by a
+        /// claims registry. The chain doesn't check the claim itself.
+        BatteryOwnerClaims get(identity_claim): map T::AccountId => Option<T::Hash>;
+        /// Whether batteries may only be traded to accounts that linked an identity claim.
+        RequireIdentityForTrade get(require_identity_for_trade) config(): bool;
+
+        /// Features governance switched on or off, see `is_feature_enabled`.
+        FeatureFlags get(feature_flag): map Feature => Option<bool>;
+
+        /// Hash of the call each station's idempotency keys were used for.
+        ProcessedKeys get(processed_key): map (T::AccountId, IdempotencyKey) => Option<T::Hash>;
+        KeysProcessedAt get(keys_processed_at): map T::BlockNumber => Vec<(T::AccountId, IdempotencyKey)>;
+        /// Blocks an idempotency key is remembered for.
+        KeyRetentionBlocks get(key_retention_blocks) config(): T::BlockNumber;
+
+        /// Receipts of recent trades by receipt hash, for checking printed receipts.
+        Receipts get(receipt): map T::Hash => Option<ReceiptInfo<T::Hash, T::BlockNumber>>;
+        ReceiptsIssuedAt get(receipts_issued_at): map T::BlockNumber => Vec<T::Hash>;
+        /// Blocks a trade receipt can be verified for.
+        ReceiptRetentionBlocks get(receipt_retention_blocks) config(): T::BlockNumber;
+
+        /// Block of the last call each account signed.
+        LastActivity get(last_activity): map T::AccountId => T::BlockNumber;
+        /// Blocks without any call before an account counts as abandoned.
+        AbandonmentPeriod get(abandonment_period) config(): T::BlockNumber;
+        EscheatNoticePeriod get(escheat_notice_period) config(): T::BlockNumber;
+        /// Community account that receives escheated batteries.
+        EscheatPool get(escheat_pool) config(): T::AccountId;
+        /// Block from which an abandoned account's batteries may be escheated.
+        EscheatNotices get(escheat_notice): map T::AccountId => Option<T::BlockNumber>;
+
+        MaintenanceLogCount get(maintenance_log_count): map T::Hash => u64;
+        /// Bumped by every call that changes a battery or anything kept about it, so
+        /// off-chain signers can tie a payload to the battery's current state.
+        BatteryNonce get(battery_nonce): map T::Hash => u64;
+        /// Hash of each battery's `BatteryPassport`, refreshed on every change to it.
+        PassportHash get(passport_hash): map T::Hash => T::Hash;
+
+        MaintenanceLogs get(maintenance_log): map (T::Hash, u64) => Option<MaintenanceRecord<T::AccountId, T::Hash, T::BlockNumber>>;
+    }
+}
+
+/// `decl_module!` can't `cfg` single calls, so the module is declared through this macro
+/// and the test-helper calls are passed in only when the `test-helpers` feature is on.
+/// They come last, so every other call keeps its index with or without the feature.
+macro_rules! decl_battery_module {
+    ($($test_helpers:tt)*) => {
+decl_module! {
+    pub struct Module<T: Trait> for enum Call where origin: T::Origin {
+        fn deposit_event<T>() = default;
+
+        fn on_initialize(_n: T::BlockNumber) {
+            Self::migrate_to_compact_batteries();
+        }
+
+        fn on_finalize(n: T::BlockNumber) {
+            Self::update_merkle_root();
+            Self::checkpoint_ownership(n);
+            Self::prune_idempotency_keys(n);
+            Self::prune_receipts(n);
+            Self::expire_rebalances(n);
+            Self::expire_audits(n);
+            Self::start_audit_round(n);
+        }
+
+        fn offchain_worker(n: T::BlockNumber) {
+            Self::submit_audits(n);
+        }
+
+        pub fn register_station(origin) -> Result {
+            let sender = ensure_signed(origin)?;
+            Self::do_register_station(sender.clone())?;
+            Self::note_activity(&sender);
+            Ok(())
+        }
+
+        /// Deprecated, use `register_battery`.
+        pub fn registry_battery(origin, owner: T::AccountId) -> Result {
+            let sender = ensure_signed(origin)?;
+            Self::ensure_migrated()?;
+            Self::do_register_battery(sender.clone(), owner, false)?;
+            Self::note_activity(&sender);
+            Self::deposit_event(RawEvent::DeprecatedCallUsed(DeprecatedCall::RegistryBattery));
+            Ok(())
+        }
+
+        /// Deprecated, use `set_tradable`.
+        pub fn switch_tradable(origin, id: T::Hash) -> Result {
+            let sender = ensure_signed(origin)?;
+            Self::ensure_migrated()?;
+            let tradable = !Self::batteries(id).is_tradable();
+            Self::do_set_tradable(sender.clone(), id, tradable)?;
+            Self::note_activity(&sender);
+            Self::deposit_event(RawEvent::DeprecatedCallUsed(DeprecatedCall::SwitchTradable));
+            Ok(())
+        }
+
+        /// May be signed by the station or its session key. Resubmitting the call with the
+        /// same `idempotency_key` succeeds without storing the battery again.
+        pub fn store_to_station(origin, id: T::Hash, idempotency_key: Option<IdempotencyKey>) -> Result {
+            let sender = ensure_signed(origin)?;
+            Self::ensure_migrated()?;
+            let payload = (&b"store"[..], id).using_encoded(<T as system::Trait>::Hashing::hash);
+            if Self::is_replay(&Self::custodial_station(sender.clone())?, idempotency_key, payload)? {
+                return Ok(());
+            }
+            let station = Self::can_store(&sender, id)?;
+
+            // change state
+            Self::do_store(&sender, station.clone(), id);
+            Self::record_key(&station, idempotency_key, payload);
+            Self::note_activity(&sender);
+            Ok(())
+        }
+
+        pub fn fetch_from_station(origin, id: T::Hash) -> Result {
+            let sender = ensure_signed(origin)?;
+            Self::ensure_migrated()?;
+            let station = Self::can_fetch(&sender, id)?;
+            let mut battery = Self::batteries(id);
+            let before = battery.custody.clone();
+            battery.custody = Custody::Owner;
+
+            // change state
+            Self::remove_from_station(&station, id);
+            Self::put_battery(id, battery.clone());
+            Self::note_custody_change(&before, &battery.custody);
+            Self::stop_staking(id, &battery.owner);
+            Self::record_swap(&station);
+            Self::audit(&station, Some(sender.clone()), StationAction::BatteryFetched(id));
+            Self::bump_nonce(id);
+            Self::note_activity(&sender);
+
+            Self::deposit_event(RawEvent::FetchFromStation(id, sender, battery.owner));
+            Ok(())
+        }
+
+        /// Resubmitting the call with the same `idempotency_key` succeeds without
+        /// trading the battery again.
+        pub fn trade_battery(origin, id: T::Hash, to: T::AccountId, idempotency_key: Option<IdempotencyKey>) -> Result {
+            let sender = ensure_signed(origin)?;
+            Self::ensure_migrated()?;
+            let payload = (&b"trade"[..], id, &to).using_encoded(<T as system::Trait>::Hashing::hash);
+            if Self::is_replay(&sender, idempotency_key, payload)? {
+                return Ok(());
+            }
+            Self::can_trade(&sender, id, &to)?;
+            let mut battery = Self::batteries(id);
+            let from = battery.owner.clone();
+            battery.owner = to.clone();
+            let before = battery.custody.clone();
+            battery.custody = Custody::Station(sender.clone());
+
+            // change state
+            Self::note_custody_change(&before, &battery.custody);
+            <Stats<T>>::mutate(|stats| stats.trades += 1);
+            Self::count_probation_trade(&sender);
+            Self::put_battery(id, battery);
+            Self::start_staking(id);
+            Self::remove_owned(&from, id);
+            Self::add_owned(&to, id);
+
+            Self::audit(&sender, Some(sender.clone()), StationAction::BatteryTraded(id));
+            Self::record_key(&sender, idempotency_key, payload);
+            let receipt = Self::issue_receipt(id, &from, &to);
+            Self::issue_warranty_certificate(id, &to);
+            Self::bump_nonce(id);
+            Self::note_activity(&sender);
+            Self::notify_transfer(id, &from, &to);
+
+            Self::deposit_event(RawEvent::Trade(id, from, to, sender, receipt));
+            Ok(())
+        }
+
+        /// Publish where the station is so riders can find it. Coordinates are in microdegrees.
+        pub fn set_station_location(origin, lat: i64, lon: i64) -> Result {
+            let sender = ensure_signed(origin)?;
+            ensure!(<StationsIndex<T>>::exists(sender.clone()), "Sender is not a station");
+            ensure!(lat >= -90_000_000 && lat <= 90_000_000, "Latitude out of range");
+            ensure!(lon >= -180_000_000 && lon <= 180_000_000, "Longitude out of range");
+
+            <StationLocation<T>>::insert(sender.clone(), (lat, lon));
+            Self::audit(&sender, Some(sender.clone()), StationAction::LocationSet);
+            Self::note_activity(&sender);
+
+            Self::deposit_event(RawEvent::StationLocationSet(sender, lat, lon));
+            Ok(())
+        }
+
+        /// Designate the hot key a station's kiosk signs custody operations with.
+        /// The previous key, if any, stops working in the same call.
+        pub fn set_session_key(origin, key: T::AccountId) -> Result {
+            let sender = ensure_signed(origin)?;
+            ensure!(<StationsIndex<T>>::exists(sender.clone()), "Sender is not a station");
+            ensure!(!<StationsIndex<T>>::exists(key.clone()), "Session key can't be a station");
+            ensure!(!<KeyToStation<T>>::exists(key.clone()), "Session key already in use");
+
+            if let Some(old_key) = Self::session_key(sender.clone()) {
+                <KeyToStation<T>>::remove(old_key);
+            }
+            <SessionKeys<T>>::insert(sender.clone(), key.clone());
+            <KeyToStation<T>>::insert(key.clone(), sender.clone());
+            Self::audit(&sender, Some(sender.clone()), StationAction::SessionKeySet);
+            Self::note_activity(&sender);
+
+            Self::deposit_event(RawEvent::SessionKeySet(sender, key));
+            Ok(())
+        }
+
+        /// Publish a battery model, with the sender as its manufacturer.
+        pub fn register_model(origin, name: Vec<u8>) -> Result {
+            let sender = ensure_signed(origin)?;
+            ensure!(!name.is_empty(), "Model name can't be empty");
+            ensure!(name.len() <= MAX_MODEL_NAME_LEN, "Model name too long");
+
+            let model_id = (&sender, &name).using_encoded(<T as system::Trait>::Hashing::hash);
+            ensure!(!<Models<T>>::exists(model_id), "Model already exists");
+            <Models<T>>::insert(model_id, BatteryModel { manufacturer: sender.clone(), name });
+            Self::note_activity(&sender);
+
+            Self::deposit_event(RawEvent::ModelRegistered(model_id, sender));
+            Ok(())
+        }
+
+        /// Tag a battery the manufacturer still owns with one of its models.
+        pub fn set_battery_model(origin, id: T::Hash, model_id: T::Hash) -> Result {
+            let sender = ensure_signed(origin)?;
+            Self::ensure_migrated()?;
+            Self::ensure_not_in_flight(id)?;
+            let model = Self::model(model_id).ok_or("Model does not exist")?;
+            ensure!(model.manufacturer == sender, "You are not the manufacturer of this model");
+            ensure!(<Batteries<T>>::exists(id), "Battery does not exist");
+            ensure!(Self::batteries(id).owner == sender, "You are not the owner of this battery");
+            ensure!(!<BatteryModelOf<T>>::exists(id), "Battery model already set");
+
+            Self::set_model(id, &sender, model_id);
+            Self::bump_nonce(id);
+            Self::note_activity(&sender);
+
+            Self::deposit_event(RawEvent::BatteryModelSet(id, model_id));
+            Ok(())
+        }
+
+        /// Lock `price` towards a battery of `model_id`, to be delivered by its manufacturer.
+        pub fn place_preorder(origin, model_id: T::Hash, price: BalanceOf<T>) -> Result {
+            let sender = ensure_signed(origin)?;
+            Self::ensure_feature_enabled(Feature::PreOrders)?;
+            ensure!(<Models<T>>::exists(model_id), "Model does not exist");
+
+            let nonce = Self::preorder_nonce();
+            let preorder_id = (&sender, model_id, nonce).using_encoded(<T as system::Trait>::Hashing::hash);
+            T::Currency::reserve(&sender, price)?;
+
+            let unlock_at = <system::Module<T>>::block_number() + Self::preorder_lock_period();
+            <PreOrders<T>>::insert(preorder_id, PreOrder {
+                buyer: sender.clone(),
+                model_id,
+                locked_funds: price,
+                unlock_at,
+            });
+            <PreOrdersOf<T>>::mutate(sender.clone(), |preorders| preorders.push(preorder_id));
+            <PreOrderNonce<T>>::put(nonce + 1);
+            Self::note_activity(&sender);
+
+            Self::deposit_event(RawEvent::PreOrderPlaced(preorder_id, sender, model_id, price));
+            Ok(())
+        }
+
+        /// Deliver one of the manufacturer's batteries of the ordered model and collect the funds.
+        pub fn fulfill_preorder(origin, preorder_id: T::Hash, battery_id: T::Hash) -> Result {
+            let sender = ensure_signed(origin)?;
+            Self::ensure_migrated()?;
+            Self::ensure_not_in_flight(battery_id)?;
+            let preorder = Self::preorder(preorder_id).ok_or("Pre-order does not exist")?;
+            let model = Self::model(preorder.model_id).ok_or("Model does not exist")?;
+            ensure!(model.manufacturer == sender, "You are not the manufacturer of this model");
+            ensure!(<Batteries<T>>::exists(battery_id), "Battery does not exist");
+            let mut battery = Self::batteries(battery_id);
+            ensure!(battery.owner == sender, "You are not the owner of this battery");
+            ensure!(Self::battery_model(battery_id) == Some(preorder.model_id), "Battery is not of the ordered model");
+            ensure!(!<BridgeRequestOf<T>>::exists(battery_id), "Battery is locked in the bridge");
+            Self::ensure_transferable(battery_id)?;
+            Self::ensure_transfer_approved(battery_id, &preorder.buyer)?;
+            ensure!(
+                T::Currency::reserved_balance(&preorder.buyer) >= preorder.locked_funds,
+                "Pre-order funds are no longer reserved"
+            );
+
+            let remainder = T::Currency::repatriate_reserved(&preorder.buyer, &sender, preorder.locked_funds)?;
+            ensure!(remainder.is_zero(), "Pre-order funds are no longer reserved");
+
+            // change state
+            Self::stop_staking(battery_id, &sender);
+            battery.owner = preorder.buyer.clone();
+            let before = battery.custody.clone();
+            if let Custody::Tradable(station) = battery.custody {
+                battery.custody = Custody::Station(station);
+            }
+            Self::note_custody_change(&before, &battery.custody);
+            if battery.station().is_some() {
+                Self::start_staking(battery_id);
+            }
+            Self::put_battery(battery_id, battery);
+            Self::remove_owned(&sender, battery_id);
+            Self::add_owned(&preorder.buyer, battery_id);
+            <PreOrders<T>>::remove(preorder_id);
+            <PreOrdersOf<T>>::mutate(preorder.buyer.clone(), |preorders| preorders.retain(|open| *open != preorder_id));
+            Self::bump_nonce(battery_id);
+            Self::note_activity(&sender);
+            Self::notify_transfer(battery_id, &sender, &preorder.buyer);
+
+            Self::deposit_event(RawEvent::PreOrderFulfilled(preorder_id, battery_id));
+            Ok(())
+        }
+
+        /// Give up on a pre-order once its lock period is over.
+        pub fn cancel_preorder(origin, preorder_id: T::Hash) -> Result {
+            let sender = ensure_signed(origin)?;
+            let preorder = Self::preorder(preorder_id).ok_or("Pre-order does not exist")?;
+            ensure!(preorder.buyer == sender, "You are not the buyer of this pre-order");
+            ensure!(<system::Module<T>>::block_number() >= preorder.unlock_at, "Pre-order is still locked");
+
+            T::Currency::unreserve(&sender, preorder.locked_funds);
+            <PreOrders<T>>::remove(preorder_id);
+            <PreOrdersOf<T>>::mutate(sender.clone(), |preorders| preorders.retain(|open| *open != preorder_id));
+            Self::note_activity(&sender);
+
+            Self::deposit_event(RawEvent::PreOrderCancelled(preorder_id));
+            Ok(())
+        }
+
+        /// Mint the governance tokens a staked battery has earned so far to its owner.
+        pub fn claim_staking_rewards(origin, id: T::Hash) -> Result {
+            let sender = ensure_signed(origin)?;
+            Self::ensure_migrated()?;
+            Self::ensure_not_in_flight(id)?;
+            ensure!(<Batteries<T>>::exists(id), "Battery does not exist");
+            ensure!(Self::batteries(id).owner == sender, "You are not the owner of this battery");
+            ensure!(<StakeStart<T>>::exists(id), "Battery is not staked");
+
+            let reward = Self::pending_staking_rewards(id);
+            Self::start_staking(id);
+            Self::mint_rewards(id, &sender, reward);
+            Self::bump_nonce(id);
+            Self::note_activity(&sender);
+            Ok(())
+        }
+
+        /// Register a battery owned by `owner`, held by the sending station.
+        pub fn register_battery(origin, owner: T::AccountId) -> Result {
+            let sender = ensure_signed(origin)?;
+            Self::ensure_migrated()?;
+            Self::do_register_battery(sender.clone(), owner, false)?;
+            Self::note_activity(&sender);
+            Ok(())
+        }
+
+        /// Open a battery held by a station for trade, or withdraw it again.
+        pub fn set_tradable(origin, id: T::Hash, tradable: bool) -> Result {
+            let sender = ensure_signed(origin)?;
+            Self::ensure_migrated()?;
+            Self::do_set_tradable(sender.clone(), id, tradable)?;
+            Self::note_activity(&sender);
+            Ok(())
+        }
+
+        pub fn set_trust_level(origin, station: T::AccountId, level: TrustLevel) -> Result {
+            ensure_root(origin)?;
+            ensure!(<StationsIndex<T>>::exists(station.clone()), "Not a station!");
+
+            <TrustLevels<T>>::insert(station.clone(), level);
+            Self::audit(&station, None, StationAction::TrustLevelSet(level));
+
+            Self::deposit_event(RawEvent::TrustLevelSet(station, level));
+            Ok(())
+        }
+
+        /// Record that a premium station refitted a battery it holds with a cell pack
+        /// of another model.
+        pub fn upgrade_battery_model(origin, id: T::Hash, new_model_id: T::Hash) -> Result {
+            let sender = ensure_signed(origin)?;
+            Self::ensure_migrated()?;
+            Self::ensure_not_in_flight(id)?;
+            ensure!(Self::trust_level(sender.clone()) == TrustLevel::Premium, "Station must be premium");
+            ensure!(<Batteries<T>>::exists(id), "Battery does not exist");
+            ensure!(Self::batteries(id).station() == Some(&sender), "Sender must be the station of this battery");
+            ensure!(<Models<T>>::exists(new_model_id), "Model does not exist");
+            let old_model_id = Self::battery_model(id);
+            ensure!(old_model_id != Some(new_model_id), "Battery is already of this model");
+
+            Self::set_model(id, &Self::batteries(id).owner, new_model_id);
+            Self::log_maintenance(id, sender.clone(), MaintenanceKind::ModelUpgrade(old_model_id, new_model_id));
+            Self::audit(&sender, Some(sender.clone()), StationAction::ModelUpgraded(id));
+            Self::bump_nonce(id);
+            Self::note_activity(&sender);
+
+            Self::deposit_event(RawEvent::BatteryModelUpgraded(id, new_model_id));
+            Ok(())
+        }
+
+        /// Hand a battery whose owner account was reaped to `new_owner`. Custody is kept
+        /// as it is; staking rewards accrued by the dead owner are forfeited.
+        pub fn reassign_orphaned(origin, id: T::Hash, new_owner: T::AccountId) -> Result {
+            ensure_root(origin)?;
+            Self::ensure_migrated()?;
+            Self::ensure_not_in_flight(id)?;
+            ensure!(<Batteries<T>>::exists(id), "Battery does not exist");
+            let mut battery = Self::batteries(id);
+            ensure!(!T::AccountStatus::is_alive(&battery.owner), "Owner account is still active");
+            ensure!(T::AccountStatus::is_alive(&new_owner), "Owner account is inactive");
+            let from = battery.owner.clone();
+            battery.owner = new_owner.clone();
+
+            Self::put_battery(id, battery);
+            if <StakeStart<T>>::exists(id) {
+                Self::start_staking(id);
+            }
+            Self::remove_owned(&from, id);
+            Self::add_owned(&new_owner, id);
+            Self::bump_nonce(id);
+            Self::notify_transfer(id, &from, &new_owner);
+
+            Self::deposit_event(RawEvent::OrphanRescued(id, from, new_owner));
+            Ok(())
+        }
+
+        pub fn add_co_owner(origin, id: T::Hash, co_owner: T::AccountId) -> Result {
+            let sender = ensure_signed(origin)?;
+            Self::ensure_migrated()?;
+            Self::ensure_not_in_flight(id)?;
+            ensure!(<Batteries<T>>::exists(id), "Battery does not exist");
+            ensure!(Self::batteries(id).owner == sender, "You are not the owner of this battery");
+            ensure!(co_owner != sender, "Owner can't be a co-owner");
+            let mut co_owners = Self::co_owners(id);
+            ensure!(!co_owners.contains(&co_owner), "Already a co-owner");
+            ensure!(co_owners.len() < MAX_CO_OWNERS, "Too many co-owners");
+            co_owners.push(co_owner.clone());
+
+            <CoOwners<T>>::insert(id, co_owners);
+            Self::bump_nonce(id);
+            Self::note_activity(&sender);
+
+            Self::deposit_event(RawEvent::CoOwnerAdded(id, co_owner));
+            Ok(())
+        }
+
+        pub fn remove_co_owner(origin, id: T::Hash, co_owner: T::AccountId) -> Result {
+            let sender = ensure_signed(origin)?;
+            Self::ensure_migrated()?;
+            Self::ensure_not_in_flight(id)?;
+            ensure!(<Batteries<T>>::exists(id), "Battery does not exist");
+            ensure!(Self::batteries(id).owner == sender, "You are not the owner of this battery");
+            let mut co_owners = Self::co_owners(id);
+            let index = co_owners.iter().position(|c| *c == co_owner).ok_or("Not a co-owner")?;
+            co_owners.swap_remove(index);
+
+            if co_owners.is_empty() {
+                Self::clear_co_owners(id);
+            } else {
+                <CoOwners<T>>::insert(id, co_owners);
+                <Approvals<T>>::mutate(id, |approvals| approvals.retain(|(who, _)| *who != co_owner));
+            }
+            Self::bump_nonce(id);
+            Self::note_activity(&sender);
+
+            Self::deposit_event(RawEvent::CoOwnerRemoved(id, co_owner));
+            Ok(())
+        }
+
+        /// Approve `action_hash`, replacing any earlier approval by the same co-owner.
+        /// Transfers are identified by `transfer_action_hash`.
+        pub fn approve_action(origin, id: T::Hash, action_hash: T::Hash) -> Result {
+            let sender = ensure_signed(origin)?;
+            Self::ensure_migrated()?;
+            Self::ensure_not_in_flight(id)?;
+            ensure!(Self::co_owners(id).contains(&sender), "Not a co-owner");
+
+            <Approvals<T>>::mutate(id, |approvals| {
+                approvals.retain(|(who, _)| *who != sender);
+                approvals.push((sender.clone(), action_hash));
+            });
+            Self::bump_nonce(id);
+            Self::note_activity(&sender);
+
+            Self::deposit_event(RawEvent::ActionApproved(id, sender, action_hash));
+            Ok(())
+        }
+
+        /// Set how many blocks after registration batteries of `model_id` stay under warranty.
+        pub fn set_model_warranty(origin, model_id: T::Hash, blocks: T::BlockNumber) -> Result {
+            let sender = ensure_signed(origin)?;
+            Self::ensure_feature_enabled(Feature::Warranties)?;
+            let model = Self::model(model_id).ok_or("Model does not exist")?;
+            ensure!(model.manufacturer == sender, "You are not the manufacturer of this model");
+
+            <ModelWarranty<T>>::insert(model_id, blocks);
+            Self::note_activity(&sender);
+
+            Self::deposit_event(RawEvent::ModelWarrantySet(model_id, blocks));
+            Ok(())
+        }
+
+        /// Claim to be the rightful owner of a stolen battery. Trading and storing it are
+        /// frozen for `ChallengePeriodBlocks` while the arbitrator looks into it.
+        pub fn challenge_battery_ownership(origin, id: T::Hash) -> Result {
+            let sender = ensure_signed(origin)?;
+            Self::ensure_migrated()?;
+            Self::ensure_not_in_flight(id)?;
+            ensure!(<Batteries<T>>::exists(id), "Battery does not exist");
+            ensure!(Self::batteries(id).owner != sender, "You already own this battery");
+            ensure!(!<ChallengeOf<T>>::exists(id), "Battery is already challenged");
+
+            let now = <system::Module<T>>::block_number();
+            let challenge_id = (id, sender.clone(), now).using_encoded(<T as system::Trait>::Hashing::hash);
+            <PendingChallenges<T>>::insert(challenge_id, OwnershipChallenge {
+                battery_id: id,
+                challenger: sender.clone(),
+                ends_at: now + Self::challenge_period_blocks(),
+            });
+            <ChallengeOf<T>>::insert(id, challenge_id);
+            Self::bump_nonce(id);
+            Self::note_activity(&sender);
+
+            Self::deposit_event(RawEvent::ChallengeFiled(challenge_id, sender));
+            Ok(())
+        }
+
+        /// Settle a challenge in favour of `winner`, who must be the challenger or the
+        /// current owner. A battery awarded to the challenger keeps its custody but is
+        /// withdrawn from trade, and rewards staked by the previous owner are forfeited.
+        pub fn resolve_challenge(origin, challenge_id: T::Hash, winner: T::AccountId) -> Result {
+            T::ArbitratorOrigin::ensure_origin(origin)?;
+            Self::ensure_migrated()?;
+            let challenge = Self::challenge(challenge_id).ok_or("Challenge does not exist")?;
+            let id = challenge.battery_id;
+            Self::ensure_not_in_flight(id)?;
+            let mut battery = Self::batteries(id);
+            let from = battery.owner.clone();
+            ensure!(winner == challenge.challenger || winner == from, "Winner must be a party to the challenge");
+
+            if winner != from {
+                battery.owner = winner.clone();
+                let before = battery.custody.clone();
+                if let Custody::Tradable(station) = battery.custody {
+                    battery.custody = Custody::Station(station);
+                }
+                Self::note_custody_change(&before, &battery.custody);
+                if battery.station().is_some() {
+                    Self::start_staking(id);
+                }
+                Self::put_battery(id, battery);
+                Self::remove_owned(&from, id);
+                Self::add_owned(&winner, id);
+            }
+            <PendingChallenges<T>>::remove(challenge_id);
+            <ChallengeOf<T>>::remove(id);
+            <ChallengeEvidence<T>>::remove(challenge_id);
+            Self::bump_nonce(id);
+            if winner != from {
+                Self::notify_transfer(id, &from, &winner);
+            }
+
+            Self::deposit_event(RawEvent::ChallengeResolved(challenge_id, winner));
+            Ok(())
+        }
+
+        /// Take a battery out of service for good. Its id is kept as a tombstone and
+        /// can't be registered again.
+        pub fn decommission_battery(origin, id: T::Hash, reason: DecommissionReason) -> Result {
+            let sender = ensure_signed(origin)?;
+            Self::ensure_migrated()?;
+            Self::ensure_not_in_flight(id)?;
+            ensure!(<Batteries<T>>::exists(id), "Battery does not exist");
+            ensure!(Self::batteries(id).owner == sender, "You are not the owner of this battery");
+            ensure!(!<BridgeRequestOf<T>>::exists(id), "Battery is locked in the bridge");
+            ensure!(reason != DecommissionReason::Duplicate, "Only root removes duplicates");
+            ensure!(!<PendingCompensations<T>>::exists(id), "Claim the recall compensation first");
+            Self::ensure_approved(id, Self::decommission_action_hash(id, reason))?;
+            Self::note_activity(&sender);
+            Self::do_decommission(id, reason);
+            Ok(())
+        }
+
+        /// Remove a battery registered twice by mistake, without keeping a tombstone.
+        pub fn remove_duplicate_battery(origin, id: T::Hash) -> Result {
+            ensure_root(origin)?;
+            Self::ensure_migrated()?;
+            Self::ensure_not_in_flight(id)?;
+            ensure!(<Batteries<T>>::exists(id), "Battery does not exist");
+            Self::do_decommission(id, DecommissionReason::Duplicate);
+            Ok(())
+        }
+
+        /// Limit when batteries can be stored at or fetched from the station. `schedule`
+        /// holds seven days starting on Monday, each with `(start_minute, end_minute)`
+        /// windows in UTC; the end minute is exclusive.
+        pub fn set_opening_hours(origin, schedule: Vec<Vec<(u32, u32)>>) -> Result {
+            let sender = ensure_signed(origin)?;
+            ensure!(<StationsIndex<T>>::exists(sender.clone()), "Sender is not a station");
+            ensure!(schedule.len() == 7, "Opening hours must cover seven days");
+            for day in &schedule {
+                ensure!(day.len() <= MAX_OPENING_WINDOWS_PER_DAY, "Too many opening windows");
+                for &(start, end) in day {
+                    ensure!(start < end && end <= MINUTES_PER_DAY, "Invalid opening window");
+                }
+            }
+
+            <OpeningHours<T>>::insert(sender.clone(), schedule);
+            Self::audit(&sender, Some(sender.clone()), StationAction::OpeningHoursSet);
+            Self::note_activity(&sender);
+
+            Self::deposit_event(RawEvent::StationOpeningHoursSet(sender));
+            Ok(())
+        }
+
+        /// List the battery for trade every time it is stored in a station, until
+        /// disabled or the battery changes owner.
+        pub fn set_auto_relist(origin, id: T::Hash, enabled: bool) -> Result {
+            let sender = ensure_signed(origin)?;
+            Self::ensure_migrated()?;
+            Self::ensure_not_in_flight(id)?;
+            ensure!(<Batteries<T>>::exists(id), "Battery does not exist");
+            ensure!(Self::batteries(id).owner == sender, "You are not the owner of this battery");
+
+            if enabled {
+                Self::ensure_transferable(id)?;
+                let action_hash = Self::listing_action_hash(id);
+                Self::ensure_approved(id, action_hash)?;
+                Self::consume_approvals(id, action_hash);
+                <AutoRelist<T>>::insert(id, true);
+            } else {
+                <AutoRelist<T>>::remove(id);
+            }
+            Self::bump_nonce(id);
+            Self::note_activity(&sender);
+
+            Self::deposit_event(RawEvent::AutoRelistSet(id, enabled));
+            Ok(())
+        }
+
+        /// Recount `NetworkStats` in case the incremental updates drifted, scanning
+        /// up to `limit` batteries per call. The totals are replaced once the scan
+        /// reaches the end. Trades can't be recounted and keep their current value.
+        pub fn rebuild_stats(origin, limit: u64) -> Result {
+            ensure_root(origin)?;
+            Self::ensure_migrated()?;
+            ensure!(limit > 0, "Limit must be positive");
+            let (start, mut stats) = <StatsRebuild<T>>::get().unwrap_or_default();
+            let end = rstd::cmp::min(start.saturating_add(limit), Self::all_batteries_count());
+
+            for index in start..end {
+                let id = Self::battery_by_index(index);
+                if <Batteries<T>>::exists(id) {
+                    stats.add_battery(&Self::batteries(id).custody);
+                } else if <DecommissionedBatteries<T>>::exists(id) {
+                    stats.decommissioned += 1;
+                }
+            }
+
+            if end == Self::all_batteries_count() {
+                stats.stations = Self::stations_count();
+                stats.trades = Self::network_stats().trades;
+                <Stats<T>>::put(stats.clone());
+                <StatsRebuild<T>>::kill();
+                Self::deposit_event(RawEvent::StatsRebuilt(stats));
+            } else {
+                <StatsRebuild<T>>::put((end, stats));
+            }
+            Ok(())
+        }
+
+        /// Declare how many swaps per hour the station's hardware can handle.
+        pub fn set_swap_capacity(origin, capacity: u32) -> Result {
+            let sender = ensure_signed(origin)?;
+            ensure!(<StationsIndex<T>>::exists(sender.clone()), "Sender is not a station");
+
+            let previous = Self::swap_capacity(&sender).unwrap_or(0);
+            <NetworkSwapCapacity<T>>::mutate(|total| *total = total.saturating_sub(previous).saturating_add(capacity));
+            <StationSwapCapacity<T>>::insert(sender.clone(), capacity);
+            Self::audit(&sender, Some(sender.clone()), StationAction::SwapCapacitySet(capacity));
+            Self::note_activity(&sender);
+
+            Self::deposit_event(RawEvent::SwapCapacitySet(sender, capacity));
+            Ok(())
+        }
+
+        /// Lock a battery so a wrapped token can be minted for it on `dest_chain_id`.
+        /// The battery leaves its station and can't be stored again until unlocked.
+        pub fn lock_for_bridge(origin, id: T::Hash, dest_chain_id: u64, dest_address: Vec<u8>) -> Result {
+            let sender = ensure_signed(origin)?;
+            Self::ensure_migrated()?;
+            Self::ensure_feature_enabled(Feature::Bridge)?;
+            Self::ensure_not_in_flight(id)?;
+            ensure!(<Batteries<T>>::exists(id), "Battery does not exist");
+            let mut battery = Self::batteries(id);
+            ensure!(battery.owner == sender, "You are not the owner of this battery");
+            ensure!(!<BridgeRequestOf<T>>::exists(id), "Battery is locked in the bridge");
+            ensure!(!dest_address.is_empty() && dest_address.len() <= MAX_BRIDGE_ADDRESS_LEN, "Invalid destination address");
+
+            let request_id = (id, sender.clone(), <system::Module<T>>::block_number())
+                .using_encoded(<T as system::Trait>::Hashing::hash);
+
+            // change state
+            if let Some(station) = battery.station().cloned() {
+                let before = battery.custody.clone();
+                battery.custody = Custody::Owner;
+                Self::remove_from_station(&station, id);
+                Self::put_battery(id, battery.clone());
+                Self::note_custody_change(&before, &battery.custody);
+                Self::stop_staking(id, &sender);
+                Self::audit(&station, Some(sender.clone()), StationAction::BatteryRemoved(id));
+            }
+            <PendingBridgeRequests<T>>::insert(request_id, BridgeRequest {
+                battery_id: id,
+                locker: sender.clone(),
+                dest_chain_id,
+                dest_address,
+                confirmation: None,
+            });
+            <BridgeRequestOf<T>>::insert(id, request_id);
+            Self::bump_nonce(id);
+            Self::note_activity(&sender);
+
+            Self::deposit_event(RawEvent::BridgeLocked(request_id, id));
+            Ok(())
+        }
+
+        /// Record that the wrapped token was minted by transaction `tx_hash`.
+        pub fn confirm_bridge(origin, request_id: T::Hash, tx_hash: Vec<u8>) -> Result {
+            T::BridgeOrigin::ensure_origin(origin)?;
+            Self::ensure_migrated()?;
+            let mut request = Self::bridge_request(request_id).ok_or("Bridge request does not exist")?;
+            ensure!(request.confirmation.is_none(), "Bridge request already confirmed");
+            ensure!(!tx_hash.is_empty() && tx_hash.len() <= MAX_BRIDGE_ADDRESS_LEN, "Invalid transaction hash");
+            request.confirmation = Some(tx_hash.clone());
+
+            Self::bump_nonce(request.battery_id);
+            <PendingBridgeRequests<T>>::insert(request_id, request);
+
+            Self::deposit_event(RawEvent::BridgeConfirmed(request_id, tx_hash));
+            Ok(())
+        }
+
+        /// Release a battery once the relayer saw its wrapped token burnt. `proof` is
+        /// checked by the relayer and not interpreted on chain.
+        pub fn unlock_from_bridge(origin, id: T::Hash, proof: Vec<u8>) -> Result {
+            T::BridgeOrigin::ensure_origin(origin)?;
+            Self::ensure_migrated()?;
+            let request_id = Self::bridge_request_of(id).ok_or("Battery is not locked in the bridge")?;
+            let request = Self::bridge_request(request_id).ok_or("Bridge request does not exist")?;
+            ensure!(request.confirmation.is_some(), "Bridge request is not confirmed");
+            ensure!(!proof.is_empty(), "Missing burn proof");
+
+            <PendingBridgeRequests<T>>::remove(request_id);
+            <BridgeRequestOf<T>>::remove(id);
+
+            Self::bump_nonce(id);
+            Self::deposit_event(RawEvent::BridgeUnlocked(id));
+            Ok(())
+        }
+
+        /// Register as a station that can also charge the batteries it holds.
+        pub fn register_charging_station(origin) -> Result {
+            let sender = ensure_signed(origin)?;
+            Self::ensure_feature_enabled(Feature::Charging)?;
+            Self::do_register_station(sender.clone())?;
+            <IsChargingStation<T>>::insert(sender.clone(), true);
+            Self::note_activity(&sender);
+
+            Self::deposit_event(RawEvent::ChargingStationRegistered(sender));
+            Ok(())
+        }
+
+        /// Start charging a held battery to `target` percent.
+        pub fn set_target_soc(origin, id: T::Hash, target: u8) -> Result {
+            let sender = ensure_signed(origin)?;
+            Self::ensure_migrated()?;
+            Self::ensure_not_in_flight(id)?;
+            ensure!(Self::is_charging_station(&sender), "Sender is not a charging station");
+            ensure!(<Batteries<T>>::exists(id), "Battery does not exist");
+            ensure!(Self::batteries(id).station() == Some(&sender), "Sender must be the station of this battery");
+            ensure!(target <= 100, "Target state of charge must be a percentage");
+
+            <TargetSoc<T>>::insert(id, target);
+            Self::audit(&sender, Some(sender.clone()), StationAction::TargetSocSet(id, target));
+            Self::bump_nonce(id);
+            Self::note_activity(&sender);
+
+            Self::deposit_event(RawEvent::ChargingStarted(id, sender, target));
+            Ok(())
+        }
+
+        /// Lift a station out of probation before it reached the trade threshold.
+        pub fn graduate_station(origin, station: T::AccountId) -> Result {
+            ensure_root(origin)?;
+            ensure!(<StationsIndex<T>>::exists(station.clone()), "Not a station!");
+            ensure!(Self::station_status(&station) != StationStatus::Full, "Station is not on probation");
+            Self::graduate(station);
+            Ok(())
+        }
+
+        /// Recall a battery of one of the manufacturer's models, locking `compensation`
+        /// for whoever owns the battery when it is claimed. It may be zero.
+        pub fn recall_battery(origin, id: T::Hash, compensation: BalanceOf<T>) -> Result {
+            let sender = ensure_signed(origin)?;
+            Self::ensure_migrated()?;
+            Self::ensure_feature_enabled(Feature::Recalls)?;
+            ensure!(<Batteries<T>>::exists(id), "Battery does not exist");
+            let model_id = Self::battery_model(id).ok_or("Battery has no model")?;
+            let model = Self::model(model_id).ok_or("Model does not exist")?;
+            ensure!(model.manufacturer == sender, "You are not the manufacturer of this model");
+            ensure!(!Self::is_recalled(id), "Battery already recalled");
+
+            if !compensation.is_zero() {
+                T::Currency::reserve(&sender, compensation)?;
+                <PendingCompensations<T>>::insert(id, RecallCompensation { manufacturer: sender.clone(), amount: compensation });
+            }
+            <Recalled<T>>::insert(id, true);
+            Self::bump_nonce(id);
+            Self::note_activity(&sender);
+
+            Self::deposit_event(RawEvent::BatteryRecalled(id, compensation));
+            Ok(())
+        }
+
+        /// Collect the compensation the manufacturer locked when recalling the battery.
+        pub fn claim_recall_compensation(origin, id: T::Hash) -> Result {
+            let sender = ensure_signed(origin)?;
+            Self::ensure_migrated()?;
+            Self::ensure_not_in_flight(id)?;
+            ensure!(<Batteries<T>>::exists(id), "Battery does not exist");
+            ensure!(Self::batteries(id).owner == sender, "You are not the owner of this battery");
+            let compensation = Self::compensation(id).ok_or("No compensation to claim")?;
+
+            T::Currency::repatriate_reserved(&compensation.manufacturer, &sender, compensation.amount)?;
+            <PendingCompensations<T>>::remove(id);
+            Self::bump_nonce(id);
+            Self::note_activity(&sender);
+
+            Self::deposit_event(RawEvent::RecallCompensationClaimed(id, sender, compensation.amount));
+            Ok(())
+        }
+
+        /// Set how many co-owner approvals listing, transferring or decommissioning the
+        /// battery takes, instead of the network default.
+        pub fn set_co_owner_threshold(origin, id: T::Hash, threshold: u32) -> Result {
+            let sender = ensure_signed(origin)?;
+            Self::ensure_migrated()?;
+            Self::ensure_not_in_flight(id)?;
+            ensure!(<Batteries<T>>::exists(id), "Battery does not exist");
+            ensure!(Self::batteries(id).owner == sender, "You are not the owner of this battery");
+            ensure!(threshold > 0 && threshold as usize <= MAX_CO_OWNERS, "Invalid co-owner threshold");
+
+            <CoOwnerThreshold<T>>::insert(id, threshold);
+            Self::bump_nonce(id);
+            Self::note_activity(&sender);
+
+            Self::deposit_event(RawEvent::CoOwnerThresholdSet(id, threshold));
+            Ok(())
+        }
+
+        /// Give an account that made no call for `AbandonmentPeriod` blocks public notice
+        /// that its batteries will be escheated. Any call it makes cancels the notice.
+        pub fn begin_escheat(origin, owner: T::AccountId) -> Result {
+            T::AdminOrigin::ensure_origin(origin)?;
+            Self::ensure_migrated()?;
+            Self::ensure_feature_enabled(Feature::Escheat)?;
+            ensure!(Self::owned_batteries_count(&owner) > 0, "Account owns no batteries");
+            ensure!(!<EscheatNotices<T>>::exists(&owner), "Escheat already noticed");
+            let now = <system::Module<T>>::block_number();
+            ensure!(now >= Self::last_activity(&owner) + Self::abandonment_period(), "Account is not abandoned");
+
+            let ends_at = now + Self::escheat_notice_period();
+            <EscheatNotices<T>>::insert(owner.clone(), ends_at);
+
+            Self::deposit_event(RawEvent::EscheatNoticed(owner, ends_at));
+            Ok(())
+        }
+
+        /// Hand up to `limit` of a noticed account's batteries to the escheat pool once
+        /// the notice period is over. Repeat until the account owns none.
+        pub fn complete_escheat(origin, owner: T::AccountId, limit: u32) -> Result {
+            T::AdminOrigin::ensure_origin(origin)?;
+            Self::ensure_migrated()?;
+            let ends_at = Self::escheat_notice(&owner).ok_or("No escheat notice for this account")?;
+            ensure!(<system::Module<T>>::block_number() >= ends_at, "Escheat notice period is not over");
+            ensure!(limit > 0, "Limit must be positive");
+            let pool = Self::escheat_pool();
+            ensure!(pool != owner, "Pool can't be escheated");
+
+            let owned = Self::owned_batteries_count(&owner);
+            let count = rstd::cmp::min(rstd::cmp::min(limit, MAX_ESCHEAT_CHUNK) as u64, owned);
+            let ids: Vec<T::Hash> = (owned - count..owned)
+                .rev()
+                .map(|index| Self::battery_of_owner_by_index((owner.clone(), index)))
+                .collect();
+
+            // change state
+            if count == owned {
+                <EscheatNotices<T>>::remove(&owner);
+            }
+            for &id in &ids {
+                Self::escheat_battery(id, &owner, &pool);
+            }
+            // Hooks only run once every battery of the chunk has moved, so one that
+            // touches another battery of the owner can't disturb the loop.
+            for id in ids {
+                Self::notify_transfer(id, &owner, &pool);
+            }
+
+            Self::deposit_event(RawEvent::BatteriesEscheated(owner, count as u32));
+            Ok(())
+        }
+
+        /// Switch an optional feature on or off, overriding `Feature::enabled_by_default`.
+        pub fn set_feature_enabled(origin, feature: Feature, enabled: bool) -> Result {
+            T::AdminOrigin::ensure_origin(origin)?;
+
+            <FeatureFlags<T>>::insert(feature, enabled);
+
+            Self::deposit_event(RawEvent::FeatureEnabledSet(feature, enabled));
+            Ok(())
+        }
+
+        /// Report `station` for misconduct, locking `stake` until the reports are ruled on.
+        /// Once the stake reported within the current era reaches `ReviewStakeThreshold`,
+        /// the station is placed under review and may no longer trade.
+        pub fn report_station(
+            origin,
+            station: T::AccountId,
+            category: ReportCategory,
+            evidence_hash: T::Hash,
+            stake: BalanceOf<T>
+        ) -> Result {
+            let sender = ensure_signed(origin)?;
+            Self::ensure_feature_enabled(Feature::StationReports)?;
+            ensure!(<StationsIndex<T>>::exists(station.clone()), "Not a station!");
+            ensure!(sender != station, "Station can't report itself");
+            ensure!(!stake.is_zero(), "Report needs a stake");
+            let era = Self::report_era();
+            let filed = Self::reports_filed((sender.clone(), era));
+            ensure!(filed < MAX_REPORTS_PER_ERA, "Too many reports this era");
+            let mut reports = Self::station_reports(&station);
+            ensure!(reports.len() < MAX_PENDING_REPORTS, "Too many pending reports");
+
+            T::Currency::reserve(&sender, stake)?;
+            reports.push(StationReport { reporter: sender.clone(), category, evidence_hash, stake });
+            <StationReports<T>>::insert(station.clone(), reports);
+            <ReportsFiled<T>>::insert((sender.clone(), era), filed + 1);
+            let reported = Self::reported_stake((station.clone(), era)) + stake;
+            <ReportedStake<T>>::insert((station.clone(), era), reported);
+            Self::note_activity(&sender);
+
+            Self::deposit_event(RawEvent::StationReported(station.clone(), sender, category, stake));
+            if reported >= Self::review_stake_threshold() {
+                Self::place_under_review(station);
+            }
+            Ok(())
+        }
+
+        /// Rule on every pending report against `station`. Upheld reports get their stakes
+        /// back and put the station back on probation, rejected ones lose their stakes.
+        /// Upholding reports leaves the station's bond alone, only missed audits slash it.
+        /// Also ends a review a missed audit started.
+        pub fn resolve_reports(origin, station: T::AccountId, uphold: bool) -> Result {
+            T::AdminOrigin::ensure_origin(origin)?;
+            let reports = Self::station_reports(&station);
+            ensure!(!reports.is_empty() || Self::is_under_review(&station), "No reports against this station");
+
+            for report in reports {
+                if uphold {
+                    T::Currency::unreserve(&report.reporter, report.stake);
+                } else {
+                    let _ = T::Currency::slash_reserved(&report.reporter, report.stake);
+                }
+            }
+            <StationReports<T>>::remove(&station);
+            <ReportedStake<T>>::remove((station.clone(), Self::report_era()));
+            <UnderReview<T>>::remove(&station);
+            if uphold {
+                <StationStatuses<T>>::insert(station.clone(), StationStatus::Probation { trades_completed: 0 });
+            }
+            Self::audit(&station, None, StationAction::ReportsResolved(uphold));
+
+            Self::deposit_event(RawEvent::ReportsResolved(station, uphold));
+            Ok(())
+        }
+
+        /// Anchor the content hash of a document about the battery, such as photos of its
+        /// condition at a handover. May be called by the owner or the station holding it.
+        pub fn anchor_document(origin, id: T::Hash, doc_hash: T::Hash, kind: DocKind) -> Result {
+            let sender = ensure_signed(origin)?;
+            Self::ensure_migrated()?;
+            Self::ensure_feature_enabled(Feature::Documents)?;
+            Self::ensure_not_in_flight(id)?;
+            ensure!(<Batteries<T>>::exists(id), "Battery does not exist");
+            let battery = Self::batteries(id);
+            ensure!(
+                battery.owner == sender || battery.station() == Some(&sender),
+                "Sender is neither the owner nor the station of this battery"
+            );
+            let index = Self::document_count(id);
+            ensure!(index < MAX_DOCUMENTS_PER_BATTERY, "Too many documents for this battery");
+            let period = Self::custody_period(id);
+            let in_period = match Self::custody_documents(id) {
+                (last_period, count) if last_period == period => count,
+                _ => 0,
+            };
+            ensure!(in_period < MAX_DOCUMENTS_PER_CUSTODY, "Too many documents in this custody period");
+
+            <Documents<T>>::insert((id, index), DocumentAnchor {
+                doc_hash,
+                kind,
+                anchored_by: sender.clone(),
+                at: <system::Module<T>>::block_number(),
+            });
+            <DocumentCount<T>>::insert(id, index + 1);
+            <CustodyDocuments<T>>::insert(id, (period, in_period + 1));
+            Self::note_activity(&sender);
+
+            Self::deposit_event(RawEvent::DocumentAnchored(id, index, sender));
+            Ok(())
+        }
+
+        /// Cite one of the challenged battery's documents for the arbitrator. The
+        /// challenger and the owner may both cite documents.
+        pub fn submit_evidence(origin, challenge_id: T::Hash, document_index: u32) -> Result {
+            let sender = ensure_signed(origin)?;
+            Self::ensure_migrated()?;
+            let challenge = Self::challenge(challenge_id).ok_or("Challenge does not exist")?;
+            let id = challenge.battery_id;
+            ensure!(
+                sender == challenge.challenger || sender == Self::batteries(id).owner,
+                "Sender is not a party to the challenge"
+            );
+            ensure!(<Documents<T>>::exists((id, document_index)), "Document does not exist");
+            let mut evidence = Self::challenge_evidence(challenge_id);
+            ensure!(!evidence.contains(&document_index), "Document already submitted");
+            ensure!(evidence.len() < MAX_CHALLENGE_EVIDENCE, "Too much evidence");
+            evidence.push(document_index);
+
+            <ChallengeEvidence<T>>::insert(challenge_id, evidence);
+            Self::note_activity(&sender);
+
+            Self::deposit_event(RawEvent::EvidenceSubmitted(challenge_id, document_index));
+            Ok(())
+        }
+
+        /// Link the sender to an identity claim, replacing the one linked before.
+        pub fn link_identity_claim(origin, claim_hash: T::Hash) -> Result {
+            let sender = ensure_signed(origin)?;
+            Self::ensure_feature_enabled(Feature::IdentityClaims)?;
+
+            <BatteryOwnerClaims<T>>::insert(sender.clone(), claim_hash);
+            Self::note_activity(&sender);
+
+            Self::deposit_event(RawEvent::IdentityClaimLinked(sender, claim_hash));
+            Ok(())
+        }
+
+        /// Refuse to store batteries registered more than `max_days` days ago.
+        pub fn set_station_age_policy(origin, max_days: u64) -> Result {
+            let sender = ensure_signed(origin)?;
+            Self::ensure_feature_enabled(Feature::StationAgePolicies)?;
+            ensure!(<StationsIndex<T>>::exists(sender.clone()), "Sender is not a station");
+
+            <StationAgePolicy<T>>::insert(sender.clone(), max_days);
+            Self::audit(&sender, Some(sender.clone()), StationAction::AgePolicySet(max_days));
+            Self::note_activity(&sender);
+
+            Self::deposit_event(RawEvent::StationAgePolicySet(sender, max_days));
+            Ok(())
+        }
+
+        /// Register a battery like `register_battery` that can never be traded, listed or
+        /// sold on, e.g. one given out under a subsidy program.
+        pub fn register_non_transferable_battery(origin, owner: T::AccountId) -> Result {
+            let sender = ensure_signed(origin)?;
+            Self::ensure_migrated()?;
+            Self::do_register_battery(sender.clone(), owner, true)?;
+            Self::note_activity(&sender);
+            Ok(())
+        }
+
+        /// Lift the restriction `register_non_transferable_battery` put on battery `id`,
+        /// once its owner graduates from the program.
+        pub fn lift_transfer_restriction(origin, id: T::Hash) -> Result {
+            T::AdminOrigin::ensure_origin(origin)?;
+            Self::ensure_migrated()?;
+            ensure!(Self::is_non_transferable(id), "Battery is not non-transferable");
+
+            // change state
+            <NonTransferable<T>>::remove(id);
+            Self::update_passport_hash(id);
+            Self::bump_nonce(id);
+
+            Self::deposit_event(RawEvent::TransferRestrictionLifted(id));
+            Ok(())
+        }
+
+        /// Vouch for `station`. Once it has more than `PromotionThreshold` votes, a
+        /// `Basic` station is promoted to `Verified`.
+        pub fn vote_to_promote_station(origin, station: T::AccountId) -> Result {
+            let sender = ensure_signed(origin)?;
+            Self::ensure_feature_enabled(Feature::PromotionVotes)?;
+            ensure!(<StationsIndex<T>>::exists(station.clone()), "Not a station!");
+            ensure!(sender != station, "Stations can't vote for themselves");
+            ensure!(!Self::has_voted((sender.clone(), station.clone())), "Already voted for this station");
+
+            // change state
+            <HasVoted<T>>::insert((sender.clone(), station.clone()), true);
+            let votes = Self::votes(&station) + 1;
+            <StationPromotionVotes<T>>::insert(station.clone(), votes);
+            Self::note_activity(&sender);
+
+            Self::deposit_event(RawEvent::VoteCast(sender, station.clone()));
+            if votes > Self::promotion_threshold() && Self::trust_level(station.clone()) == TrustLevel::Basic {
+                <TrustLevels<T>>::insert(station.clone(), TrustLevel::Verified);
+                Self::audit(&station, None, StationAction::TrustLevelSet(TrustLevel::Verified));
+                Self::deposit_event(RawEvent::StationPromoted(station));
+            }
+            Ok(())
+        }
+
+        /// Withdraw a vote. A station that was already promoted keeps its trust level.
+        pub fn retract_vote(origin, station: T::AccountId) -> Result {
+            let sender = ensure_signed(origin)?;
+            Self::ensure_feature_enabled(Feature::PromotionVotes)?;
+            ensure!(Self::has_voted((sender.clone(), station.clone())), "No vote for this station");
+
+            // change state
+            <HasVoted<T>>::remove((sender.clone(), station.clone()));
+            <StationPromotionVotes<T>>::mutate(station.clone(), |votes| *votes -= 1);
+            Self::note_activity(&sender);
+
+            Self::deposit_event(RawEvent::VoteRetracted(sender, station));
+            Ok(())
+        }
+
+        /// Offer to move batteries the station owns and holds to `to_station`, e.g. when
+        /// two franchises agree to even out their inventory.
+        pub fn propose_rebalance(origin, to_station: T::AccountId, ids: Vec<T::Hash>) -> Result {
+            let sender = ensure_signed(origin)?;
+            Self::ensure_migrated()?;
+            Self::ensure_feature_enabled(Feature::Rebalancing)?;
+            ensure!(<StationsIndex<T>>::exists(sender.clone()), "Sender is not a station");
+            ensure!(<StationsIndex<T>>::exists(to_station.clone()), "Not a station!");
+            ensure!(sender != to_station, "Can't rebalance to the same station");
+            ensure!(!ids.is_empty(), "No batteries to rebalance");
+            ensure!(ids.len() <= MAX_REBALANCE_BATTERIES, "Too many batteries to rebalance");
+            for (index, &id) in ids.iter().enumerate() {
+                ensure!(!ids[..index].contains(&id), "Duplicate battery in rebalance");
+                Self::ensure_pool_battery(&sender, id)?;
+            }
+
+            let nonce = Self::rebalance_nonce();
+            let proposal_id = (&sender, &to_station, nonce).using_encoded(<T as system::Trait>::Hashing::hash);
+            let expires_at = <system::Module<T>>::block_number() + Self::rebalance_expiry_blocks();
+
+            // change state
+            <RebalanceNonce<T>>::put(nonce + 1);
+            <RebalanceProposals<T>>::insert(proposal_id, RebalanceProposal {
+                from: sender.clone(),
+                to: to_station.clone(),
+                ids,
+                expires_at,
+            });
+            <RebalancesExpiringAt<T>>::mutate(expires_at, |proposals| proposals.push(proposal_id));
+            Self::note_activity(&sender);
+
+            Self::deposit_event(RawEvent::RebalanceProposed(proposal_id, sender, to_station));
+            Ok(())
+        }
+
+        /// Take in every battery of a rebalance proposal. Fails without moving any if one of
+        /// them can no longer be moved or the station can't hold them all.
+        pub fn accept_rebalance(origin, proposal_id: T::Hash) -> Result {
+            let sender = ensure_signed(origin)?;
+            Self::ensure_migrated()?;
+            let proposal = Self::rebalance_proposal(proposal_id).ok_or("Rebalance proposal does not exist")?;
+            ensure!(proposal.to == sender, "Sender is not the destination station");
+            Self::ensure_rebalance_fits(&proposal)?;
+
+            // change state
+            <RebalanceProposals<T>>::remove(proposal_id);
+            for &id in &proposal.ids {
+                Self::relocate(id, &proposal.from, &proposal.to);
+            }
+            Self::note_activity(&sender);
+
+            Self::deposit_event(RawEvent::RebalanceAccepted(proposal_id));
+            Ok(())
+        }
+
+        pub fn reject_rebalance(origin, proposal_id: T::Hash) -> Result {
+            let sender = ensure_signed(origin)?;
+            let proposal = Self::rebalance_proposal(proposal_id).ok_or("Rebalance proposal does not exist")?;
+            ensure!(proposal.to == sender, "Sender is not the destination station");
+
+            <RebalanceProposals<T>>::remove(proposal_id);
+            Self::note_activity(&sender);
+
+            Self::deposit_event(RawEvent::RebalanceRejected(proposal_id));
+            Ok(())
+        }
+
+        /// Answer the audit the station was selected for with the health of batteries it holds.
+        pub fn submit_audit_results(origin, audit_id: T::Hash, battery_health_reports: Vec<(T::Hash, u8)>) -> Result {
+            let sender = ensure_signed(origin)?;
+            Self::ensure_migrated()?;
+            let audit = Self::compliance_audit(audit_id).ok_or("Audit does not exist")?;
+            ensure!(audit.station == sender, "Sender is not the audited station");
+            ensure!(battery_health_reports.len() <= MAX_AUDIT_REPORTS, "Too many health reports");
+            for (index, &(id, health)) in battery_health_reports.iter().enumerate() {
+                ensure!(health <= 100, "Health must be a percentage");
+                ensure!(!battery_health_reports[..index].iter().any(|&(other, _)| other == id), "Duplicate battery in audit");
+                ensure!(Self::batteries(id).station() == Some(&sender), "Battery is not held by the station");
+            }
+
+            // change state
+            <ComplianceAudits<T>>::remove(audit_id);
+            <OpenAudit<T>>::remove(&sender);
+            for &(id, health) in &battery_health_reports {
+                <BatteryHealth<T>>::insert(id, health);
+            }
+            <AuditReports<T>>::insert(audit_id, battery_health_reports);
+            Self::audit(&sender, Some(sender.clone()), StationAction::AuditSubmitted);
+            Self::note_activity(&sender);
+
+            Self::deposit_event(RawEvent::AuditSubmitted(audit_id, sender));
+            Ok(())
+        }
+
+        /// Ask `station` to take battery `id` in. Requests to several stations can be open at
+        /// once; the first one to confirm gets the battery and the others are dropped.
+        pub fn request_store(origin, id: T::Hash, station: T::AccountId) -> Result {
+            let sender = ensure_signed(origin)?;
+            Self::ensure_migrated()?;
+            Self::ensure_feature_enabled(Feature::StoreRequests)?;
+            Self::ensure_not_in_flight(id)?;
+            ensure!(<Batteries<T>>::exists(id), "Battery does not exist");
+            let battery = Self::batteries(id);
+            ensure!(Self::is_owner_or_co_owner(&battery, id, &sender), "You are not the owner of this battery");
+            ensure!(battery.station().is_none(), "Battery is already in custody");
+            ensure!(<StationsIndex<T>>::exists(station.clone()), "Not a station!");
+            let mut stations = Self::pending_store_stations(id);
+            ensure!(!stations.contains(&station), "Store already requested at this station");
+            ensure!(stations.len() < MAX_PENDING_STORES, "Too many pending store requests");
+
+            // change state
+            let expires_at = <system::Module<T>>::block_number() + Self::store_request_expiry_blocks();
+            stations.push(station.clone());
+            <PendingStoreStations<T>>::insert(id, stations);
+            <PendingStores<T>>::insert((id, station.clone()), expires_at);
+            Self::bump_nonce(id);
+            Self::note_activity(&sender);
+
+            Self::deposit_event(RawEvent::StoreRequested(id, station));
+            Ok(())
+        }
+
+        pub fn cancel_store_request(origin, id: T::Hash, station: T::AccountId) -> Result {
+            let sender = ensure_signed(origin)?;
+            Self::ensure_migrated()?;
+            ensure!(<Batteries<T>>::exists(id), "Battery does not exist");
+            ensure!(Self::is_owner_or_co_owner(&Self::batteries(id), id, &sender), "You are not the owner of this battery");
+            ensure!(<PendingStores<T>>::exists((id, station.clone())), "No store request at this station");
+
+            // change state
+            <PendingStores<T>>::remove((id, station.clone()));
+            <PendingStoreStations<T>>::mutate(id, |stations| stations.retain(|pending| *pending != station));
+            Self::bump_nonce(id);
+            Self::note_activity(&sender);
+
+            Self::deposit_event(RawEvent::StoreRequestCancelled(id, station));
+            Ok(())
+        }
+
+        /// Take in a battery whose owner requested it with `request_store`. May be signed by
+        /// the station or its session key.
+        pub fn confirm_store(origin, id: T::Hash) -> Result {
+            let sender = ensure_signed(origin)?;
+            Self::ensure_migrated()?;
+            let station = Self::custodial_station(sender.clone())?;
+            ensure!(<Batteries<T>>::exists(id), "Battery does not exist");
+            ensure!(Self::batteries(id).station().is_none(), "Battery is already in custody");
+            let expires_at = Self::pending_store((id, station.clone())).ok_or("No store request at this station")?;
+            ensure!(<system::Module<T>>::block_number() <= expires_at, "Store request expired");
+            Self::can_store(&sender, id)?;
+
+            // change state
+            Self::do_store(&sender, station, id);
+            Self::note_activity(&sender);
+            Ok(())
+        }
+
+        /// Choose which alerts off-chain notifiers send the account.
+        pub fn set_notification_prefs(origin, prefs: NotificationPrefs) -> Result {
+            let sender = ensure_signed(origin)?;
+            Self::ensure_feature_enabled(Feature::Notifications)?;
+            <NotificationPreferences<T>>::insert(sender.clone(), prefs);
+            Self::note_activity(&sender);
+
+            Self::deposit_event(RawEvent::NotificationPrefsUpdated(sender));
+            Ok(())
+        }
+
+        /// Open the audit `station` was drawn for in the current round. Sent unsigned by the
+        /// off-chain worker, see `validate_unsigned`.
+        pub fn schedule_audit(origin, station: T::AccountId) -> Result {
+            ensure_inherent(origin)?;
+            Self::ensure_audit_due(&station)?;
+
+            // change state
+            Self::begin_audit(station);
+            Ok(())
+        }
+
+        $($test_helpers)*
+    }
+}
+    };
+}
+
+#[cfg(not(feature = "test-helpers"))]
+decl_battery_module! {}
+
+// Shortcuts for integration tests to set up states that would otherwise take dozens of
+// extrinsics. They are root only, write storage directly and skip the checks of the
+// extrinsics they stand in for.
+#[cfg(feature = "test-helpers")]
+decl_battery_module! {
+    /// Stores `battery` under `id`, replacing any battery stored under it. Keeps
+    /// `AllBatteriesArray`, the owner and station indexes and `Stats` in line with it.
+    pub fn force_set_battery(origin, id: T::Hash, battery: Battery<T::AccountId, T::BlockNumber>) -> Result {
+        ensure_root(origin)?;
+        Self::do_force_set_battery(id, battery);
+        Ok(())
+    }
+
+    /// Withdraws battery `id` from trade as if its listing had run out, without a call
+    /// from its owner. Listings don't expire on their own yet.
+    pub fn force_expire_listing(origin, id: T::Hash) -> Result {
+        ensure_root(origin)?;
+        Self::do_force_expire_listing(id);
+        Ok(())
+    }
+
+    /// Overwrites the counters alone, leaving the arrays they count as they are.
+    pub fn force_set_counts(origin, all_batteries: u64, stations: u64) -> Result {
+        ensure_root(origin)?;
+        <AllBatteriesCount<T>>::put(all_batteries);
+        <StationsCount<T>>::put(stations);
+        Ok(())
+    }
+
+    /// Overwrites `StorageVersion`, e.g. to start from a chain that is mid-migration.
+    pub fn force_set_storage_version(origin, version: u32) -> Result {
+        ensure_root(origin)?;
+        <StorageVersion<T>>::put(version);
+        Ok(())
+    }
+}
+
+#[cfg(feature = "test-helpers")]
+impl<T: Trait> Module<T> {
+    fn do_force_set_battery(id: T::Hash, battery: Battery<T::AccountId, T::BlockNumber>) {
+        let previous = if <Batteries<T>>::exists(id) { Some(Self::batteries(id)) } else { None };
+        match &previous {
+            Some(previous) => {
+                Self::note_custody_change(&previous.custody, &battery.custody);
+                if previous.owner != battery.owner {
+                    Self::remove_owned(&previous.owner, id);
+                }
+                if let Some(station) = previous.station() {
+                    if battery.station() != Some(station) {
+                        Self::remove_from_station(station, id);
+                    }
+                }
+            }
+            None => {
+                <Stats<T>>::mutate(|stats| stats.add_battery(&battery.custody));
+                <AllBatteriesArray<T>>::insert(Self::all_batteries_count(), id);
+                <AllBatteriesCount<T>>::mutate(|n| *n += 1);
+            }
+        }
+        if previous.as_ref().map_or(true, |previous| previous.owner != battery.owner) {
+            Self::add_owned(&battery.owner, id);
+        }
+        if let Some(station) = battery.station() {
+            if previous.as_ref().map_or(true, |previous| previous.station() != Some(station)) {
+                Self::add_to_station(station, id);
+            }
+        }
+        Self::put_battery(id, battery);
+        <MerkleRootOutdated<T>>::put(true);
+    }
+
+    fn do_force_expire_listing(id: T::Hash) {
+        let mut battery = Self::batteries(id);
+        if let Custody::Tradable(station) = battery.custody.clone() {
+            let before = battery.custody.clone();
+            battery.custody = Custody::Station(station);
+            Self::note_custody_change(&before, &battery.custody);
+            Self::put_battery(id, battery);
+            Self::start_staking(id);
+        }
+    }
+}
+
+#[cfg(all(feature = "std", any(test, feature = "test-helpers")))]
+thread_local! {
+    static NEXT_TEST_ID: std::cell::Cell<Option<u64>> = std::cell::Cell::new(None);
+}
+
+/// Hands out `predicted_id(n)`, `predicted_id(n + 1)`... once `set_counter(Some(n))` is
+/// called, so tests can name the batteries they register before registering them. Keeps
+/// the derived ids while no counter is set. The counter is per thread, i.e. per test.
+#[cfg(all(feature = "std", any(test, feature = "test-helpers")))]
+pub struct TestIdGenerator;
+
+#[cfg(all(feature = "std", any(test, feature = "test-helpers")))]
+impl TestIdGenerator {
+    pub fn set_counter(next: Option<u64>) {
+        NEXT_TEST_ID.with(|counter| counter.set(next));
+    }
+
+    /// The id handed out for counter value `n`: `n + 1` little endian, zero padded, so
+    /// no predicted id is the all-zero default hash.
+    pub fn predicted_id<Hash: Default + AsMut<[u8]>>(n: u64) -> Hash {
+        let mut id = Hash::default();
+        (n + 1).using_encoded(|bytes| id.as_mut()[..bytes.len()].copy_from_slice(bytes));
+        id
+    }
+}
+
+#[cfg(all(feature = "std", any(test, feature = "test-helpers")))]
+impl<AccountId, Hash: Default + AsMut<[u8]>> BatteryIdGenerator<AccountId, Hash> for TestIdGenerator {
+    fn generate_id(_owner: &AccountId, random_id: Hash) -> Hash {
+        match NEXT_TEST_ID.with(|counter| counter.get()) {
+            Some(n) => {
+                Self::set_counter(Some(n + 1));
+                Self::predicted_id(n)
+            }
+            None => random_id,
+        }
+    }
+}
+
+/// tests for this module
+#[cfg(test)]
+mod tests;
+#[cfg(test)]
+mod codec_tests;
@@ -0,0 +1,452 @@
+//! Station registry, probation, misconduct reviews, audits, rebalancing and discovery.
+
+use super::*;
+use runtime_primitives::{ApplyError, traits::As, transaction_validity::TransactionValidity};
+use crate::geo;
+
+impl<T: Trait> Module<T> {
+    pub(super) fn audit(station: &T::AccountId, actor: Option<T::AccountId>, action: StationAction<T::Hash>) {
+        let index = Self::audit_count(station);
+        <StationAuditLog<T>>::insert((station.clone(), index), StationAuditEntry {
+            actor,
+            at: <system::Module<T>>::block_number(),
+            extrinsic_index: <system::Module<T>>::extrinsic_index().unwrap_or_default(),
+            action,
+        });
+        <StationAuditCount<T>>::insert(station.clone(), index + 1);
+    }
+
+    /// Up to `limit` entries of `station`'s audit log, starting at entry `offset`.
+    pub fn station_audit_log(
+        station: &T::AccountId,
+        offset: u64,
+        limit: u64,
+    ) -> Vec<StationAuditEntry<T::AccountId, T::BlockNumber, T::Hash>> {
+        let end = rstd::cmp::min(offset.saturating_add(limit), Self::audit_count(station));
+        (offset..end)
+            .filter_map(|index| Self::audit_entry((station.clone(), index)))
+            .collect()
+    }
+
+    pub(super) fn report_era() -> u64 {
+        let era_length = rstd::cmp::max(Self::report_era_length().as_(), 1);
+        <system::Module<T>>::block_number().as_() / era_length
+    }
+
+    /// Stops `station` from trading until `resolve_reports` rules on it.
+    pub(super) fn place_under_review(station: T::AccountId) {
+        if !Self::is_under_review(&station) {
+            <UnderReview<T>>::insert(station.clone(), true);
+            Self::audit(&station, None, StationAction::PlacedUnderReview);
+            Self::deposit_event(RawEvent::StationUnderReview(station));
+        }
+    }
+
+    /// Every `AuditInterval` blocks, starts a round of audits drawn from the block's
+    /// random seed. The off-chain worker sends the draws as `schedule_audit` calls.
+    pub(super) fn start_audit_round(n: T::BlockNumber) {
+        let interval = Self::audit_interval();
+        if !Self::is_feature_enabled(Feature::Audits) {
+            return;
+        }
+        if interval.is_zero() || !(n % interval).is_zero() {
+            return;
+        }
+        <AuditRound<T>>::put((n, <system::Module<T>>::random_seed()));
+    }
+
+    /// `AuditSampleSize` distinct stations drawn for the current audit round, if one is
+    /// still open.
+    pub fn audit_sample() -> Vec<T::AccountId> {
+        let count = Self::stations_count();
+        let (round, seed) = match Self::audit_round() {
+            Some(round) => round,
+            None => return Vec::new(),
+        };
+        if count == 0 || <system::Module<T>>::block_number() >= round + Self::audit_interval() {
+            return Vec::new();
+        }
+
+        let sample = rstd::cmp::min(Self::audit_sample_size() as u64, count);
+        let mut picked: Vec<u64> = Vec::new();
+        for draw in 0..sample {
+            let random = (seed, round, draw).using_encoded(<T as system::Trait>::Hashing::hash);
+            let mut index = u64::decode(&mut random.as_ref()).unwrap_or(0) % count;
+            while picked.contains(&index) {
+                index = (index + 1) % count;
+            }
+            picked.push(index);
+        }
+        picked.into_iter().map(Self::station_by_index).collect()
+    }
+
+    /// Fails unless `station` was drawn for the current round and wasn't audited in it yet.
+    pub(super) fn ensure_audit_due(station: &T::AccountId) -> Result {
+        Self::ensure_feature_enabled(Feature::Audits)?;
+        ensure!(Self::audit_sample().contains(station), "Station was not drawn for an audit");
+        ensure!(!<OpenAudit<T>>::exists(station), "Station already has an open audit");
+        ensure!(
+            Self::last_audit_round(station) != Self::audit_round().map(|(round, _)| round),
+            "Station was already audited this round"
+        );
+        Ok(())
+    }
+
+    pub(super) fn begin_audit(station: T::AccountId) {
+        let now = <system::Module<T>>::block_number();
+        let deadline = now + Self::audit_deadline_blocks();
+        let nonce = Self::audit_nonce();
+        let audit_id = (&station, nonce).using_encoded(<T as system::Trait>::Hashing::hash);
+        <AuditNonce<T>>::put(nonce + 1);
+        <ComplianceAudits<T>>::insert(audit_id, AuditSchedule { station: station.clone(), scheduled_at: now, deadline });
+        <OpenAudit<T>>::insert(station.clone(), audit_id);
+        if let Some((round, _)) = Self::audit_round() {
+            <LastAuditRound<T>>::insert(station.clone(), round);
+        }
+        <AuditsDueAt<T>>::mutate(deadline, |audits| audits.push(audit_id));
+        Self::deposit_event(RawEvent::AuditScheduled(audit_id, station));
+    }
+
+    /// Sends a `schedule_audit` call for every station drawn in the round that started
+    /// at block `n`.
+    pub(super) fn submit_audits(n: T::BlockNumber) {
+        if Self::audit_round().map(|(round, _)| round) != Some(n) {
+            return;
+        }
+        for station in Self::audit_sample() {
+            if Self::ensure_audit_due(&station).is_ok() {
+                T::AuditSubmitter::submit_schedule_audit(station);
+            }
+        }
+    }
+
+    /// Vouches for the unsigned calls the off-chain worker sends: a `schedule_audit` for a
+    /// station that is due one, until the round is over.
+    pub fn validate_unsigned(call: &Call<T>) -> TransactionValidity {
+        let station = match call {
+            Call::schedule_audit(station) => station,
+            _ => return TransactionValidity::Invalid(ApplyError::BadSignature as i8),
+        };
+        match (Self::audit_round(), Self::ensure_audit_due(station)) {
+            (Some((round, _)), Ok(())) => TransactionValidity::Valid {
+                priority: 0,
+                requires: vec![],
+                provides: vec![(&b"schedule_audit"[..], round, station).encode()],
+                longevity: (round + Self::audit_interval() - <system::Module<T>>::block_number()).as_(),
+            },
+            _ => TransactionValidity::Invalid(ApplyError::BadSignature as i8),
+        }
+    }
+
+    /// Slashes the bond of every station that let an audit due by block `n` pass and
+    /// places it under review.
+    pub(super) fn expire_audits(n: T::BlockNumber) {
+        for audit_id in <AuditsDueAt<T>>::take(n) {
+            if let Some(audit) = Self::compliance_audit(audit_id) {
+                <ComplianceAudits<T>>::remove(audit_id);
+                <OpenAudit<T>>::remove(&audit.station);
+                Self::audit(&audit.station, None, StationAction::AuditMissed);
+                Self::deposit_event(RawEvent::AuditMissed(audit_id, audit.station.clone()));
+                let bond = <StationBonds<T>>::take(&audit.station);
+                if !bond.is_zero() {
+                    let _ = T::Currency::slash_reserved(&audit.station, bond);
+                    Self::deposit_event(RawEvent::StationBondSlashed(audit.station.clone(), bond));
+                }
+                Self::place_under_review(audit.station);
+            }
+        }
+    }
+
+    pub(super) fn ensure_below_probation_cap(station: &T::AccountId) -> Result {
+        if let StationStatus::Probation { .. } = Self::station_status(station) {
+            ensure!(Self::batteries_count_in_station(station) < PROBATION_INVENTORY_CAP, "Station on probation is full");
+        }
+        Ok(())
+    }
+
+    pub(super) fn count_probation_trade(station: &T::AccountId) {
+        if let StationStatus::Probation { trades_completed } = Self::station_status(station) {
+            let trades_completed = trades_completed + 1;
+            if trades_completed >= Self::probation_trade_threshold() {
+                Self::graduate(station.clone());
+            } else {
+                <StationStatuses<T>>::insert(station.clone(), StationStatus::Probation { trades_completed });
+            }
+        }
+    }
+
+    pub(super) fn graduate(station: T::AccountId) {
+        <StationStatuses<T>>::insert(station.clone(), StationStatus::Full);
+        Self::audit(&station, None, StationAction::Graduated);
+        Self::deposit_event(RawEvent::StationGraduated(station));
+    }
+
+    pub(super) fn do_register_station(sender: T::AccountId) -> Result {
+        ensure!(!<StationsIndex<T>>::exists(sender.clone()), "Already been station!");
+        ensure!(!<KeyToStation<T>>::exists(sender.clone()), "Account is a session key of a station");
+
+        let bond = Self::station_bond();
+        if !bond.is_zero() {
+            T::Currency::reserve(&sender, bond)?;
+            <StationBonds<T>>::insert(sender.clone(), bond);
+        }
+        <StationsArray<T>>::insert(Self::stations_count(), sender.clone());
+        <StationsIndex<T>>::insert(sender.clone(), Self::stations_count());
+        <StationsCount<T>>::mutate(|n| *n += 1);
+        <Stats<T>>::mutate(|stats| stats.stations += 1);
+        <StationStatuses<T>>::insert(sender.clone(), StationStatus::Probation { trades_completed: 0 });
+        Self::audit(&sender, Some(sender.clone()), StationAction::Registered);
+
+        Self::deposit_event(RawEvent::RegistryStation(sender));
+        Ok(())
+    }
+
+    /// Public view of battery `id` if it is currently held by `station`.
+    /// The owner is hidden unless `viewer` is the owner.
+    pub fn cross_station_battery_inquiry(
+        station: &T::AccountId,
+        id: T::Hash,
+        viewer: Option<&T::AccountId>,
+    ) -> Option<BatteryPublicInfo<T::Hash, T::BlockNumber, T::AccountId>> {
+        if !<Batteries<T>>::exists(id) {
+            return None;
+        }
+        let battery = Self::batteries(id);
+        if battery.station() != Some(station) {
+            return None;
+        }
+
+        let tradable = battery.is_tradable();
+        let registered_at = battery.registered_at;
+        let owner = match viewer {
+            Some(who) if *who == battery.owner => Some(battery.owner),
+            _ => None,
+        };
+        Some(BatteryPublicInfo {
+            id,
+            station: station.clone(),
+            tradable,
+            registered_at,
+            owner,
+        })
+    }
+
+    /// Whether a signed inquiry valid until block `valid_until` may still reveal the owner.
+    pub fn is_inquiry_signature_current(valid_until: T::BlockNumber) -> bool {
+        let now = <system::Module<T>>::block_number();
+        now <= valid_until && valid_until <= now + T::BlockNumber::sa(MAX_INQUIRY_VALIDITY_BLOCKS)
+    }
+
+    /// Whether `station` is open at the current block's timestamp.
+    pub(super) fn is_open(station: &T::AccountId) -> bool {
+        let schedule = match Self::opening_hours(station) {
+            Some(schedule) => schedule,
+            None => return true,
+        };
+        let minutes = <timestamp::Module<T>>::get().as_() / 60;
+        let minute_of_day = (minutes % MINUTES_PER_DAY as u64) as u32;
+        // The unix epoch was a Thursday, day 3 counting from Monday.
+        let day = ((minutes / MINUTES_PER_DAY as u64 + 3) % 7) as usize;
+        schedule[day].iter().any(|&(start, end)| start <= minute_of_day && minute_of_day < end)
+    }
+
+    /// Oldest battery age in days `station` accepts, unlimited without a policy.
+    pub fn max_age_days(station: &T::AccountId) -> u64 {
+        Self::station_age_policy(station).unwrap_or(u64::max_value())
+    }
+
+    /// Whole days since `battery` was registered.
+    pub(super) fn battery_age_days(battery: &Battery<T::AccountId, T::BlockNumber>) -> u64 {
+        let age = <system::Module<T>>::block_number() - battery.registered_at;
+        age.as_() / Self::blocks_per_day().as_().max(1)
+    }
+
+    /// Checks battery `id` is owned and held by `station`, and free to leave it.
+    pub(super) fn ensure_pool_battery(station: &T::AccountId, id: T::Hash) -> Result {
+        Self::ensure_not_in_flight(id)?;
+        ensure!(<Batteries<T>>::exists(id), "Battery does not exist");
+        let battery = Self::batteries(id);
+        ensure!(battery.owner == *station && battery.station() == Some(station), "Battery is not in the station's pool");
+        ensure!(!Self::is_frozen_by_challenge(id), "Battery ownership is being challenged");
+        ensure!(!<BridgeRequestOf<T>>::exists(id), "Battery is locked in the bridge");
+        Ok(())
+    }
+
+    /// Checks every battery of `proposal` can still move and the destination accepts them all.
+    pub(super) fn ensure_rebalance_fits(proposal: &RebalanceProposal<T::AccountId, T::Hash, T::BlockNumber>) -> Result {
+        let max_age_days = Self::max_age_days(&proposal.to);
+        for &id in &proposal.ids {
+            Self::ensure_pool_battery(&proposal.from, id)?;
+            ensure!(Self::battery_age_days(&Self::batteries(id)) <= max_age_days, "Battery is older than the station accepts");
+        }
+        if let StationStatus::Probation { .. } = Self::station_status(&proposal.to) {
+            let count = Self::batteries_count_in_station(&proposal.to) + proposal.ids.len() as u64;
+            ensure!(count <= PROBATION_INVENTORY_CAP, "Station on probation is full");
+        }
+        Ok(())
+    }
+
+    /// Moves battery `id` from station `from` to station `to`, keeping it listed if it was.
+    pub(super) fn relocate(id: T::Hash, from: &T::AccountId, to: &T::AccountId) {
+        let mut battery = Self::batteries(id);
+        battery.custody = match battery.custody {
+            Custody::Tradable(_) => Custody::Tradable(to.clone()),
+            _ => Custody::Station(to.clone()),
+        };
+        Self::remove_from_station(from, id);
+        Self::put_battery(id, battery);
+        Self::add_to_station(to, id);
+        Self::audit(from, None, StationAction::BatteryRemoved(id));
+        Self::audit(to, None, StationAction::BatteryStored(id));
+        Self::bump_nonce(id);
+    }
+
+    pub(super) fn expire_rebalances(n: T::BlockNumber) {
+        for proposal_id in <RebalancesExpiringAt<T>>::take(n) {
+            if <RebalanceProposals<T>>::exists(proposal_id) {
+                <RebalanceProposals<T>>::remove(proposal_id);
+                Self::deposit_event(RawEvent::RebalanceExpired(proposal_id));
+            }
+        }
+    }
+
+    /// The station `who` acts for in custody-only operations: either the station
+    /// itself or the holder of its current session key.
+    pub(super) fn custodial_station(who: T::AccountId) -> rstd::result::Result<T::AccountId, &'static str> {
+        if <StationsIndex<T>>::exists(who.clone()) {
+            Ok(who)
+        } else {
+            Self::key_station(who).ok_or("Sender is not a station")
+        }
+    }
+
+    /// Stations holding at least one tradable battery of `min_grade` or better charged
+    /// to at least `min_soc` percent, as `(station, distance_meters)` from the given
+    /// point, nearest first. Stations without a location, or already at their declared
+    /// swap capacity this hour, are skipped.
+    pub fn find_stations_with_tradable_batteries(
+        min_grade: BatteryGrade,
+        min_soc: u8,
+        user_lat: i64,
+        user_lon: i64,
+        max_results: u8,
+    ) -> Vec<(T::AccountId, u64)> {
+        let mut found = Vec::new();
+        for index in 0..Self::stations_count() {
+            let station = Self::station_by_index(index);
+            let (lat, lon) = match Self::station_location(&station) {
+                Some(location) => location,
+                None => continue,
+            };
+            if Self::has_spare_swap_capacity(&station) && Self::has_tradable_battery(&station, min_grade, min_soc) {
+                found.push((station, geo::distance_meters(user_lat, user_lon, lat, lon)));
+            }
+        }
+        found.sort_by_key(|&(_, distance)| distance);
+        found.truncate(max_results as usize);
+        found
+    }
+
+    /// The candidate best placed to take in battery `id` next, or `None` if none can. Stations
+    /// that couldn't store it right now, or are running at their declared swap capacity, are
+    /// skipped. The rest are scored by kilometers from the station holding the battery plus
+    /// their utilization in percent, less `UNMET_DEMAND_WEIGHT` for each swap nearby the
+    /// battery could serve there, the lowest score winning. Distance counts for nothing
+    /// while the battery is with its owner, whose whereabouts the chain doesn't know.
+    pub fn recommend_store_station(id: T::Hash, candidate_stations: Vec<T::AccountId>) -> Option<T::AccountId> {
+        if !<Batteries<T>>::exists(id) {
+            return None;
+        }
+        let battery = Self::batteries(id);
+        let origin = battery.station().and_then(Self::station_location);
+
+        let mut best: Option<(i64, T::AccountId)> = None;
+        for station in candidate_stations {
+            if !<StationsIndex<T>>::exists(&station)
+                || battery.station() == Some(&station)
+                || Self::is_under_review(&station)
+                || !Self::is_open(&station)
+                || !Self::has_spare_swap_capacity(&station)
+                || Self::ensure_below_probation_cap(&station).is_err()
+                || Self::battery_age_days(&battery) > Self::max_age_days(&station)
+            {
+                continue;
+            }
+            let distance_km = match (origin, Self::station_location(&station)) {
+                (Some((lat, lon)), Some((to_lat, to_lon))) => geo::distance_meters(lat, lon, to_lat, to_lon) / 1000,
+                (Some(_), None) => continue,
+                (None, _) => 0,
+            };
+            let utilization = Self::swap_capacity(&station)
+                .map_or(0, |capacity| Self::current_throughput_rate(&station) as u64 * 100 / rstd::cmp::max(capacity, 1) as u64);
+            let unmet_demand = Self::station_location(&station).map_or(0, |(lat, lon)| Self::unmet_demand_near(&station, lat, lon));
+            let free_slots = Self::swap_capacity(&station)
+                .map_or(u64::max_value(), |capacity| capacity.saturating_sub(Self::current_throughput_rate(&station)) as u64);
+            let demand_bonus = rstd::cmp::min(unmet_demand, free_slots) * UNMET_DEMAND_WEIGHT;
+            let score = (distance_km + utilization) as i64 - demand_bonus as i64;
+            if best.as_ref().map_or(true, |&(best_score, _)| score < best_score) {
+                best = Some((score, station));
+            }
+        }
+        best.map(|(_, station)| station)
+    }
+
+    /// Swaps this hour at the stations other than `candidate` within `DEMAND_RADIUS_METERS`
+    /// of the given point, less the tradable batteries those stations hold. The candidate's
+    /// own swaps already count towards its utilization.
+    pub(super) fn unmet_demand_near(candidate: &T::AccountId, lat: i64, lon: i64) -> u64 {
+        let mut swaps = 0u64;
+        let mut listed = 0u64;
+        for index in 0..Self::stations_count() {
+            let station = Self::station_by_index(index);
+            if station == *candidate {
+                continue;
+            }
+            match Self::station_location(&station) {
+                Some((to_lat, to_lon)) if geo::distance_meters(lat, lon, to_lat, to_lon) <= DEMAND_RADIUS_METERS => {}
+                _ => continue,
+            }
+            swaps += Self::current_throughput_rate(&station) as u64;
+            listed += (0..Self::batteries_count_in_station(&station))
+                .filter(|&index| Self::batteries(Self::battery_of_station_by_index((station.clone(), index))).is_tradable())
+                .count() as u64;
+        }
+        swaps.saturating_sub(listed)
+    }
+
+    pub(super) fn current_hour() -> u64 {
+        <timestamp::Module<T>>::get().as_() / 3600
+    }
+
+    pub(super) fn record_swap(station: &T::AccountId) {
+        let hour = Self::current_hour();
+        <SwapThroughput<T>>::mutate(station, |(recorded_hour, swaps)| {
+            if *recorded_hour != hour {
+                *recorded_hour = hour;
+                *swaps = 0;
+            }
+            *swaps = swaps.saturating_add(1);
+        });
+    }
+
+    /// Stores and fetches at `station` so far in the current hour.
+    pub fn current_throughput_rate(station: &T::AccountId) -> u32 {
+        let (hour, swaps) = Self::swap_throughput(station);
+        if hour == Self::current_hour() { swaps } else { 0 }
+    }
+
+    pub(super) fn has_spare_swap_capacity(station: &T::AccountId) -> bool {
+        Self::swap_capacity(station).map_or(true, |capacity| capacity > Self::current_throughput_rate(station))
+    }
+
+    /// The state of charge of a battery is the target its charging station set, zero
+    /// for batteries that aren't being charged.
+    pub(super) fn has_tradable_battery(station: &T::AccountId, min_grade: BatteryGrade, min_soc: u8) -> bool {
+        (0..Self::batteries_count_in_station(station)).any(|index| {
+            let id = Self::battery_of_station_by_index((station.clone(), index));
+            Self::batteries(id).is_tradable()
+                && Self::battery_health(id).map_or(BatteryGrade::C, BatteryGrade::from_health) >= min_grade
+                && Self::target_soc(id).unwrap_or(0) >= min_soc
+        })
+    }
+}
@@ -0,0 +1,2880 @@
+use super::*;
+
+use runtime_io::with_externalities;
+use primitives::{H256, Blake2Hasher};
+use support::{impl_outer_origin, impl_outer_event, assert_ok, assert_noop, traits::{Currency, ReservableCurrency}};
+use runtime_primitives::{
+    BuildStorage,
+    traits::{BlakeTwo256, Hash, IdentityLookup},
+    transaction_validity::TransactionValidity,
+    testing::{Digest, DigestItem, Header}
+};
+
+impl_outer_origin! {
+    pub enum Origin for Test {}
+}
+
+mod battery {
+    pub use super::super::Event;
+}
+
+impl_outer_event! {
+    pub enum TestEvent for Test {
+        balances<T>, battery<T>,
+    }
+}
+
+#[derive(Clone, Eq, PartialEq)]
+pub struct Test;
+impl system::Trait for Test {
+    type Origin = Origin;
+    type Index = u64;
+    type BlockNumber = u64;
+    type Hash = H256;
+    type Hashing = BlakeTwo256;
+    type Digest = Digest;
+    type AccountId = u64;
+    type Lookup = IdentityLookup<Self::AccountId>;
+    type Header = Header;
+    type Event = TestEvent;
+    type Log = DigestItem;
+}
+impl timestamp::Trait for Test {
+    type Moment = u64;
+    type OnTimestampSet = ();
+}
+impl balances::Trait for Test {
+    type Balance = u64;
+    type OnFreeBalanceZero = ();
+    type OnNewAccount = ();
+    type Event = TestEvent;
+    type TransactionPayment = ();
+    type TransferPayment = ();
+    type DustRemoval = ();
+}
+impl Trait for Test {
+    type Event = TestEvent;
+    type Currency = balances::Module<Test>;
+    type GovCurrency = balances::Module<Test>;
+    type AccountStatus = MockAccountStatus;
+    type ArbitratorOrigin = system::EnsureRoot<u64>;
+    type BridgeOrigin = system::EnsureRoot<u64>;
+    type AdminOrigin = system::EnsureRoot<u64>;
+    type OnDecommission = MockDecommissionHooks;
+    type OnBatteryTransfer = ReentrantTransferHook;
+    type IdGenerator = TestIdGenerator;
+    type AuditSubmitter = MockAuditSubmitter;
+}
+
+thread_local! {
+    static DEAD_ACCOUNTS: std::cell::RefCell<Vec<u64>> = std::cell::RefCell::new(Vec::new());
+}
+
+pub struct MockAccountStatus;
+impl AccountLiveness<u64> for MockAccountStatus {
+    fn is_alive(who: &u64) -> bool {
+        DEAD_ACCOUNTS.with(|dead| !dead.borrow().contains(who))
+    }
+}
+
+thread_local! {
+    static DECOMMISSION_HOOKS: std::cell::RefCell<Vec<(&'static str, H256, u64)>> = std::cell::RefCell::new(Vec::new());
+}
+
+/// Also checks the hooks only run once the tombstone is written.
+pub struct MockDecommissionHooks;
+impl OnDecommission<H256, u64> for MockDecommissionHooks {
+    fn on_recycled(id: &H256, last_owner: &u64) {
+        assert!(<DecommissionedBatteries<Test>>::exists(id));
+        DECOMMISSION_HOOKS.with(|calls| calls.borrow_mut().push(("recycled", *id, *last_owner)));
+    }
+    fn on_damaged(id: &H256, last_owner: &u64) {
+        assert!(<DecommissionedBatteries<Test>>::exists(id));
+        DECOMMISSION_HOOKS.with(|calls| calls.borrow_mut().push(("damaged", *id, *last_owner)));
+    }
+}
+
+fn decommission_hook_calls() -> Vec<(&'static str, H256, u64)> {
+    DECOMMISSION_HOOKS.with(|calls| calls.borrow().clone())
+}
+
+thread_local! {
+    static REENTRANT_RESULTS: std::cell::RefCell<Vec<Result>> = std::cell::RefCell::new(Vec::new());
+    static SIBLING: std::cell::Cell<Option<H256>> = std::cell::Cell::new(None);
+    static SIBLING_RESULTS: std::cell::RefCell<Vec<Result>> = std::cell::RefCell::new(Vec::new());
+}
+
+/// Misbehaves by trying to move the battery again from inside the hook, and the
+/// battery set with `watch_sibling` too.
+pub struct ReentrantTransferHook;
+impl OnBatteryTransfer<H256, u64> for ReentrantTransferHook {
+    fn on_battery_transfer(id: &H256, _from: &u64, to: &u64) {
+        let result = Module::<Test>::fetch_from_station(Origin::signed(*to), *id);
+        REENTRANT_RESULTS.with(|results| results.borrow_mut().push(result));
+        if let Some(sibling) = SIBLING.with(|sibling| sibling.get()).filter(|sibling| sibling != id) {
+            let result = Module::<Test>::fetch_from_station(Origin::signed(*to), sibling);
+            SIBLING_RESULTS.with(|results| results.borrow_mut().push(result));
+        }
+    }
+}
+
+fn reentrant_results() -> Vec<Result> {
+    REENTRANT_RESULTS.with(|results| results.borrow().clone())
+}
+
+fn watch_sibling(id: H256) {
+    SIBLING.with(|sibling| sibling.set(Some(id)));
+}
+
+fn sibling_results() -> Vec<Result> {
+    SIBLING_RESULTS.with(|results| results.borrow().clone())
+}
+
+thread_local! {
+    static SUBMITTED_AUDITS: std::cell::RefCell<Vec<u64>> = std::cell::RefCell::new(Vec::new());
+}
+
+/// Keeps the stations the off-chain worker would send `schedule_audit` calls for.
+pub struct MockAuditSubmitter;
+impl SubmitAudit<u64> for MockAuditSubmitter {
+    fn submit_schedule_audit(station: u64) {
+        SUBMITTED_AUDITS.with(|submitted| submitted.borrow_mut().push(station));
+    }
+}
+
+fn submitted_audits() -> Vec<u64> {
+    SUBMITTED_AUDITS.with(|submitted| submitted.borrow().clone())
+}
+
+fn is_valid(validity: TransactionValidity) -> bool {
+    match validity {
+        TransactionValidity::Valid { .. } => true,
+        _ => false,
+    }
+}
+
+fn reap(who: u64) {
+    DEAD_ACCOUNTS.with(|dead| dead.borrow_mut().push(who));
+}
+type BatteryModule = Module<Test>;
+type System = system::Module<Test>;
+type Balances = balances::Module<Test>;
+
+const STATION: u64 = 10;
+const OTHER_STATION: u64 = 11;
+const OWNER: u64 = 1;
+const MANUFACTURER: u64 = 30;
+const PREORDER_LOCK_PERIOD: u64 = 10;
+const REWARDS_PER_BLOCK: u64 = 5;
+const CHALLENGE_PERIOD_BLOCKS: u64 = 20;
+const PROBATION_TRADE_THRESHOLD: u32 = 3;
+const BLOCKS_PER_CHECKPOINT: u64 = 5;
+const ABANDONMENT_PERIOD: u64 = 100;
+const ESCHEAT_NOTICE_PERIOD: u64 = 10;
+const ESCHEAT_POOL: u64 = 40;
+const KEY_RETENTION_BLOCKS: u64 = 5;
+const RECEIPT_RETENTION_BLOCKS: u64 = 5;
+const REPORT_ERA_LENGTH: u64 = 10;
+const REVIEW_STAKE_THRESHOLD: u64 = 100;
+const BLOCKS_PER_DAY: u64 = 10;
+const PROMOTION_THRESHOLD: u64 = 2;
+const REBALANCE_EXPIRY_BLOCKS: u64 = 5;
+const AUDIT_INTERVAL: u64 = 10;
+const AUDIT_DEADLINE_BLOCKS: u64 = 5;
+const STORE_REQUEST_EXPIRY_BLOCKS: u64 = 5;
+
+#[derive(Default)]
+struct ExtBuilder {
+    deterministic_ids: bool,
+    /// `force_*` calls to make once genesis is built, in order.
+    #[cfg(feature = "test-helpers")]
+    forced: Vec<Box<dyn Fn()>>,
+}
+
+#[cfg(feature = "test-helpers")]
+impl ExtBuilder {
+    fn with_battery(mut self, id: H256, owner: u64, custody: Custody<u64>, registered_at: u64) -> Self {
+        let battery = Battery { owner, custody, registered_at };
+        self.forced.push(Box::new(move || {
+            assert_ok!(BatteryModule::force_set_battery(system::RawOrigin::Root.into(), id, battery.clone()));
+        }));
+        self
+    }
+
+    fn with_expired_listing(mut self, id: H256) -> Self {
+        self.forced.push(Box::new(move || {
+            assert_ok!(BatteryModule::force_expire_listing(system::RawOrigin::Root.into(), id));
+        }));
+        self
+    }
+
+    fn with_counts(mut self, all_batteries: u64, stations: u64) -> Self {
+        self.forced.push(Box::new(move || {
+            assert_ok!(BatteryModule::force_set_counts(system::RawOrigin::Root.into(), all_batteries, stations));
+        }));
+        self
+    }
+
+    fn with_storage_version(mut self, version: u32) -> Self {
+        self.forced.push(Box::new(move || {
+            assert_ok!(BatteryModule::force_set_storage_version(system::RawOrigin::Root.into(), version));
+        }));
+        self
+    }
+
+    fn apply_forced(&self, mut ext: runtime_io::TestExternalities<Blake2Hasher>) -> runtime_io::TestExternalities<Blake2Hasher> {
+        with_externalities(&mut ext, || self.forced.iter().for_each(|force| force()));
+        ext
+    }
+}
+
+#[cfg(not(feature = "test-helpers"))]
+impl ExtBuilder {
+    fn apply_forced(&self, ext: runtime_io::TestExternalities<Blake2Hasher>) -> runtime_io::TestExternalities<Blake2Hasher> {
+        ext
+    }
+}
+
+impl ExtBuilder {
+    /// Batteries get `predicted_id(0)`, `predicted_id(1)`... in registration order.
+    fn with_deterministic_ids(mut self) -> Self {
+        self.deterministic_ids = true;
+        self
+    }
+
+    fn build(self) -> runtime_io::TestExternalities<Blake2Hasher> {
+        TestIdGenerator::set_counter(if self.deterministic_ids { Some(0) } else { None });
+        let mut t = system::GenesisConfig::<Test>::default().build_storage().unwrap().0;
+        t.extend(balances::GenesisConfig::<Test> {
+            transaction_base_fee: 0,
+            transaction_byte_fee: 0,
+            existential_deposit: 0,
+            transfer_fee: 0,
+            creation_fee: 0,
+            balances: vec![(OWNER, 1_000), (2, 1_000), (MANUFACTURER, 1_000)],
+            vesting: vec![],
+        }.build_storage().unwrap().0);
+        t.extend(GenesisConfig::<Test> {
+            preorder_lock_period: PREORDER_LOCK_PERIOD,
+            rewards_per_block: REWARDS_PER_BLOCK,
+            co_owner_approval_threshold: 2,
+            challenge_period_blocks: CHALLENGE_PERIOD_BLOCKS,
+            probation_trade_threshold: PROBATION_TRADE_THRESHOLD,
+            blocks_per_checkpoint: BLOCKS_PER_CHECKPOINT,
+            abandonment_period: ABANDONMENT_PERIOD,
+            escheat_notice_period: ESCHEAT_NOTICE_PERIOD,
+            escheat_pool: ESCHEAT_POOL,
+            key_retention_blocks: KEY_RETENTION_BLOCKS,
+            receipt_retention_blocks: RECEIPT_RETENTION_BLOCKS,
+            require_identity_for_trade: false,
+            report_era_length: REPORT_ERA_LENGTH,
+            review_stake_threshold: REVIEW_STAKE_THRESHOLD,
+            blocks_per_day: BLOCKS_PER_DAY,
+            promotion_threshold: PROMOTION_THRESHOLD,
+            rebalance_expiry_blocks: REBALANCE_EXPIRY_BLOCKS,
+            audit_interval: AUDIT_INTERVAL,
+            audit_sample_size: 2,
+            audit_deadline_blocks: AUDIT_DEADLINE_BLOCKS,
+            station_bond: 0,
+            store_request_expiry_blocks: STORE_REQUEST_EXPIRY_BLOCKS,
+        }.build_storage().unwrap().0);
+        self.apply_forced(t.into())
+    }
+}
+
+fn new_test_ext() -> runtime_io::TestExternalities<Blake2Hasher> {
+    ExtBuilder::default().build()
+}
+
+fn predicted_id(n: u64) -> H256 {
+    TestIdGenerator::predicted_id(n)
+}
+
+/// Registers `STATION` and a battery owned by `OWNER` in it.
+fn station_with_battery() -> H256 {
+    assert_ok!(BatteryModule::register_station(Origin::signed(STATION)));
+    assert_ok!(BatteryModule::register_battery(Origin::signed(STATION), OWNER));
+    BatteryModule::battery_by_index(0)
+}
+
+#[test]
+fn compact_layout_shrinks_a_typical_battery() {
+    let legacy = LegacyBattery {
+        id: H256::from([1u8; 32]),
+        owner: OWNER,
+        station: Some(STATION),
+        tradable: false,
+        registry_time: 1_556_000_000u64,
+    };
+    let compact = Battery {
+        owner: OWNER,
+        custody: Custody::Station(STATION),
+        registered_at: 100_000u64,
+    };
+
+    // id (32) + owner (8) + station (1 + 8) + tradable (1) + moment (8)
+    assert_eq!(legacy.encode().len(), 58);
+    // owner (8) + custody (1 + 8) + compact block number (4)
+    assert_eq!(compact.encode().len(), 21);
+}
+
+#[test]
+fn migration_converts_legacy_batteries() {
+    with_externalities(&mut new_test_ext(), || {
+        <StorageVersion<Test>>::put(0);
+        System::set_block_number(7);
+        let ids = [H256::from([1u8; 32]), H256::from([2u8; 32])];
+        let legacy = [
+            LegacyBattery { id: ids[0], owner: OWNER, station: Some(STATION), tradable: true, registry_time: 42u64 },
+            LegacyBattery { id: ids[1], owner: OWNER, station: None, tradable: false, registry_time: 43u64 },
+        ];
+        for (index, old) in legacy.iter().enumerate() {
+            runtime_io::set_storage(&BatteryModule::battery_storage_key(old.id), &old.encode());
+            <AllBatteriesArray<Test>>::insert(index as u64, old.id);
+        }
+        <AllBatteriesCount<Test>>::put(2);
+
+        BatteryModule::migrate_to_compact_batteries();
+
+        assert_eq!(BatteryModule::storage_version(), 1);
+        let first = BatteryModule::batteries(ids[0]);
+        assert_eq!(first.owner(), &OWNER);
+        assert_eq!(first.station(), Some(&STATION));
+        assert!(first.is_tradable());
+        assert_eq!(first.registered_at(), 7);
+        assert_eq!(BatteryModule::legacy_registry_time(ids[0]), Some(42));
+
+        let second = BatteryModule::batteries(ids[1]);
+        assert_eq!(second.station(), None);
+        assert!(!second.is_tradable());
+        assert_eq!(BatteryModule::legacy_registry_time(ids[1]), Some(43));
+    });
+}
+
+#[test]
+fn migration_from_v1_to_v2_preserves_data() {
+    with_externalities(&mut new_test_ext(), || {
+        <StorageVersion<Test>>::put(0);
+        System::set_block_number(3);
+        let id = H256::from([5u8; 32]);
+        let old = LegacyBattery { id, owner: 2u64, station: Some(STATION), tradable: false, registry_time: 99u64 };
+        let encoded = old.encode();
+        runtime_io::set_storage(&BatteryModule::battery_storage_key(id), &encoded);
+        <AllBatteriesArray<Test>>::insert(0, id);
+        <AllBatteriesCount<Test>>::put(1);
+
+        BatteryModule::migrate_to_compact_batteries();
+
+        let decoded = LegacyBattery::<H256, u64, u64>::decode(&mut &encoded[..]).unwrap();
+        let migrated = BatteryModule::batteries(decoded.id);
+        assert_eq!(migrated.owner(), &decoded.owner);
+        assert_eq!(migrated.station(), decoded.station.as_ref());
+        assert_eq!(migrated.is_tradable(), decoded.tradable);
+        assert_eq!(BatteryModule::legacy_registry_time(decoded.id), Some(decoded.registry_time));
+        assert_eq!(migrated.registered_at(), 3);
+
+        let converted: Battery<u64, u64> = decoded.into();
+        assert_eq!(converted.registered_at(), 0);
+    });
+}
+
+#[test]
+fn migration_converts_a_bounded_batch_per_block() {
+    with_externalities(&mut new_test_ext(), || {
+        <StorageVersion<Test>>::put(0);
+        let count = MIGRATION_BATCH_SIZE + 1;
+        let id_of = |index: u64| H256::from([index as u8 + 1; 32]);
+        for index in 0..count {
+            let id = id_of(index);
+            let old = LegacyBattery { id, owner: OWNER, station: Some(STATION), tradable: false, registry_time: 42u64 };
+            runtime_io::set_storage(&BatteryModule::battery_storage_key(id), &old.encode());
+            <AllBatteriesArray<Test>>::insert(index, id);
+        }
+        <AllBatteriesCount<Test>>::put(count);
+
+        BatteryModule::migrate_to_compact_batteries();
+        assert_eq!(BatteryModule::storage_version(), 0);
+        assert_eq!(BatteryModule::migration_cursor(), MIGRATION_BATCH_SIZE);
+        assert_eq!(BatteryModule::legacy_registry_time(id_of(MIGRATION_BATCH_SIZE - 1)), Some(42));
+        assert_eq!(BatteryModule::legacy_registry_time(id_of(MIGRATION_BATCH_SIZE)), None);
+
+        BatteryModule::migrate_to_compact_batteries();
+        assert_eq!(BatteryModule::storage_version(), 1);
+        assert_eq!(BatteryModule::migration_cursor(), 0);
+        assert_eq!(BatteryModule::batteries(id_of(MIGRATION_BATCH_SIZE)).station(), Some(&STATION));
+    });
+}
+
+#[test]
+fn battery_calls_wait_for_the_migration() {
+    with_externalities(&mut new_test_ext(), || {
+        let id = station_with_battery();
+        assert_ok!(BatteryModule::set_tradable(Origin::signed(OWNER), id, true));
+        <StorageVersion<Test>>::put(0);
+        let old = LegacyBattery { id, owner: OWNER, station: Some(STATION), tradable: true, registry_time: 42u64 };
+        runtime_io::set_storage(&BatteryModule::battery_storage_key(id), &old.encode());
+
+        assert_noop!(BatteryModule::trade_battery(Origin::signed(STATION), id, 2, None), "Battery storage is being migrated");
+        assert_noop!(BatteryModule::fetch_from_station(Origin::signed(OWNER), id), "Battery storage is being migrated");
+        assert_noop!(BatteryModule::register_battery(Origin::signed(STATION), OWNER), "Battery storage is being migrated");
+        assert_eq!(BatteryModule::can_trade(&STATION, id, &2), Err("Battery storage is being migrated"));
+
+        BatteryModule::migrate_to_compact_batteries();
+        assert_ok!(BatteryModule::trade_battery(Origin::signed(STATION), id, 2, None));
+        assert_eq!(BatteryModule::batteries(id).owner(), &2);
+    });
+}
+
+#[test]
+fn migration_runs_only_once() {
+    with_externalities(&mut new_test_ext(), || {
+        let id = station_with_battery();
+        BatteryModule::migrate_to_compact_batteries();
+        BatteryModule::migrate_to_compact_batteries();
+
+        assert_eq!(BatteryModule::storage_version(), 1);
+        assert_eq!(BatteryModule::batteries(id).station(), Some(&STATION));
+        assert_eq!(BatteryModule::legacy_registry_time(id), None);
+    });
+}
+
+#[test]
+fn station_location_must_be_valid() {
+    with_externalities(&mut new_test_ext(), || {
+        assert_noop!(BatteryModule::set_station_location(Origin::signed(STATION), 0, 0), "Sender is not a station");
+        assert_ok!(BatteryModule::register_station(Origin::signed(STATION)));
+        assert_noop!(BatteryModule::set_station_location(Origin::signed(STATION), 90_000_001, 0), "Latitude out of range");
+        assert_noop!(BatteryModule::set_station_location(Origin::signed(STATION), 0, -180_000_001), "Longitude out of range");
+        assert_ok!(BatteryModule::set_station_location(Origin::signed(STATION), 48_856_600, 2_352_200));
+        assert_eq!(BatteryModule::station_location(STATION), Some((48_856_600, 2_352_200)));
+    });
+}
+
+#[test]
+fn signed_inquiries_expire() {
+    with_externalities(&mut new_test_ext(), || {
+        System::set_block_number(10);
+        assert!(BatteryModule::is_inquiry_signature_current(10));
+        assert!(BatteryModule::is_inquiry_signature_current(10 + MAX_INQUIRY_VALIDITY_BLOCKS));
+        // Already expired, or valid for longer than allowed.
+        assert!(!BatteryModule::is_inquiry_signature_current(9));
+        assert!(!BatteryModule::is_inquiry_signature_current(11 + MAX_INQUIRY_VALIDITY_BLOCKS));
+    });
+}
+
+#[test]
+fn discovery_lists_nearest_stations_with_tradable_batteries() {
+    with_externalities(&mut new_test_ext(), || {
+        // Stations 10, 11 and 12 at increasing distance east of the user, 13 without a location.
+        for (station, lon) in [(10u64, 3_000_000i64), (11, 1_000_000), (12, 2_000_000), (13, 0)].iter() {
+            assert_ok!(BatteryModule::register_station(Origin::signed(*station)));
+            assert_ok!(BatteryModule::register_battery(Origin::signed(*station), OWNER));
+            if *station != 13 {
+                assert_ok!(BatteryModule::set_station_location(Origin::signed(*station), 0, *lon));
+            }
+        }
+        for index in [0u64, 1, 3].iter() {
+            assert_ok!(BatteryModule::set_tradable(Origin::signed(OWNER), BatteryModule::battery_by_index(*index), true));
+        }
+
+        let found = BatteryModule::find_stations_with_tradable_batteries(BatteryGrade::C, 0, 0, 0, 5);
+        assert_eq!(found, vec![(11, 111_194), (10, 333_584)]);
+        assert_eq!(BatteryModule::find_stations_with_tradable_batteries(BatteryGrade::C, 0, 0, 0, 1), vec![(11, 111_194)]);
+    });
+}
+
+#[test]
+fn discovery_filters_on_grade_and_charge() {
+    with_externalities(&mut new_test_ext(), || {
+        for (station, lon) in [(10u64, 1_000_000i64), (11, 2_000_000)].iter() {
+            assert_ok!(BatteryModule::register_station(Origin::signed(*station)));
+            assert_ok!(BatteryModule::register_battery(Origin::signed(*station), OWNER));
+            assert_ok!(BatteryModule::set_station_location(Origin::signed(*station), 0, *lon));
+        }
+        let (near, far) = (BatteryModule::battery_by_index(0), BatteryModule::battery_by_index(1));
+        for id in [near, far].iter() {
+            assert_ok!(BatteryModule::set_tradable(Origin::signed(OWNER), *id, true));
+        }
+        <BatteryHealth<Test>>::insert(near, 75);
+        <TargetSoc<Test>>::insert(near, 40);
+        <BatteryHealth<Test>>::insert(far, 95);
+        <TargetSoc<Test>>::insert(far, 90);
+
+        let found = |min_grade, min_soc| -> Vec<u64> {
+            BatteryModule::find_stations_with_tradable_batteries(min_grade, min_soc, 0, 0, 5)
+                .into_iter()
+                .map(|(station, _)| station)
+                .collect()
+        };
+        assert_eq!(found(BatteryGrade::C, 0), vec![10, 11]);
+        assert_eq!(found(BatteryGrade::B, 40), vec![10, 11]);
+        assert_eq!(found(BatteryGrade::A, 0), vec![11]);
+        assert_eq!(found(BatteryGrade::B, 80), vec![11]);
+        assert!(found(BatteryGrade::A, 95).is_empty());
+
+        // Unaudited batteries that aren't being charged only match the lowest bar.
+        <BatteryHealth<Test>>::remove(near);
+        <TargetSoc<Test>>::remove(near);
+        assert_eq!(found(BatteryGrade::C, 0), vec![10, 11]);
+        assert_eq!(found(BatteryGrade::C, 1), vec![11]);
+        assert_eq!((BatteryGrade::from_health(90), BatteryGrade::from_health(89)), (BatteryGrade::A, BatteryGrade::B));
+        assert_eq!((BatteryGrade::from_health(70), BatteryGrade::from_health(69)), (BatteryGrade::B, BatteryGrade::C));
+    });
+}
+
+#[test]
+fn recommended_station_weighs_distance_against_utilization() {
+    with_externalities(&mut new_test_ext(), || {
+        let id = station_with_battery();
+        assert_ok!(BatteryModule::set_station_location(Origin::signed(STATION), 0, 0));
+        // 111 km away and idle, 56 km away and at capacity, 22 km away and half busy.
+        for (station, lon, capacity, swaps) in [(11u64, 1_000_000i64, 10u32, 0), (12, 500_000, 1, 1), (13, 200_000, 10, 5)].iter() {
+            assert_ok!(BatteryModule::register_station(Origin::signed(*station)));
+            assert_ok!(BatteryModule::set_station_location(Origin::signed(*station), 0, *lon));
+            assert_ok!(BatteryModule::set_swap_capacity(Origin::signed(*station), *capacity));
+            for _ in 0..*swaps {
+                BatteryModule::record_swap(station);
+            }
+        }
+
+        let candidates = vec![2, STATION, 11, 12, 13];
+        assert_eq!(BatteryModule::recommend_store_station(id, candidates.clone()), Some(13));
+        for _ in 0..4 {
+            BatteryModule::record_swap(&13);
+        }
+        assert_eq!(BatteryModule::recommend_store_station(id, candidates.clone()), Some(11));
+        assert_eq!(BatteryModule::recommend_store_station(id, vec![2, STATION, 12]), None);
+        assert_eq!(BatteryModule::recommend_store_station(H256::from([7u8; 32]), candidates), None);
+    });
+}
+
+#[test]
+fn recommended_station_follows_unmet_demand() {
+    with_externalities(&mut new_test_ext(), || {
+        let id = station_with_battery();
+        assert_ok!(BatteryModule::set_station_location(Origin::signed(STATION), 0, 0));
+        // 20 km west, 40 km east, and a busy station 4 km past the second one.
+        for (station, lon) in [(11u64, -180_000i64), (12, 360_000), (14, 400_000)].iter() {
+            assert_ok!(BatteryModule::register_station(Origin::signed(*station)));
+            assert_ok!(BatteryModule::set_station_location(Origin::signed(*station), 0, *lon));
+        }
+        let candidates = vec![11, 12];
+        assert_eq!(BatteryModule::recommend_store_station(id, candidates.clone()), Some(11));
+
+        for _ in 0..3 {
+            BatteryModule::record_swap(&14);
+        }
+        assert_eq!(BatteryModule::unmet_demand_near(&12, 0, 360_000), 3);
+        assert_eq!(BatteryModule::recommend_store_station(id, candidates.clone()), Some(12));
+
+        // The demand only counts as far as the candidate has swaps to spare.
+        assert_ok!(BatteryModule::set_swap_capacity(Origin::signed(12), 1));
+        assert_eq!(BatteryModule::recommend_store_station(id, candidates.clone()), Some(11));
+        assert_ok!(BatteryModule::set_swap_capacity(Origin::signed(12), 10));
+        assert_eq!(BatteryModule::recommend_store_station(id, candidates.clone()), Some(12));
+
+        // Batteries already listed nearby cover the demand.
+        for _ in 0..3 {
+            assert_ok!(BatteryModule::register_battery(Origin::signed(14), OWNER));
+            let listed = BatteryModule::battery_by_index(BatteryModule::all_batteries_count() - 1);
+            assert_ok!(BatteryModule::set_tradable(Origin::signed(OWNER), listed, true));
+        }
+        assert_eq!(BatteryModule::unmet_demand_near(&12, 0, 360_000), 0);
+        assert_eq!(BatteryModule::recommend_store_station(id, candidates), Some(11));
+    });
+}
+
+#[test]
+fn session_key_can_store_but_not_trade() {
+    with_externalities(&mut new_test_ext(), || {
+        const KEY: u64 = 20;
+        let id = station_with_battery();
+        assert_ok!(BatteryModule::set_session_key(Origin::signed(STATION), KEY));
+        assert_eq!(BatteryModule::key_station(KEY), Some(STATION));
+
+        assert_ok!(BatteryModule::set_tradable(Origin::signed(OWNER), id, true));
+        assert_noop!(BatteryModule::trade_battery(Origin::signed(KEY), id, 2, None), "Sender is not a station");
+
+        assert_ok!(BatteryModule::fetch_from_station(Origin::signed(OWNER), id));
+        assert_ok!(BatteryModule::store_to_station(Origin::signed(KEY), id, None));
+        assert_eq!(BatteryModule::batteries(id).station(), Some(&STATION));
+        assert_eq!(BatteryModule::batteries_count_in_station(STATION), 1);
+    });
+}
+
+#[test]
+fn rotated_session_key_is_rejected() {
+    with_externalities(&mut new_test_ext(), || {
+        const OLD_KEY: u64 = 20;
+        const NEW_KEY: u64 = 21;
+        let id = station_with_battery();
+        assert_ok!(BatteryModule::set_session_key(Origin::signed(STATION), OLD_KEY));
+        assert_ok!(BatteryModule::set_session_key(Origin::signed(STATION), NEW_KEY));
+        assert_eq!(BatteryModule::key_station(OLD_KEY), None);
+        assert_eq!(BatteryModule::session_key(STATION), Some(NEW_KEY));
+
+        assert_ok!(BatteryModule::fetch_from_station(Origin::signed(OWNER), id));
+        assert_noop!(BatteryModule::store_to_station(Origin::signed(OLD_KEY), id, None), "Sender is not a station");
+        assert_ok!(BatteryModule::store_to_station(Origin::signed(NEW_KEY), id, None));
+    });
+}
+
+#[test]
+fn session_key_must_be_free() {
+    with_externalities(&mut new_test_ext(), || {
+        assert_ok!(BatteryModule::register_station(Origin::signed(STATION)));
+        assert_ok!(BatteryModule::register_station(Origin::signed(11)));
+        assert_noop!(BatteryModule::set_session_key(Origin::signed(STATION), 11), "Session key can't be a station");
+        assert_ok!(BatteryModule::set_session_key(Origin::signed(STATION), 20));
+        assert_noop!(BatteryModule::set_session_key(Origin::signed(11), 20), "Session key already in use");
+        assert_noop!(BatteryModule::register_station(Origin::signed(20)), "Account is a session key of a station");
+    });
+}
+
+#[test]
+fn trade_keeps_the_sellers_other_batteries_listed() {
+    with_externalities(&mut new_test_ext(), || {
+        let first = station_with_battery();
+        assert_ok!(BatteryModule::register_battery(Origin::signed(STATION), OWNER));
+        let second = BatteryModule::battery_by_index(1);
+
+        assert_ok!(BatteryModule::set_tradable(Origin::signed(OWNER), first, true));
+        assert_ok!(BatteryModule::trade_battery(Origin::signed(STATION), first, 2, None));
+
+        assert_eq!(BatteryModule::owned_batteries_count(OWNER), 1);
+        assert_eq!(BatteryModule::battery_of_owner_by_index((OWNER, 0)), second);
+        assert_eq!(BatteryModule::owned_battery_index(second), 0);
+        assert_eq!(BatteryModule::owned_batteries_count(2), 1);
+        assert_eq!(BatteryModule::battery_of_owner_by_index((2, 0)), first);
+        assert_eq!(BatteryModule::owned_battery_index(first), 0);
+    });
+}
+
+/// Registers a model for `MANUFACTURER` and a battery of it the manufacturer owns.
+fn manufacturer_with_battery() -> (H256, H256) {
+    assert_ok!(BatteryModule::register_station(Origin::signed(STATION)));
+    assert_ok!(BatteryModule::register_model(Origin::signed(MANUFACTURER), b"LFP-48V".to_vec()));
+    let model_id = (MANUFACTURER, b"LFP-48V".to_vec()).using_encoded(BlakeTwo256::hash);
+    assert_ok!(BatteryModule::register_battery(Origin::signed(STATION), MANUFACTURER));
+    let id = BatteryModule::battery_by_index(0);
+    assert_ok!(BatteryModule::set_battery_model(Origin::signed(MANUFACTURER), id, model_id));
+    (model_id, id)
+}
+
+fn last_preorder_id(buyer: u64, model_id: H256) -> H256 {
+    (buyer, model_id, BatteryModule::preorder_nonce() - 1).using_encoded(BlakeTwo256::hash)
+}
+
+#[test]
+fn model_names_are_validated() {
+    with_externalities(&mut new_test_ext(), || {
+        assert_noop!(BatteryModule::register_model(Origin::signed(MANUFACTURER), vec![]), "Model name can't be empty");
+        assert_noop!(
+            BatteryModule::register_model(Origin::signed(MANUFACTURER), vec![b'a'; MAX_MODEL_NAME_LEN + 1]),
+            "Model name too long"
+        );
+        assert_ok!(BatteryModule::register_model(Origin::signed(MANUFACTURER), b"LFP-48V".to_vec()));
+        assert_noop!(BatteryModule::register_model(Origin::signed(MANUFACTURER), b"LFP-48V".to_vec()), "Model already exists");
+    });
+}
+
+#[test]
+fn trades_under_warranty_pass_the_certificate_on() {
+    with_externalities(&mut new_test_ext(), || {
+        System::set_block_number(1);
+        let (model_id, id) = manufacturer_with_battery();
+        assert_noop!(BatteryModule::set_model_warranty(Origin::signed(MANUFACTURER), model_id, 50), "Feature is disabled");
+        enable(&[Feature::Warranties]);
+        assert_noop!(
+            BatteryModule::set_model_warranty(Origin::signed(OWNER), model_id, 50),
+            "You are not the manufacturer of this model"
+        );
+        assert_ok!(BatteryModule::set_model_warranty(Origin::signed(MANUFACTURER), model_id, 50));
+        assert_eq!(BatteryModule::warranty_of(id), Some((MANUFACTURER, 51)));
+
+        assert_ok!(BatteryModule::set_tradable(Origin::signed(MANUFACTURER), id, true));
+        assert_ok!(BatteryModule::trade_battery(Origin::signed(STATION), id, OWNER, None));
+        let cert = BatteryModule::warranty_cert((id, OWNER)).unwrap();
+        assert_eq!((cert.manufacturer, cert.issued_at, cert.expires_at), (MANUFACTURER, 1, 51));
+        assert!(battery_events().contains(&RawEvent::WarrantyCertificateIssued(id, OWNER)));
+
+        // The seller's certificate is invalidated.
+        assert_ok!(BatteryModule::set_tradable(Origin::signed(OWNER), id, true));
+        assert_ok!(BatteryModule::trade_battery(Origin::signed(STATION), id, 2, None));
+        assert_eq!(BatteryModule::warranty_cert((id, OWNER)), None);
+        assert!(BatteryModule::warranty_cert((id, 2)).is_some());
+
+        // Once the warranty ran out, trades issue no certificate.
+        System::set_block_number(51);
+        assert_eq!(BatteryModule::warranty_of(id), None);
+        assert_ok!(BatteryModule::set_tradable(Origin::signed(2), id, true));
+        assert_ok!(BatteryModule::trade_battery(Origin::signed(STATION), id, OWNER, None));
+        assert_eq!(BatteryModule::warranty_cert((id, 2)), None);
+        assert_eq!(BatteryModule::warranty_cert((id, OWNER)), None);
+    });
+}
+
+#[test]
+fn preorder_is_fulfilled_by_the_manufacturer() {
+    with_externalities(&mut new_test_ext(), || {
+        let (model_id, id) = manufacturer_with_battery();
+        assert_ok!(BatteryModule::place_preorder(Origin::signed(OWNER), model_id, 300));
+        let preorder_id = last_preorder_id(OWNER, model_id);
+        assert_eq!(Balances::reserved_balance(OWNER), 300);
+        assert_eq!(BatteryModule::preorders_of(OWNER), vec![preorder_id]);
+
+        assert_noop!(
+            BatteryModule::fulfill_preorder(Origin::signed(STATION), preorder_id, id),
+            "You are not the manufacturer of this model"
+        );
+        assert_ok!(BatteryModule::fulfill_preorder(Origin::signed(MANUFACTURER), preorder_id, id));
+
+        assert_eq!(BatteryModule::batteries(id).owner(), &OWNER);
+        assert_eq!(BatteryModule::battery_of_owner_by_index((OWNER, 0)), id);
+        assert_eq!(BatteryModule::owned_batteries_count(MANUFACTURER), 0);
+        assert_eq!(Balances::reserved_balance(OWNER), 0);
+        assert_eq!(Balances::free_balance(OWNER), 700);
+        assert_eq!(Balances::free_balance(MANUFACTURER), 1_300);
+        assert_eq!(BatteryModule::preorder(preorder_id), None);
+        assert!(BatteryModule::preorders_of(OWNER).is_empty());
+    });
+}
+
+#[test]
+fn preorder_is_not_fulfilled_without_its_funds() {
+    with_externalities(&mut new_test_ext(), || {
+        let (model_id, id) = manufacturer_with_battery();
+        assert_ok!(BatteryModule::place_preorder(Origin::signed(OWNER), model_id, 300));
+        let preorder_id = last_preorder_id(OWNER, model_id);
+        let _ = Balances::slash_reserved(&OWNER, 100);
+
+        assert_noop!(
+            BatteryModule::fulfill_preorder(Origin::signed(MANUFACTURER), preorder_id, id),
+            "Pre-order funds are no longer reserved"
+        );
+        assert_eq!(Balances::reserved_balance(OWNER), 200);
+        assert_eq!(Balances::free_balance(MANUFACTURER), 1_000);
+        assert_eq!(BatteryModule::batteries(id).owner(), &MANUFACTURER);
+    });
+}
+
+#[test]
+fn preorder_needs_a_battery_of_the_ordered_model() {
+    with_externalities(&mut new_test_ext(), || {
+        let (_, id) = manufacturer_with_battery();
+        assert_ok!(BatteryModule::register_model(Origin::signed(MANUFACTURER), b"NMC-36V".to_vec()));
+        let other_model = (MANUFACTURER, b"NMC-36V".to_vec()).using_encoded(BlakeTwo256::hash);
+        assert_ok!(BatteryModule::place_preorder(Origin::signed(OWNER), other_model, 300));
+        let preorder_id = last_preorder_id(OWNER, other_model);
+
+        assert_noop!(
+            BatteryModule::fulfill_preorder(Origin::signed(MANUFACTURER), preorder_id, id),
+            "Battery is not of the ordered model"
+        );
+    });
+}
+
+#[test]
+fn preorder_can_be_cancelled_after_unlock() {
+    with_externalities(&mut new_test_ext(), || {
+        let (model_id, _) = manufacturer_with_battery();
+        System::set_block_number(1);
+        assert_ok!(BatteryModule::place_preorder(Origin::signed(OWNER), model_id, 300));
+        let preorder_id = last_preorder_id(OWNER, model_id);
+        assert_eq!(BatteryModule::preorder(preorder_id).unwrap().unlock_at, 1 + PREORDER_LOCK_PERIOD);
+
+        System::set_block_number(PREORDER_LOCK_PERIOD);
+        assert_noop!(BatteryModule::cancel_preorder(Origin::signed(OWNER), preorder_id), "Pre-order is still locked");
+        System::set_block_number(1 + PREORDER_LOCK_PERIOD);
+        assert_noop!(
+            BatteryModule::cancel_preorder(Origin::signed(2), preorder_id),
+            "You are not the buyer of this pre-order"
+        );
+        assert_ok!(BatteryModule::cancel_preorder(Origin::signed(OWNER), preorder_id));
+
+        assert_eq!(Balances::reserved_balance(OWNER), 0);
+        assert_eq!(Balances::free_balance(OWNER), 1_000);
+        assert_eq!(BatteryModule::preorder(preorder_id), None);
+        assert!(BatteryModule::preorders_of(OWNER).is_empty());
+    });
+}
+
+#[test]
+fn staked_battery_earns_rewards() {
+    with_externalities(&mut new_test_ext(), || {
+        System::set_block_number(1);
+        let id = station_with_battery();
+        assert_eq!(BatteryModule::stake_start(id), Some(1));
+
+        System::set_block_number(5);
+        assert_eq!(BatteryModule::pending_staking_rewards(id), 4 * REWARDS_PER_BLOCK);
+        assert_noop!(BatteryModule::claim_staking_rewards(Origin::signed(2), id), "You are not the owner of this battery");
+        assert_ok!(BatteryModule::claim_staking_rewards(Origin::signed(OWNER), id));
+        assert_eq!(Balances::free_balance(OWNER), 1_000 + 4 * REWARDS_PER_BLOCK);
+        assert_eq!(BatteryModule::stake_start(id), Some(5));
+        assert_eq!(BatteryModule::pending_staking_rewards(id), 0);
+    });
+}
+
+#[test]
+fn listed_or_fetched_battery_is_not_staked() {
+    with_externalities(&mut new_test_ext(), || {
+        System::set_block_number(1);
+        let id = station_with_battery();
+
+        // Listing pays out what was earned and stops the accrual.
+        System::set_block_number(3);
+        assert_ok!(BatteryModule::set_tradable(Origin::signed(OWNER), id, true));
+        assert_eq!(Balances::free_balance(OWNER), 1_000 + 2 * REWARDS_PER_BLOCK);
+        assert_eq!(BatteryModule::stake_start(id), None);
+        assert_noop!(BatteryModule::claim_staking_rewards(Origin::signed(OWNER), id), "Battery is not staked");
+
+        // Unlisting stakes it again, fetching pays out and stops it.
+        assert_ok!(BatteryModule::set_tradable(Origin::signed(OWNER), id, false));
+        System::set_block_number(6);
+        assert_ok!(BatteryModule::fetch_from_station(Origin::signed(OWNER), id));
+        assert_eq!(Balances::free_balance(OWNER), 1_000 + 5 * REWARDS_PER_BLOCK);
+        assert_eq!(BatteryModule::stake_start(id), None);
+    });
+}
+
+fn battery_events() -> Vec<Event<Test>> {
+    System::events().into_iter().filter_map(|record| match record.event {
+        TestEvent::battery(event) => Some(event),
+        _ => None,
+    }).collect()
+}
+
+fn is_deprecation(event: &&Event<Test>) -> bool {
+    match event {
+        RawEvent::DeprecatedCallUsed(_) => true,
+        _ => false,
+    }
+}
+
+#[test]
+fn old_and_new_names_register_the_same_battery() {
+    let register = |deprecated: bool| with_externalities(&mut new_test_ext(), || {
+        assert_ok!(BatteryModule::register_station(Origin::signed(STATION)));
+        if deprecated {
+            assert_ok!(BatteryModule::registry_battery(Origin::signed(STATION), OWNER));
+        } else {
+            assert_ok!(BatteryModule::register_battery(Origin::signed(STATION), OWNER));
+        }
+        let id = BatteryModule::battery_by_index(0);
+        (id, BatteryModule::batteries(id), battery_events())
+    });
+    let (old_id, old_battery, old_events) = register(true);
+    let (new_id, new_battery, new_events) = register(false);
+
+    assert_eq!(old_id, new_id);
+    assert_eq!(old_battery, new_battery);
+    assert_eq!(old_events.last(), Some(&RawEvent::DeprecatedCallUsed(DeprecatedCall::RegistryBattery)));
+    assert_eq!(new_events.iter().filter(is_deprecation).count(), 0);
+    assert_eq!(old_events[..old_events.len() - 1], new_events[..]);
+}
+
+#[test]
+fn old_and_new_names_toggle_tradable_the_same_way() {
+    let toggle = |deprecated: bool| with_externalities(&mut new_test_ext(), || {
+        let id = station_with_battery();
+        let mut states = vec![];
+        for &tradable in [true, false].iter() {
+            if deprecated {
+                assert_ok!(BatteryModule::switch_tradable(Origin::signed(OWNER), id));
+            } else {
+                assert_ok!(BatteryModule::set_tradable(Origin::signed(OWNER), id, tradable));
+            }
+            states.push(BatteryModule::batteries(id));
+        }
+        (states, battery_events())
+    });
+    let (old_states, old_events) = toggle(true);
+    let (new_states, new_events) = toggle(false);
+
+    assert_eq!(old_states, new_states);
+    assert!(new_states[0].is_tradable());
+    assert!(!new_states[1].is_tradable());
+    assert_eq!(old_events.iter().filter(is_deprecation).count(), 2);
+    assert_eq!(new_events.iter().filter(is_deprecation).count(), 0);
+    let without_deprecations: Vec<_> = old_events.into_iter().filter(|event| !is_deprecation(&event)).collect();
+    assert_eq!(without_deprecations, new_events);
+}
+
+#[test]
+fn set_tradable_checks_match_switch_tradable() {
+    with_externalities(&mut new_test_ext(), || {
+        let id = station_with_battery();
+        let missing = H256::from([9u8; 32]);
+        assert_noop!(BatteryModule::set_tradable(Origin::signed(OWNER), missing, true), "Id does not exist");
+        assert_noop!(BatteryModule::switch_tradable(Origin::signed(OWNER), missing), "Id does not exist");
+        assert_noop!(BatteryModule::set_tradable(Origin::signed(2), id, true), "You are not the owner of this battery");
+        assert_noop!(BatteryModule::switch_tradable(Origin::signed(2), id), "You are not the owner of this battery");
+
+        assert_ok!(BatteryModule::fetch_from_station(Origin::signed(OWNER), id));
+        assert_noop!(BatteryModule::set_tradable(Origin::signed(OWNER), id, true), "Battery must be in station");
+        assert_noop!(BatteryModule::switch_tradable(Origin::signed(OWNER), id), "Battery must be in station");
+    });
+}
+
+#[test]
+fn trust_level_is_set_by_root() {
+    with_externalities(&mut new_test_ext(), || {
+        assert_noop!(BatteryModule::set_trust_level(system::RawOrigin::Root.into(), STATION, TrustLevel::Premium), "Not a station!");
+        assert_ok!(BatteryModule::register_station(Origin::signed(STATION)));
+        assert_eq!(BatteryModule::trust_level(STATION), TrustLevel::Basic);
+        assert!(BatteryModule::set_trust_level(Origin::signed(STATION), STATION, TrustLevel::Premium).is_err());
+        assert_ok!(BatteryModule::set_trust_level(system::RawOrigin::Root.into(), STATION, TrustLevel::Premium));
+        assert_eq!(BatteryModule::trust_level(STATION), TrustLevel::Premium);
+    });
+}
+
+#[test]
+fn premium_station_upgrades_battery_model() {
+    with_externalities(&mut new_test_ext(), || {
+        System::set_block_number(3);
+        let (old_model, id) = manufacturer_with_battery();
+        assert_ok!(BatteryModule::register_model(Origin::signed(MANUFACTURER), b"NMC-36V".to_vec()));
+        let new_model = (MANUFACTURER, b"NMC-36V".to_vec()).using_encoded(BlakeTwo256::hash);
+
+        assert_noop!(
+            BatteryModule::upgrade_battery_model(Origin::signed(STATION), id, new_model),
+            "Station must be premium"
+        );
+        assert_ok!(BatteryModule::set_trust_level(system::RawOrigin::Root.into(), STATION, TrustLevel::Premium));
+        assert_noop!(
+            BatteryModule::upgrade_battery_model(Origin::signed(STATION), id, old_model),
+            "Battery is already of this model"
+        );
+        assert_ok!(BatteryModule::upgrade_battery_model(Origin::signed(STATION), id, new_model));
+
+        assert_eq!(BatteryModule::battery_model(id), Some(new_model));
+        assert_eq!(BatteryModule::maintenance_log_count(id), 1);
+        assert_eq!(BatteryModule::maintenance_log((id, 0)), Some(MaintenanceRecord {
+            station: STATION,
+            at: 3,
+            kind: MaintenanceKind::ModelUpgrade(Some(old_model), new_model),
+        }));
+        assert_eq!(battery_events().last(), Some(&RawEvent::BatteryModelUpgraded(id, new_model)));
+    });
+}
+
+#[test]
+fn only_the_holding_station_upgrades_battery_model() {
+    with_externalities(&mut new_test_ext(), || {
+        let (_, id) = manufacturer_with_battery();
+        assert_ok!(BatteryModule::register_model(Origin::signed(MANUFACTURER), b"NMC-36V".to_vec()));
+        let new_model = (MANUFACTURER, b"NMC-36V".to_vec()).using_encoded(BlakeTwo256::hash);
+        assert_ok!(BatteryModule::register_station(Origin::signed(11)));
+        assert_ok!(BatteryModule::set_trust_level(system::RawOrigin::Root.into(), 11, TrustLevel::Premium));
+
+        assert_noop!(
+            BatteryModule::upgrade_battery_model(Origin::signed(11), id, new_model),
+            "Sender must be the station of this battery"
+        );
+        assert_noop!(
+            BatteryModule::upgrade_battery_model(Origin::signed(11), H256::from([9u8; 32]), new_model),
+            "Battery does not exist"
+        );
+    });
+}
+
+#[test]
+fn inactive_owners_are_rejected() {
+    with_externalities(&mut new_test_ext(), || {
+        let id = station_with_battery();
+        assert_ok!(BatteryModule::fetch_from_station(Origin::signed(OWNER), id));
+        reap(OWNER);
+
+        assert_noop!(BatteryModule::store_to_station(Origin::signed(STATION), id, None), "Owner account is inactive");
+        assert_noop!(BatteryModule::set_tradable(Origin::signed(OWNER), id, true), "Owner account is inactive");
+    });
+}
+
+#[test]
+fn trade_to_inactive_account_is_rejected() {
+    with_externalities(&mut new_test_ext(), || {
+        let id = station_with_battery();
+        assert_ok!(BatteryModule::set_tradable(Origin::signed(OWNER), id, true));
+        reap(2);
+
+        assert_noop!(BatteryModule::trade_battery(Origin::signed(STATION), id, 2, None), "Owner account is inactive");
+        assert_ok!(BatteryModule::trade_battery(Origin::signed(STATION), id, 3, None));
+    });
+}
+
+#[test]
+fn root_rescues_orphaned_battery() {
+    with_externalities(&mut new_test_ext(), || {
+        let id = station_with_battery();
+        assert_noop!(
+            BatteryModule::reassign_orphaned(system::RawOrigin::Root.into(), id, 2),
+            "Owner account is still active"
+        );
+        reap(OWNER);
+        assert!(BatteryModule::reassign_orphaned(Origin::signed(2), id, 2).is_err());
+        assert_ok!(BatteryModule::reassign_orphaned(system::RawOrigin::Root.into(), id, 2));
+
+        assert_eq!(BatteryModule::batteries(id).owner(), &2);
+        assert_eq!(BatteryModule::owned_batteries_count(OWNER), 0);
+        assert_eq!(BatteryModule::battery_of_owner_by_index((2, 0)), id);
+        assert_eq!(battery_events().last(), Some(&RawEvent::OrphanRescued(id, OWNER, 2)));
+        assert_ok!(BatteryModule::fetch_from_station(Origin::signed(2), id));
+    });
+}
+
+fn approve_listing(id: H256, co_owners: &[u64]) {
+    for co_owner in co_owners {
+        assert_ok!(BatteryModule::approve_action(Origin::signed(*co_owner), id, BatteryModule::listing_action_hash(id)));
+    }
+}
+
+#[test]
+fn co_owners_fetch_and_list() {
+    with_externalities(&mut new_test_ext(), || {
+        let id = station_with_battery();
+        assert_noop!(BatteryModule::add_co_owner(Origin::signed(2), id, 3), "You are not the owner of this battery");
+        assert_ok!(BatteryModule::add_co_owner(Origin::signed(OWNER), id, 2));
+        assert_noop!(BatteryModule::add_co_owner(Origin::signed(OWNER), id, 2), "Already a co-owner");
+
+        approve_listing(id, &[2]);
+        assert_ok!(BatteryModule::set_tradable(Origin::signed(2), id, true));
+        assert_ok!(BatteryModule::set_tradable(Origin::signed(2), id, false));
+        assert_ok!(BatteryModule::fetch_from_station(Origin::signed(2), id));
+        assert_eq!(battery_events().last(), Some(&RawEvent::FetchFromStation(id, 2, OWNER)));
+
+        assert_ok!(BatteryModule::remove_co_owner(Origin::signed(OWNER), id, 2));
+        assert_ok!(BatteryModule::store_to_station(Origin::signed(STATION), id, None));
+        assert_noop!(BatteryModule::fetch_from_station(Origin::signed(2), id), "You are not the owner of this battery");
+    });
+}
+
+#[test]
+fn co_owner_count_is_bounded() {
+    with_externalities(&mut new_test_ext(), || {
+        let id = station_with_battery();
+        for co_owner in 0..MAX_CO_OWNERS as u64 {
+            assert_ok!(BatteryModule::add_co_owner(Origin::signed(OWNER), id, 100 + co_owner));
+        }
+        assert_noop!(BatteryModule::add_co_owner(Origin::signed(OWNER), id, 99), "Too many co-owners");
+    });
+}
+
+#[test]
+fn transfer_needs_co_owner_threshold() {
+    with_externalities(&mut new_test_ext(), || {
+        let id = station_with_battery();
+        assert_ok!(BatteryModule::add_co_owner(Origin::signed(OWNER), id, 2));
+        assert_ok!(BatteryModule::add_co_owner(Origin::signed(OWNER), id, 3));
+        assert_ok!(BatteryModule::add_co_owner(Origin::signed(OWNER), id, 4));
+        approve_listing(id, &[2, 3]);
+        assert_ok!(BatteryModule::set_tradable(Origin::signed(OWNER), id, true));
+
+        let to_five = BatteryModule::transfer_action_hash(id, &5);
+        assert_noop!(BatteryModule::approve_action(Origin::signed(5), id, to_five), "Not a co-owner");
+        // A single co-owner can't push the transfer through on their own.
+        assert_ok!(BatteryModule::approve_action(Origin::signed(2), id, to_five));
+        assert_noop!(BatteryModule::trade_battery(Origin::signed(STATION), id, 5, None), "Not enough co-owner approvals");
+        // Approvals for another recipient don't count.
+        assert_ok!(BatteryModule::approve_action(Origin::signed(3), id, BatteryModule::transfer_action_hash(id, &6)));
+        assert_noop!(BatteryModule::trade_battery(Origin::signed(STATION), id, 5, None), "Not enough co-owner approvals");
+
+        assert_ok!(BatteryModule::approve_action(Origin::signed(3), id, to_five));
+        assert_ok!(BatteryModule::trade_battery(Origin::signed(STATION), id, 5, None));
+
+        assert_eq!(BatteryModule::batteries(id).owner(), &5);
+        assert!(BatteryModule::co_owners(id).is_empty());
+        assert!(BatteryModule::approvals(id).is_empty());
+    });
+}
+
+#[test]
+fn removed_co_owner_approval_is_dropped() {
+    with_externalities(&mut new_test_ext(), || {
+        let id = station_with_battery();
+        assert_ok!(BatteryModule::add_co_owner(Origin::signed(OWNER), id, 2));
+        assert_ok!(BatteryModule::add_co_owner(Origin::signed(OWNER), id, 3));
+        approve_listing(id, &[2, 3]);
+        assert_ok!(BatteryModule::set_tradable(Origin::signed(OWNER), id, true));
+        let to_five = BatteryModule::transfer_action_hash(id, &5);
+        assert_ok!(BatteryModule::approve_action(Origin::signed(2), id, to_five));
+        assert_ok!(BatteryModule::approve_action(Origin::signed(3), id, to_five));
+
+        assert_ok!(BatteryModule::remove_co_owner(Origin::signed(OWNER), id, 3));
+        // With one co-owner left, the threshold is capped at one approval.
+        assert_eq!(BatteryModule::approvals(id), vec![(2, to_five)]);
+        assert_ok!(BatteryModule::trade_battery(Origin::signed(STATION), id, 5, None));
+    });
+}
+
+fn last_challenge_id() -> H256 {
+    match battery_events().last() {
+        Some(RawEvent::ChallengeFiled(challenge_id, _)) => *challenge_id,
+        _ => panic!("no challenge filed"),
+    }
+}
+
+#[test]
+fn challenge_freezes_battery_for_the_period() {
+    with_externalities(&mut new_test_ext(), || {
+        System::set_block_number(1);
+        let id = station_with_battery();
+        assert_ok!(BatteryModule::set_tradable(Origin::signed(OWNER), id, true));
+        assert_noop!(BatteryModule::challenge_battery_ownership(Origin::signed(OWNER), id), "You already own this battery");
+        assert_ok!(BatteryModule::challenge_battery_ownership(Origin::signed(2), id));
+        assert_noop!(BatteryModule::challenge_battery_ownership(Origin::signed(3), id), "Battery is already challenged");
+
+        assert_noop!(BatteryModule::trade_battery(Origin::signed(STATION), id, 3, None), "Battery ownership is being challenged");
+        assert_ok!(BatteryModule::fetch_from_station(Origin::signed(OWNER), id));
+        assert_noop!(BatteryModule::store_to_station(Origin::signed(STATION), id, None), "Battery ownership is being challenged");
+
+        System::set_block_number(1 + CHALLENGE_PERIOD_BLOCKS);
+        assert_ok!(BatteryModule::store_to_station(Origin::signed(STATION), id, None));
+    });
+}
+
+#[test]
+fn arbitrator_awards_battery_to_challenger() {
+    with_externalities(&mut new_test_ext(), || {
+        let id = station_with_battery();
+        assert_ok!(BatteryModule::set_tradable(Origin::signed(OWNER), id, true));
+        assert_ok!(BatteryModule::challenge_battery_ownership(Origin::signed(2), id));
+        let challenge_id = last_challenge_id();
+
+        assert!(BatteryModule::resolve_challenge(Origin::signed(2), challenge_id, 2).is_err());
+        assert_noop!(
+            BatteryModule::resolve_challenge(system::RawOrigin::Root.into(), challenge_id, 3),
+            "Winner must be a party to the challenge"
+        );
+        assert_ok!(BatteryModule::resolve_challenge(system::RawOrigin::Root.into(), challenge_id, 2));
+
+        let battery = BatteryModule::batteries(id);
+        assert_eq!(battery.owner(), &2);
+        assert_eq!(battery.station(), Some(&STATION));
+        assert!(!battery.is_tradable());
+        assert_eq!(BatteryModule::owned_batteries_count(OWNER), 0);
+        assert_eq!(BatteryModule::challenge(challenge_id), None);
+        assert_eq!(BatteryModule::challenge_of(id), None);
+        assert_eq!(battery_events().last(), Some(&RawEvent::ChallengeResolved(challenge_id, 2)));
+    });
+}
+
+#[test]
+fn rejected_challenge_unfreezes_battery() {
+    with_externalities(&mut new_test_ext(), || {
+        let id = station_with_battery();
+        assert_ok!(BatteryModule::set_tradable(Origin::signed(OWNER), id, true));
+        assert_ok!(BatteryModule::challenge_battery_ownership(Origin::signed(2), id));
+        let challenge_id = last_challenge_id();
+
+        assert_ok!(BatteryModule::resolve_challenge(system::RawOrigin::Root.into(), challenge_id, OWNER));
+
+        assert_eq!(BatteryModule::batteries(id).owner(), &OWNER);
+        assert_ok!(BatteryModule::trade_battery(Origin::signed(STATION), id, 3, None));
+    });
+}
+
+fn decommissioned_station_battery(reason: DecommissionReason) -> H256 {
+    let id = station_with_battery();
+    assert_ok!(BatteryModule::decommission_battery(Origin::signed(OWNER), id, reason));
+    id
+}
+
+#[test]
+fn decommission_clears_indexes_and_keeps_tombstone() {
+    with_externalities(&mut new_test_ext(), || {
+        <timestamp::Module<Test>>::set_timestamp(42);
+        let id = station_with_battery();
+        assert_noop!(
+            BatteryModule::decommission_battery(Origin::signed(2), id, DecommissionReason::Lost),
+            "You are not the owner of this battery"
+        );
+        assert_ok!(BatteryModule::decommission_battery(Origin::signed(OWNER), id, DecommissionReason::Lost));
+
+        assert!(!<Batteries<Test>>::exists(id));
+        assert_eq!(BatteryModule::owned_batteries_count(OWNER), 0);
+        assert_eq!(BatteryModule::batteries_count_in_station(STATION), 0);
+        assert_eq!(BatteryModule::stake_start(id), None);
+        assert!(!<BatteryNonce<Test>>::exists(id));
+        assert_eq!(BatteryModule::decommissioned(id), Some((DecommissionReason::Lost, 42, OWNER)));
+        assert!(decommission_hook_calls().is_empty());
+        assert_eq!(battery_events().last(), Some(&RawEvent::BatteryDecommissioned(id, OWNER, DecommissionReason::Lost)));
+    });
+}
+
+#[test]
+fn end_of_life_keeps_tombstone_only() {
+    with_externalities(&mut new_test_ext(), || {
+        let id = decommissioned_station_battery(DecommissionReason::EndOfLife);
+        assert_eq!(BatteryModule::decommissioned(id).map(|t| t.0), Some(DecommissionReason::EndOfLife));
+        assert!(decommission_hook_calls().is_empty());
+    });
+}
+
+#[test]
+fn recycled_battery_triggers_recycling_hook() {
+    with_externalities(&mut new_test_ext(), || {
+        let id = decommissioned_station_battery(DecommissionReason::Recycled);
+        assert_eq!(decommission_hook_calls(), vec![("recycled", id, OWNER)]);
+        assert!(BatteryModule::decommissioned(id).is_some());
+    });
+}
+
+#[test]
+fn damaged_battery_triggers_damage_hook() {
+    with_externalities(&mut new_test_ext(), || {
+        let id = decommissioned_station_battery(DecommissionReason::Damaged);
+        assert_eq!(decommission_hook_calls(), vec![("damaged", id, OWNER)]);
+        assert!(BatteryModule::decommissioned(id).is_some());
+    });
+}
+
+#[test]
+fn duplicates_are_removed_by_root_without_tombstone() {
+    with_externalities(&mut new_test_ext(), || {
+        let id = station_with_battery();
+        assert_noop!(
+            BatteryModule::decommission_battery(Origin::signed(OWNER), id, DecommissionReason::Duplicate),
+            "Only root removes duplicates"
+        );
+        assert!(BatteryModule::remove_duplicate_battery(Origin::signed(OWNER), id).is_err());
+        assert_ok!(BatteryModule::remove_duplicate_battery(system::RawOrigin::Root.into(), id));
+
+        assert!(!<Batteries<Test>>::exists(id));
+        assert_eq!(BatteryModule::decommissioned(id), None);
+    });
+}
+
+#[test]
+fn tombstoned_id_cannot_be_registered_again() {
+    with_externalities(&mut ExtBuilder::default().with_deterministic_ids().build(), || {
+        assert_ok!(BatteryModule::register_station(Origin::signed(STATION)));
+        <DecommissionedBatteries<Test>>::insert(predicted_id(0), (DecommissionReason::Lost, 0, OWNER));
+
+        assert_noop!(BatteryModule::register_battery(Origin::signed(STATION), OWNER), "Battery was decommissioned");
+    });
+}
+
+#[test]
+fn deterministic_ids_are_predictable() {
+    with_externalities(&mut ExtBuilder::default().with_deterministic_ids().build(), || {
+        station_with_battery();
+        assert_ok!(BatteryModule::register_battery(Origin::signed(STATION), 2));
+
+        assert_eq!(BatteryModule::battery_by_index(0), predicted_id(0));
+        assert_eq!(BatteryModule::battery_by_index(1), predicted_id(1));
+        assert_eq!(BatteryModule::battery(predicted_id(1)).unwrap().owner, 2);
+    });
+}
+
+#[test]
+fn account_overview_covers_owner_and_station_roles() {
+    with_externalities(&mut new_test_ext(), || {
+        // STATION holds two batteries of OWNER and owns three itself: one
+        // listed in its own station, one stored elsewhere and one at home.
+        let first = station_with_battery();
+        assert_ok!(BatteryModule::register_battery(Origin::signed(STATION), OWNER));
+        assert_ok!(BatteryModule::register_station(Origin::signed(11)));
+        for _ in 0..3 {
+            assert_ok!(BatteryModule::register_battery(Origin::signed(11), STATION));
+        }
+        let own: Vec<H256> = (0..3).map(|index| BatteryModule::battery_of_owner_by_index((STATION, index))).collect();
+        assert_ok!(BatteryModule::fetch_from_station(Origin::signed(STATION), own[0]));
+        assert_ok!(BatteryModule::store_to_station(Origin::signed(STATION), own[0], None));
+        assert_ok!(BatteryModule::set_tradable(Origin::signed(STATION), own[0], true));
+        assert_ok!(BatteryModule::fetch_from_station(Origin::signed(STATION), own[1]));
+        assert_ok!(BatteryModule::set_tradable(Origin::signed(OWNER), first, true));
+
+        assert_eq!(BatteryModule::account_overview(&STATION), AccountOverview {
+            owned: 3,
+            in_stations: 2,
+            listed: 1,
+            offers_made: 0,
+            loyalty_points: 0,
+            held_as_station: 3,
+            station_earnings: 0,
+        });
+        assert_eq!(BatteryModule::account_overview(&OWNER), AccountOverview {
+            owned: 2,
+            in_stations: 2,
+            listed: 1,
+            ..Default::default()
+        });
+    });
+}
+
+#[test]
+fn transfer_hook_cannot_reenter_for_the_same_battery() {
+    with_externalities(&mut new_test_ext(), || {
+        let id = station_with_battery();
+        assert_ok!(BatteryModule::set_tradable(Origin::signed(OWNER), id, true));
+        assert_ok!(BatteryModule::trade_battery(Origin::signed(STATION), id, 2, None));
+
+        assert_eq!(reentrant_results(), vec![Err("Operation on this battery is already in progress")]);
+        let battery = BatteryModule::batteries(id);
+        assert_eq!(battery.owner(), &2);
+        assert_eq!(battery.station(), Some(&STATION));
+        assert_eq!(battery_events().last(), Some(&RawEvent::Trade(id, OWNER, 2, STATION, receipt_hash(id, OWNER, 2, System::block_number()))));
+
+        // The guard is lifted once the outer call is done.
+        assert!(!<InFlight<Test>>::exists(id));
+        assert_ok!(BatteryModule::fetch_from_station(Origin::signed(2), id));
+    });
+}
+
+#[test]
+fn owner_model_index_follows_ownership_and_model_changes() {
+    with_externalities(&mut new_test_ext(), || {
+        let (lfp, first) = manufacturer_with_battery();
+        assert_ok!(BatteryModule::register_battery(Origin::signed(STATION), MANUFACTURER));
+        let second = BatteryModule::battery_by_index(1);
+        assert_ok!(BatteryModule::set_battery_model(Origin::signed(MANUFACTURER), second, lfp));
+        assert_ok!(BatteryModule::register_model(Origin::signed(MANUFACTURER), b"NMC-36V".to_vec()));
+        let nmc = (MANUFACTURER, b"NMC-36V".to_vec()).using_encoded(BlakeTwo256::hash);
+        assert_eq!(BatteryModule::batteries_by_owner_and_model(&MANUFACTURER, lfp), vec![first, second]);
+
+        // Trading the first one away swap-removes it from the manufacturer's list.
+        assert_ok!(BatteryModule::set_tradable(Origin::signed(MANUFACTURER), first, true));
+        assert_ok!(BatteryModule::trade_battery(Origin::signed(STATION), first, OWNER, None));
+        assert_eq!(BatteryModule::batteries_by_owner_and_model(&MANUFACTURER, lfp), vec![second]);
+        assert_eq!(BatteryModule::batteries_by_owner_and_model(&OWNER, lfp), vec![first]);
+
+        assert_ok!(BatteryModule::set_trust_level(system::RawOrigin::Root.into(), STATION, TrustLevel::Premium));
+        assert_ok!(BatteryModule::upgrade_battery_model(Origin::signed(STATION), first, nmc));
+        assert!(BatteryModule::batteries_by_owner_and_model(&OWNER, lfp).is_empty());
+        assert_eq!(BatteryModule::batteries_by_owner_and_model(&OWNER, nmc), vec![first]);
+
+        assert_ok!(BatteryModule::decommission_battery(Origin::signed(OWNER), first, DecommissionReason::EndOfLife));
+        assert!(BatteryModule::batteries_by_owner_and_model(&OWNER, nmc).is_empty());
+        assert_eq!(BatteryModule::owner_model_count((OWNER, nmc)), 0);
+    });
+}
+
+#[test]
+fn sorted_owned_batteries_keep_acquisition_order() {
+    with_externalities(&mut new_test_ext(), || {
+        assert_ok!(BatteryModule::register_station(Origin::signed(STATION)));
+        for _ in 0..4 {
+            assert_ok!(BatteryModule::register_battery(Origin::signed(STATION), OWNER));
+        }
+        let ids: Vec<H256> = (0..4).map(BatteryModule::battery_by_index).collect();
+        assert_eq!(BatteryModule::owned_batteries_sorted(&OWNER, 0, 10), ids);
+
+        // Trading the first one away moves the last one into its index slot.
+        assert_ok!(BatteryModule::set_tradable(Origin::signed(OWNER), ids[0], true));
+        assert_ok!(BatteryModule::trade_battery(Origin::signed(STATION), ids[0], 2, None));
+        assert_eq!(BatteryModule::battery_of_owner_by_index((OWNER, 0)), ids[3]);
+        assert_eq!(BatteryModule::owned_batteries_sorted(&OWNER, 0, 10), vec![ids[1], ids[2], ids[3]]);
+
+        // Trading it back puts it at the end, as the most recent acquisition.
+        assert_ok!(BatteryModule::set_tradable(Origin::signed(2), ids[0], true));
+        assert_ok!(BatteryModule::trade_battery(Origin::signed(STATION), ids[0], OWNER, None));
+        assert_eq!(BatteryModule::owned_batteries_sorted(&OWNER, 0, 10), vec![ids[1], ids[2], ids[3], ids[0]]);
+        assert_eq!(BatteryModule::owned_batteries_sorted(&OWNER, 1, 2), vec![ids[2], ids[3]]);
+    });
+}
+
+#[test]
+fn opening_hours_are_validated() {
+    with_externalities(&mut new_test_ext(), || {
+        assert_noop!(BatteryModule::set_opening_hours(Origin::signed(STATION), vec![vec![]; 7]), "Sender is not a station");
+        assert_ok!(BatteryModule::register_station(Origin::signed(STATION)));
+        assert_noop!(BatteryModule::set_opening_hours(Origin::signed(STATION), vec![vec![]; 6]), "Opening hours must cover seven days");
+        assert_noop!(
+            BatteryModule::set_opening_hours(Origin::signed(STATION), vec![vec![(600, 600)]; 7]),
+            "Invalid opening window"
+        );
+        assert_noop!(
+            BatteryModule::set_opening_hours(Origin::signed(STATION), vec![vec![(0, 1441)]; 7]),
+            "Invalid opening window"
+        );
+        assert_noop!(
+            BatteryModule::set_opening_hours(Origin::signed(STATION), vec![vec![(0, 1); MAX_OPENING_WINDOWS_PER_DAY + 1]; 7]),
+            "Too many opening windows"
+        );
+    });
+}
+
+#[test]
+fn closed_station_refuses_store_and_fetch() {
+    with_externalities(&mut new_test_ext(), || {
+        let id = station_with_battery();
+        // Open 08:00 to 18:00 on Thursdays only.
+        let mut schedule = vec![vec![]; 7];
+        schedule[3] = vec![(8 * 60, 18 * 60)];
+        assert_ok!(BatteryModule::set_opening_hours(Origin::signed(STATION), schedule));
+        assert_eq!(battery_events().last(), Some(&RawEvent::StationOpeningHoursSet(STATION)));
+
+        // Thursday 1 January 1970, 07:59.
+        <timestamp::Module<Test>>::set_timestamp(7 * 3600 + 59 * 60);
+        assert_noop!(BatteryModule::fetch_from_station(Origin::signed(OWNER), id), "Station is closed");
+        // 08:00 the same day.
+        <timestamp::Module<Test>>::set_timestamp(8 * 3600);
+        assert_ok!(BatteryModule::fetch_from_station(Origin::signed(OWNER), id));
+        // Friday 08:00.
+        <timestamp::Module<Test>>::set_timestamp(86_400 + 8 * 3600);
+        assert_noop!(BatteryModule::store_to_station(Origin::signed(STATION), id, None), "Station is closed");
+        // Thursday a week later, 17:59.
+        <timestamp::Module<Test>>::set_timestamp(7 * 86_400 + 17 * 3600 + 59 * 60);
+        assert_ok!(BatteryModule::store_to_station(Origin::signed(STATION), id, None));
+    });
+}
+
+#[test]
+fn station_age_policy_refuses_old_batteries() {
+    with_externalities(&mut new_test_ext(), || {
+        System::set_block_number(1);
+        let id = station_with_battery();
+        assert_noop!(BatteryModule::set_station_age_policy(Origin::signed(STATION), 1), "Feature is disabled");
+        enable(&[Feature::StationAgePolicies]);
+        assert_noop!(BatteryModule::set_station_age_policy(Origin::signed(OWNER), 1), "Sender is not a station");
+        assert_eq!(BatteryModule::max_age_days(&STATION), u64::max_value());
+        assert_ok!(BatteryModule::set_station_age_policy(Origin::signed(STATION), 1));
+        assert_eq!(battery_events().last(), Some(&RawEvent::StationAgePolicySet(STATION, 1)));
+        assert_ok!(BatteryModule::fetch_from_station(Origin::signed(OWNER), id));
+
+        // Age is counted in whole days.
+        System::set_block_number(1 + 2 * BLOCKS_PER_DAY - 1);
+        assert_ok!(BatteryModule::store_to_station(Origin::signed(STATION), id, None));
+        assert_ok!(BatteryModule::fetch_from_station(Origin::signed(OWNER), id));
+        System::set_block_number(1 + 2 * BLOCKS_PER_DAY);
+        assert_noop!(BatteryModule::store_to_station(Origin::signed(STATION), id, None), "Battery is older than the station accepts");
+    });
+}
+
+#[test]
+fn auto_relist_lists_on_every_store() {
+    with_externalities(&mut new_test_ext(), || {
+        let id = station_with_battery();
+        assert_noop!(BatteryModule::set_auto_relist(Origin::signed(2), id, true), "You are not the owner of this battery");
+        assert_ok!(BatteryModule::set_auto_relist(Origin::signed(OWNER), id, true));
+
+        for _ in 0..2 {
+            assert_ok!(BatteryModule::fetch_from_station(Origin::signed(OWNER), id));
+            assert_ok!(BatteryModule::store_to_station(Origin::signed(STATION), id, None));
+            assert!(BatteryModule::batteries(id).is_tradable());
+            assert_eq!(BatteryModule::stake_start(id), None);
+            assert_eq!(battery_events().last(), Some(&RawEvent::SwitchTradable(id, true)));
+        }
+
+        assert_ok!(BatteryModule::set_auto_relist(Origin::signed(OWNER), id, false));
+        assert_ok!(BatteryModule::fetch_from_station(Origin::signed(OWNER), id));
+        assert_ok!(BatteryModule::store_to_station(Origin::signed(STATION), id, None));
+        assert!(!BatteryModule::batteries(id).is_tradable());
+    });
+}
+
+#[test]
+fn auto_relist_is_dropped_on_ownership_change() {
+    with_externalities(&mut new_test_ext(), || {
+        let id = station_with_battery();
+        assert_ok!(BatteryModule::set_auto_relist(Origin::signed(OWNER), id, true));
+        assert_ok!(BatteryModule::set_tradable(Origin::signed(OWNER), id, true));
+        assert_ok!(BatteryModule::trade_battery(Origin::signed(STATION), id, 2, None));
+        assert!(!BatteryModule::auto_relist(id));
+
+        assert_ok!(BatteryModule::fetch_from_station(Origin::signed(2), id));
+        assert_ok!(BatteryModule::store_to_station(Origin::signed(STATION), id, None));
+        assert!(!BatteryModule::batteries(id).is_tradable());
+    });
+}
+
+#[test]
+fn merkle_proofs_verify_live_batteries() {
+    with_externalities(&mut new_test_ext(), || {
+        assert_ok!(BatteryModule::register_station(Origin::signed(STATION)));
+        for _ in 0..5 {
+            assert_ok!(BatteryModule::register_battery(Origin::signed(STATION), OWNER));
+        }
+        let ids: Vec<H256> = (0..5).map(BatteryModule::battery_by_index).collect();
+        assert_eq!(BatteryModule::merkle_root(), H256::default());
+        BatteryModule::update_merkle_root();
+
+        for id in &ids {
+            let proof = BatteryModule::battery_inclusion_proof(*id).unwrap();
+            assert!(BatteryModule::verify_battery_inclusion(*id, proof.clone()));
+            assert!(!BatteryModule::verify_battery_inclusion(H256::from([9u8; 32]), proof));
+        }
+        assert_eq!(BatteryModule::battery_inclusion_proof(H256::from([9u8; 32])), None);
+
+        // The root moves on once a battery is gone, so its old proof stops verifying.
+        let old_proof = BatteryModule::battery_inclusion_proof(ids[2]).unwrap();
+        assert_ok!(BatteryModule::decommission_battery(Origin::signed(OWNER), ids[2], DecommissionReason::EndOfLife));
+        BatteryModule::update_merkle_root();
+        assert!(!BatteryModule::verify_battery_inclusion(ids[2], old_proof));
+        assert_eq!(BatteryModule::battery_inclusion_proof(ids[2]), None);
+        let proof = BatteryModule::battery_inclusion_proof(ids[4]).unwrap();
+        assert!(BatteryModule::verify_battery_inclusion(ids[4], proof));
+    });
+}
+
+/// Stats counted the slow way, straight from the battery records.
+fn scanned_stats() -> NetworkStats {
+    let mut stats = NetworkStats {
+        stations: BatteryModule::stations_count(),
+        trades: BatteryModule::network_stats().trades,
+        ..Default::default()
+    };
+    for index in 0..BatteryModule::all_batteries_count() {
+        let id = BatteryModule::battery_by_index(index);
+        if <Batteries<Test>>::exists(id) {
+            stats.add_battery(&BatteryModule::batteries(id).custody);
+        } else if BatteryModule::decommissioned(id).is_some() {
+            stats.decommissioned += 1;
+        }
+    }
+    stats
+}
+
+#[test]
+fn network_stats_track_scripted_scenario() {
+    with_externalities(&mut new_test_ext(), || {
+        assert_ok!(BatteryModule::register_station(Origin::signed(STATION)));
+        assert_ok!(BatteryModule::register_station(Origin::signed(11)));
+        for _ in 0..5 {
+            assert_ok!(BatteryModule::register_battery(Origin::signed(STATION), OWNER));
+        }
+        let ids: Vec<H256> = (0..5).map(BatteryModule::battery_by_index).collect();
+        assert_ok!(BatteryModule::set_tradable(Origin::signed(OWNER), ids[0], true));
+        assert_ok!(BatteryModule::set_tradable(Origin::signed(OWNER), ids[1], true));
+        assert_ok!(BatteryModule::trade_battery(Origin::signed(STATION), ids[0], 2, None));
+        assert_ok!(BatteryModule::fetch_from_station(Origin::signed(OWNER), ids[2]));
+        assert_ok!(BatteryModule::fetch_from_station(Origin::signed(OWNER), ids[3]));
+        assert_ok!(BatteryModule::store_to_station(Origin::signed(11), ids[3], None));
+        assert_ok!(BatteryModule::decommission_battery(Origin::signed(OWNER), ids[4], DecommissionReason::Recycled));
+        assert_ok!(BatteryModule::remove_duplicate_battery(system::RawOrigin::Root.into(), ids[2]));
+
+        let expected = NetworkStats {
+            stations: 2,
+            batteries: 3,
+            in_custody: 3,
+            listed: 1,
+            decommissioned: 1,
+            trades: 1,
+        };
+        assert_eq!(BatteryModule::network_stats(), expected);
+        assert_eq!(scanned_stats(), expected);
+    });
+}
+
+#[test]
+fn rebuild_stats_repairs_drift_in_stages() {
+    with_externalities(&mut new_test_ext(), || {
+        let id = station_with_battery();
+        for _ in 0..4 {
+            assert_ok!(BatteryModule::register_battery(Origin::signed(STATION), OWNER));
+        }
+        assert_ok!(BatteryModule::set_tradable(Origin::signed(OWNER), id, true));
+        assert_ok!(BatteryModule::trade_battery(Origin::signed(STATION), id, 2, None));
+        assert_ok!(BatteryModule::decommission_battery(Origin::signed(2), id, DecommissionReason::Lost));
+        let expected = BatteryModule::network_stats();
+
+        <Stats<Test>>::put(NetworkStats { listed: 7, trades: expected.trades, ..Default::default() });
+        assert!(BatteryModule::rebuild_stats(Origin::signed(OWNER), 2).is_err());
+        assert_ok!(BatteryModule::rebuild_stats(system::RawOrigin::Root.into(), 2));
+        assert_ok!(BatteryModule::rebuild_stats(system::RawOrigin::Root.into(), 2));
+        // Nothing is replaced until the scan is complete.
+        assert_eq!(BatteryModule::network_stats().listed, 7);
+        assert_ok!(BatteryModule::rebuild_stats(system::RawOrigin::Root.into(), 2));
+
+        assert_eq!(BatteryModule::network_stats(), expected);
+        assert_eq!(battery_events().last(), Some(&RawEvent::StatsRebuilt(expected)));
+    });
+}
+
+#[test]
+fn network_swap_capacity_sums_declarations() {
+    with_externalities(&mut new_test_ext(), || {
+        assert_noop!(BatteryModule::set_swap_capacity(Origin::signed(STATION), 20), "Sender is not a station");
+        assert_ok!(BatteryModule::register_station(Origin::signed(STATION)));
+        assert_ok!(BatteryModule::register_station(Origin::signed(11)));
+        assert_ok!(BatteryModule::set_swap_capacity(Origin::signed(STATION), 20));
+        assert_ok!(BatteryModule::set_swap_capacity(Origin::signed(11), 5));
+        assert_eq!(BatteryModule::network_swap_capacity(), 25);
+        assert_ok!(BatteryModule::set_swap_capacity(Origin::signed(STATION), 8));
+        assert_eq!(BatteryModule::network_swap_capacity(), 13);
+    });
+}
+
+#[test]
+fn discovery_skips_overwhelmed_stations() {
+    with_externalities(&mut new_test_ext(), || {
+        let id = station_with_battery();
+        assert_ok!(BatteryModule::set_station_location(Origin::signed(STATION), 0, 1_000_000));
+        assert_ok!(BatteryModule::set_swap_capacity(Origin::signed(STATION), 2));
+        assert_ok!(BatteryModule::fetch_from_station(Origin::signed(OWNER), id));
+        assert_ok!(BatteryModule::store_to_station(Origin::signed(STATION), id, None));
+        assert_ok!(BatteryModule::set_tradable(Origin::signed(OWNER), id, true));
+        assert_eq!(BatteryModule::current_throughput_rate(&STATION), 2);
+        assert!(BatteryModule::find_stations_with_tradable_batteries(BatteryGrade::C, 0, 0, 0, 5).is_empty());
+
+        // The next hour the counter starts over.
+        <timestamp::Module<Test>>::set_timestamp(3600);
+        assert_eq!(BatteryModule::current_throughput_rate(&STATION), 0);
+        assert_eq!(BatteryModule::find_stations_with_tradable_batteries(BatteryGrade::C, 0, 0, 0, 5), vec![(STATION, 111_194)]);
+    });
+}
+
+fn last_bridge_request_id() -> H256 {
+    match battery_events().last() {
+        Some(RawEvent::BridgeLocked(request_id, _)) => *request_id,
+        _ => panic!("no bridge request"),
+    }
+}
+
+#[test]
+fn bridge_round_trip() {
+    with_externalities(&mut new_test_ext(), || {
+        let id = station_with_battery();
+        assert_noop!(
+            BatteryModule::lock_for_bridge(Origin::signed(OWNER), id, 1, vec![]),
+            "Invalid destination address"
+        );
+        assert_ok!(BatteryModule::lock_for_bridge(Origin::signed(OWNER), id, 1, vec![0xab; 20]));
+        let request_id = last_bridge_request_id();
+
+        let battery = BatteryModule::batteries(id);
+        assert_eq!(battery.station(), None);
+        assert!(!battery.is_tradable());
+        assert_eq!(BatteryModule::batteries_count_in_station(STATION), 0);
+        assert_noop!(BatteryModule::store_to_station(Origin::signed(STATION), id, None), "Battery is locked in the bridge");
+        assert_noop!(
+            BatteryModule::lock_for_bridge(Origin::signed(OWNER), id, 1, vec![0xab; 20]),
+            "Battery is locked in the bridge"
+        );
+
+        assert_noop!(
+            BatteryModule::unlock_from_bridge(system::RawOrigin::Root.into(), id, vec![1]),
+            "Bridge request is not confirmed"
+        );
+        assert!(BatteryModule::confirm_bridge(Origin::signed(OWNER), request_id, vec![0xcd; 32]).is_err());
+        assert_ok!(BatteryModule::confirm_bridge(system::RawOrigin::Root.into(), request_id, vec![0xcd; 32]));
+        assert_eq!(BatteryModule::bridge_request(request_id).unwrap().confirmation, Some(vec![0xcd; 32]));
+
+        assert_ok!(BatteryModule::unlock_from_bridge(system::RawOrigin::Root.into(), id, vec![1]));
+        assert_eq!(battery_events().last(), Some(&RawEvent::BridgeUnlocked(id)));
+        assert_eq!(BatteryModule::bridge_request(request_id), None);
+        assert_ok!(BatteryModule::store_to_station(Origin::signed(STATION), id, None));
+    });
+}
+
+#[test]
+fn charging_station_sets_target_soc() {
+    with_externalities(&mut new_test_ext(), || {
+        assert_ok!(BatteryModule::register_charging_station(Origin::signed(STATION)));
+        assert!(BatteryModule::is_charging_station(STATION));
+        assert_eq!(BatteryModule::stations_count(), 1);
+        assert_noop!(BatteryModule::register_charging_station(Origin::signed(STATION)), "Already been station!");
+        assert_ok!(BatteryModule::register_battery(Origin::signed(STATION), OWNER));
+        let id = BatteryModule::battery_by_index(0);
+
+        assert_noop!(BatteryModule::set_target_soc(Origin::signed(STATION), id, 101), "Target state of charge must be a percentage");
+        assert_ok!(BatteryModule::set_target_soc(Origin::signed(STATION), id, 80));
+        assert_eq!(BatteryModule::target_soc(id), Some(80));
+        assert_eq!(battery_events().last(), Some(&RawEvent::ChargingStarted(id, STATION, 80)));
+
+        assert_ok!(BatteryModule::fetch_from_station(Origin::signed(OWNER), id));
+        assert_eq!(BatteryModule::target_soc(id), None);
+        assert_noop!(
+            BatteryModule::set_target_soc(Origin::signed(STATION), id, 80),
+            "Sender must be the station of this battery"
+        );
+    });
+}
+
+#[test]
+fn plain_station_cannot_charge() {
+    with_externalities(&mut new_test_ext(), || {
+        let id = station_with_battery();
+        assert!(!BatteryModule::is_charging_station(STATION));
+        assert_noop!(BatteryModule::set_target_soc(Origin::signed(STATION), id, 80), "Sender is not a charging station");
+    });
+}
+
+/// Runs `check` and `call` on the same state and asserts they agree.
+fn assert_parity<C, D>(check: C, call: D)
+where
+    C: FnOnce() -> Result,
+    D: FnOnce() -> Result,
+{
+    let expected = check();
+    assert_eq!(call(), expected);
+}
+
+#[test]
+fn dry_run_checks_match_real_calls() {
+    with_externalities(&mut new_test_ext(), || {
+        let id = station_with_battery();
+        let unknown = H256::from([9u8; 32]);
+
+        // Storing: not a station, unknown battery, already stored.
+        assert_parity(|| BatteryModule::can_store(&2, id).map(|_| ()), || BatteryModule::store_to_station(Origin::signed(2), id, None));
+        assert_parity(|| BatteryModule::can_store(&STATION, unknown).map(|_| ()), || BatteryModule::store_to_station(Origin::signed(STATION), unknown, None));
+        assert_parity(|| BatteryModule::can_store(&STATION, id).map(|_| ()), || BatteryModule::store_to_station(Origin::signed(STATION), id, None));
+
+        // Trading: not a station, not tradable, to the owner, then a success.
+        assert_parity(|| BatteryModule::can_trade(&2, id, &3), || BatteryModule::trade_battery(Origin::signed(2), id, 3, None));
+        assert_parity(|| BatteryModule::can_trade(&STATION, id, &3), || BatteryModule::trade_battery(Origin::signed(STATION), id, 3, None));
+        assert_ok!(BatteryModule::set_tradable(Origin::signed(OWNER), id, true));
+        assert_parity(|| BatteryModule::can_trade(&STATION, id, &OWNER), || BatteryModule::trade_battery(Origin::signed(STATION), id, OWNER, None));
+        assert_eq!(BatteryModule::can_trade(&STATION, id, &3), Ok(()));
+        assert_parity(|| BatteryModule::can_trade(&STATION, id, &3), || BatteryModule::trade_battery(Origin::signed(STATION), id, 3, None));
+
+        // Fetching: not the owner, closed station, then a success.
+        assert_parity(|| BatteryModule::can_fetch(&OWNER, id).map(|_| ()), || BatteryModule::fetch_from_station(Origin::signed(OWNER), id));
+        let mut schedule = vec![vec![]; 7];
+        schedule[0] = vec![(0, 60)];
+        assert_ok!(BatteryModule::set_opening_hours(Origin::signed(STATION), schedule));
+        assert_parity(|| BatteryModule::can_fetch(&3, id).map(|_| ()), || BatteryModule::fetch_from_station(Origin::signed(3), id));
+        assert_eq!(BatteryModule::can_fetch(&3, id), Err("Station is closed"));
+        <timestamp::Module<Test>>::set_timestamp(4 * 86_400);
+        assert_eq!(BatteryModule::can_fetch(&3, id), Ok(STATION));
+        assert_parity(|| BatteryModule::can_fetch(&3, id).map(|_| ()), || BatteryModule::fetch_from_station(Origin::signed(3), id));
+
+        // Storing again once the battery is out: a success.
+        assert_parity(|| BatteryModule::can_store(&STATION, id).map(|_| ()), || BatteryModule::store_to_station(Origin::signed(STATION), id, None));
+    });
+}
+
+#[test]
+fn passport_hash_follows_battery_changes() {
+    with_externalities(&mut new_test_ext(), || {
+        let (_, id) = manufacturer_with_battery();
+        let passport = BatteryModule::battery_passport(id).unwrap();
+        assert_eq!(BatteryModule::passport_hash(id), BlakeTwo256::hash_of(&passport));
+
+        let mut seen = vec![BatteryModule::passport_hash(id)];
+        assert_ok!(BatteryModule::set_tradable(Origin::signed(MANUFACTURER), id, true));
+        seen.push(BatteryModule::passport_hash(id));
+        assert_ok!(BatteryModule::register_model(Origin::signed(MANUFACTURER), b"NMC-36V".to_vec()));
+        let nmc = (MANUFACTURER, b"NMC-36V".to_vec()).using_encoded(BlakeTwo256::hash);
+        assert_ok!(BatteryModule::set_trust_level(system::RawOrigin::Root.into(), STATION, TrustLevel::Premium));
+        assert_ok!(BatteryModule::upgrade_battery_model(Origin::signed(STATION), id, nmc));
+        seen.push(BatteryModule::passport_hash(id));
+
+        let passport = BatteryModule::battery_passport(id).unwrap();
+        assert_eq!(passport.model, Some(nmc));
+        assert_eq!(passport.maintenance.len(), 1);
+        assert_eq!(BatteryModule::passport_hash(id), BlakeTwo256::hash_of(&passport));
+        seen.dedup();
+        assert_eq!(seen.len(), 3);
+
+        assert_ok!(BatteryModule::decommission_battery(Origin::signed(MANUFACTURER), id, DecommissionReason::Lost));
+        assert!(!<PassportHash<Test>>::exists(id));
+    });
+}
+
+#[test]
+fn station_graduates_exactly_at_trade_threshold() {
+    with_externalities(&mut new_test_ext(), || {
+        let id = station_with_battery();
+        assert_eq!(BatteryModule::station_status(STATION), StationStatus::Probation { trades_completed: 0 });
+
+        let owners = [OWNER, 2, 3, OWNER];
+        for (trade, pair) in owners.windows(2).enumerate() {
+            assert_ok!(BatteryModule::set_tradable(Origin::signed(pair[0]), id, true));
+            assert_ok!(BatteryModule::trade_battery(Origin::signed(STATION), id, pair[1], None));
+            if trade + 1 < PROBATION_TRADE_THRESHOLD as usize {
+                assert_eq!(
+                    BatteryModule::station_status(STATION),
+                    StationStatus::Probation { trades_completed: trade as u32 + 1 }
+                );
+            }
+        }
+
+        assert_eq!(BatteryModule::station_status(STATION), StationStatus::Full);
+        assert!(battery_events().contains(&RawEvent::StationGraduated(STATION)));
+    });
+}
+
+#[test]
+fn probation_caps_station_inventory() {
+    with_externalities(&mut new_test_ext(), || {
+        assert_ok!(BatteryModule::register_station(Origin::signed(STATION)));
+        for _ in 0..PROBATION_INVENTORY_CAP {
+            assert_ok!(BatteryModule::register_battery(Origin::signed(STATION), OWNER));
+        }
+        assert_noop!(BatteryModule::register_battery(Origin::signed(STATION), OWNER), "Station on probation is full");
+
+        let id = BatteryModule::battery_by_index(0);
+        assert_ok!(BatteryModule::fetch_from_station(Origin::signed(OWNER), id));
+        assert_ok!(BatteryModule::register_battery(Origin::signed(STATION), OWNER));
+        assert_noop!(BatteryModule::store_to_station(Origin::signed(STATION), id, None), "Station on probation is full");
+
+        assert!(BatteryModule::graduate_station(Origin::signed(STATION), STATION).is_err());
+        assert_ok!(BatteryModule::graduate_station(system::RawOrigin::Root.into(), STATION));
+        assert_eq!(battery_events().last(), Some(&RawEvent::StationGraduated(STATION)));
+        assert_ok!(BatteryModule::store_to_station(Origin::signed(STATION), id, None));
+        assert_noop!(BatteryModule::graduate_station(system::RawOrigin::Root.into(), STATION), "Station is not on probation");
+    });
+}
+
+fn assert_no_owner_state(id: H256) {
+    assert!(!<OwnedBatteriesIndex<Test>>::exists(id));
+    assert!(!<AcquisitionSeq<Test>>::exists(id));
+    assert!(!<AutoRelist<Test>>::exists(id));
+    assert!(!<CoOwners<Test>>::exists(id));
+    assert!(!<Approvals<Test>>::exists(id));
+}
+
+fn assert_owned_index_consistent(owner: u64) {
+    for index in 0..BatteryModule::owned_batteries_count(owner) {
+        let id = BatteryModule::battery_of_owner_by_index((owner, index));
+        assert_eq!(BatteryModule::owned_battery_index(id), index);
+    }
+}
+
+#[test]
+fn leaving_an_owner_leaves_no_stale_entries() {
+    with_externalities(&mut new_test_ext(), || {
+        assert_ok!(BatteryModule::register_station(Origin::signed(STATION)));
+        for _ in 0..3 {
+            assert_ok!(BatteryModule::register_battery(Origin::signed(STATION), OWNER));
+        }
+        let traded = BatteryModule::battery_by_index(0);
+        let decommissioned = BatteryModule::battery_by_index(1);
+
+        assert_ok!(BatteryModule::add_co_owner(Origin::signed(OWNER), traded, 2));
+        approve_listing(traded, &[2]);
+        assert_ok!(BatteryModule::set_tradable(Origin::signed(OWNER), traded, true));
+        approve_listing(traded, &[2]);
+        assert_ok!(BatteryModule::set_auto_relist(Origin::signed(OWNER), traded, true));
+        let action_hash = BatteryModule::transfer_action_hash(traded, &3);
+        assert_ok!(BatteryModule::approve_action(Origin::signed(2), traded, action_hash));
+        assert_ok!(BatteryModule::trade_battery(Origin::signed(STATION), traded, 3, None));
+
+        assert!(!<AutoRelist<Test>>::exists(traded));
+        assert!(!<CoOwners<Test>>::exists(traded));
+        assert!(!<Approvals<Test>>::exists(traded));
+        assert_eq!(BatteryModule::battery_of_owner_by_index((3, BatteryModule::owned_battery_index(traded))), traded);
+        assert_owned_index_consistent(OWNER);
+        assert_owned_index_consistent(3);
+
+        assert_ok!(BatteryModule::add_co_owner(Origin::signed(OWNER), decommissioned, 2));
+        approve_listing(decommissioned, &[2]);
+        assert_ok!(BatteryModule::set_auto_relist(Origin::signed(OWNER), decommissioned, true));
+        let action_hash = BatteryModule::decommission_action_hash(decommissioned, DecommissionReason::EndOfLife);
+        assert_ok!(BatteryModule::approve_action(Origin::signed(2), decommissioned, action_hash));
+        assert_ok!(BatteryModule::decommission_battery(
+            Origin::signed(OWNER),
+            decommissioned,
+            DecommissionReason::EndOfLife
+        ));
+
+        assert_no_owner_state(decommissioned);
+        assert!(!<BatteriesIndexInStation<Test>>::exists(decommissioned));
+        assert_owned_index_consistent(OWNER);
+        assert_eq!(BatteryModule::owned_batteries_count(OWNER), 1);
+    });
+}
+
+#[test]
+fn station_audit_log_records_actor_and_action() {
+    with_externalities(&mut new_test_ext(), || {
+        const KEY: u64 = 20;
+        let id = station_with_battery();
+        assert_ok!(BatteryModule::set_session_key(Origin::signed(STATION), KEY));
+        assert_ok!(BatteryModule::fetch_from_station(Origin::signed(OWNER), id));
+        assert_ok!(BatteryModule::store_to_station(Origin::signed(KEY), id, None));
+        assert_ok!(BatteryModule::set_trust_level(system::RawOrigin::Root.into(), STATION, TrustLevel::Verified));
+
+        let log: Vec<_> = BatteryModule::station_audit_log(&STATION, 0, 10)
+            .into_iter()
+            .map(|entry| (entry.actor, entry.action))
+            .collect();
+        assert_eq!(log, vec![
+            (Some(STATION), StationAction::Registered),
+            (Some(STATION), StationAction::BatteryRegistered(id)),
+            (Some(STATION), StationAction::SessionKeySet),
+            (Some(OWNER), StationAction::BatteryFetched(id)),
+            (Some(KEY), StationAction::BatteryStored(id)),
+            (None, StationAction::TrustLevelSet(TrustLevel::Verified)),
+        ]);
+        assert_eq!(BatteryModule::audit_count(STATION), 6);
+
+        let page = BatteryModule::station_audit_log(&STATION, 4, 10);
+        assert_eq!(page.len(), 2);
+        assert_eq!(page[0].action, StationAction::BatteryStored(id));
+        assert!(BatteryModule::station_audit_log(&STATION, 6, 10).is_empty());
+    });
+}
+
+#[test]
+fn owner_claims_recall_compensation() {
+    with_externalities(&mut new_test_ext(), || {
+        let (_, id) = manufacturer_with_battery();
+        assert_ok!(BatteryModule::set_tradable(Origin::signed(MANUFACTURER), id, true));
+        assert_ok!(BatteryModule::trade_battery(Origin::signed(STATION), id, OWNER, None));
+
+        assert_noop!(BatteryModule::recall_battery(Origin::signed(OWNER), id, 200), "You are not the manufacturer of this model");
+        assert_ok!(BatteryModule::recall_battery(Origin::signed(MANUFACTURER), id, 200));
+        assert!(BatteryModule::is_recalled(id));
+        assert_eq!(Balances::reserved_balance(MANUFACTURER), 200);
+        assert_noop!(BatteryModule::recall_battery(Origin::signed(MANUFACTURER), id, 200), "Battery already recalled");
+        assert_noop!(
+            BatteryModule::decommission_battery(Origin::signed(OWNER), id, DecommissionReason::Recycled),
+            "Claim the recall compensation first"
+        );
+
+        assert_noop!(BatteryModule::claim_recall_compensation(Origin::signed(MANUFACTURER), id), "You are not the owner of this battery");
+        assert_ok!(BatteryModule::claim_recall_compensation(Origin::signed(OWNER), id));
+        assert_eq!(Balances::free_balance(OWNER), 1_200);
+        assert_eq!(Balances::free_balance(MANUFACTURER), 800);
+        assert_eq!(Balances::reserved_balance(MANUFACTURER), 0);
+        assert_eq!(battery_events().last(), Some(&RawEvent::RecallCompensationClaimed(id, OWNER, 200)));
+        assert_noop!(BatteryModule::claim_recall_compensation(Origin::signed(OWNER), id), "No compensation to claim");
+    });
+}
+
+#[test]
+fn ownership_proof_verifies_against_checkpoint() {
+    with_externalities(&mut new_test_ext(), || {
+        let id = station_with_battery();
+        assert_ok!(BatteryModule::register_battery(Origin::signed(STATION), 2));
+        assert_ok!(BatteryModule::register_battery(Origin::signed(STATION), 3));
+
+        BatteryModule::checkpoint_ownership(BLOCKS_PER_CHECKPOINT - 1);
+        assert_eq!(BatteryModule::ownership_checkpoint(0), None);
+        BatteryModule::checkpoint_ownership(BLOCKS_PER_CHECKPOINT);
+        let proof = BatteryModule::ownership_proof(1, id, &OWNER).unwrap();
+        assert_eq!(proof.len(), OWNERSHIP_TREE_DEPTH as usize);
+        assert_eq!(BatteryModule::ownership_proof(1, id, &2), None);
+
+        assert_ok!(BatteryModule::set_tradable(Origin::signed(OWNER), id, true));
+        assert_ok!(BatteryModule::trade_battery(Origin::signed(STATION), id, 2, None));
+        assert_ok!(BatteryModule::register_battery(Origin::signed(STATION), 4));
+        let later = BatteryModule::battery_by_index(3);
+        BatteryModule::checkpoint_ownership(2 * BLOCKS_PER_CHECKPOINT);
+
+        assert!(BatteryModule::verify_ownership(1, id, &OWNER, proof.clone()));
+        assert!(!BatteryModule::verify_ownership(1, id, &2, proof.clone()));
+        assert!(!BatteryModule::verify_ownership(2, id, &OWNER, proof.clone()));
+        let mut forged = proof.clone();
+        forged[0] = H256::from([9u8; 32]);
+        assert!(!BatteryModule::verify_ownership(1, id, &OWNER, forged));
+
+        // Proofs against the first checkpoint are still built from the tree as it was then.
+        assert_eq!(BatteryModule::ownership_proof(1, id, &OWNER), Some(proof));
+        assert_eq!(BatteryModule::ownership_proof(1, later, &4), None);
+        let proof = BatteryModule::ownership_proof(2, id, &2).unwrap();
+        assert!(BatteryModule::verify_ownership(2, id, &2, proof));
+        let proof = BatteryModule::ownership_proof(2, later, &4).unwrap();
+        assert!(BatteryModule::verify_ownership(2, later, &4, proof));
+        assert_eq!(BatteryModule::ownership_proof(2, id, &OWNER), None);
+    });
+}
+
+#[cfg(feature = "test-helpers")]
+#[test]
+fn test_helpers_write_storage_directly() {
+    let id = H256::from([7u8; 32]);
+    let mut ext = ExtBuilder::default()
+        .with_battery(id, OWNER, Custody::Tradable(STATION), 3)
+        .with_counts(5, 2)
+        .with_storage_version(0)
+        .build();
+    with_externalities(&mut ext, || {
+        assert!(BatteryModule::batteries(id).is_tradable());
+        assert_eq!(BatteryModule::batteries(id).registered_at(), 3);
+        assert_eq!(BatteryModule::storage_version(), 0);
+        assert_eq!((BatteryModule::all_batteries_count(), BatteryModule::stations_count()), (5, 2));
+    });
+}
+
+#[cfg(feature = "test-helpers")]
+#[test]
+fn test_helpers_keep_indexes_consistent() {
+    let id = H256::from([7u8; 32]);
+    let mut ext = ExtBuilder::default()
+        .with_battery(id, OWNER, Custody::Tradable(STATION), 3)
+        .with_expired_listing(id)
+        .build();
+    with_externalities(&mut ext, || {
+        assert_eq!(BatteryModule::all_batteries_count(), 1);
+        assert_eq!(BatteryModule::battery_by_index(0), id);
+        assert_eq!(BatteryModule::battery_of_owner_by_index((OWNER, 0)), id);
+        assert_eq!(BatteryModule::battery_of_station_by_index((STATION, 0)), id);
+        assert_eq!(BatteryModule::batteries(id).custody, Custody::Station(STATION));
+        let stats = BatteryModule::network_stats();
+        assert_eq!((stats.batteries, stats.in_custody, stats.listed), (1, 1, 0));
+
+        // Overwriting the battery moves its index entries along.
+        let battery = Battery { owner: 2, custody: Custody::Owner, registered_at: 3 };
+        assert!(BatteryModule::force_set_battery(Origin::signed(OWNER), id, battery.clone()).is_err());
+        assert_ok!(BatteryModule::force_set_battery(system::RawOrigin::Root.into(), id, battery));
+        assert_eq!(BatteryModule::all_batteries_count(), 1);
+        assert_eq!(BatteryModule::owned_batteries_count(OWNER), 0);
+        assert_eq!(BatteryModule::battery_of_owner_by_index((2, 0)), id);
+        assert_eq!(BatteryModule::batteries_count_in_station(STATION), 0);
+        let stats = BatteryModule::network_stats();
+        assert_eq!((stats.batteries, stats.in_custody), (1, 0));
+    });
+}
+
+#[cfg(not(feature = "test-helpers"))]
+#[test]
+fn default_builds_have_no_test_helper_calls() {
+    let names: Vec<&str> = BatteryModule::call_functions()
+        .iter()
+        .filter_map(|function| match function.name {
+            support::dispatch::DecodeDifferent::Encode(name) => Some(name),
+            _ => None,
+        })
+        .collect();
+    assert!(names.contains(&"trade_battery"));
+    assert!(!names.iter().any(|name| name.starts_with("force_")));
+}
+
+#[test]
+fn listing_and_decommissioning_need_co_owner_approval() {
+    with_externalities(&mut new_test_ext(), || {
+        let id = station_with_battery();
+        assert_ok!(BatteryModule::add_co_owner(Origin::signed(OWNER), id, 2));
+        assert_ok!(BatteryModule::add_co_owner(Origin::signed(OWNER), id, 3));
+        assert_noop!(BatteryModule::set_co_owner_threshold(Origin::signed(2), id, 1), "You are not the owner of this battery");
+        assert_noop!(BatteryModule::set_co_owner_threshold(Origin::signed(OWNER), id, 0), "Invalid co-owner threshold");
+        assert_ok!(BatteryModule::set_co_owner_threshold(Origin::signed(OWNER), id, 1));
+
+        assert_noop!(BatteryModule::set_tradable(Origin::signed(OWNER), id, true), "Not enough co-owner approvals");
+        approve_listing(id, &[3]);
+        assert_ok!(BatteryModule::set_tradable(Origin::signed(OWNER), id, true));
+        // The approval was used up, so listing again takes a fresh one.
+        assert!(BatteryModule::approvals(id).is_empty());
+        assert_ok!(BatteryModule::set_tradable(Origin::signed(OWNER), id, false));
+        assert_noop!(BatteryModule::set_tradable(Origin::signed(OWNER), id, true), "Not enough co-owner approvals");
+
+        let recycle = BatteryModule::decommission_action_hash(id, DecommissionReason::Recycled);
+        assert_ok!(BatteryModule::approve_action(Origin::signed(2), id, BatteryModule::decommission_action_hash(id, DecommissionReason::Lost)));
+        assert_noop!(
+            BatteryModule::decommission_battery(Origin::signed(OWNER), id, DecommissionReason::Recycled),
+            "Not enough co-owner approvals"
+        );
+        assert_ok!(BatteryModule::approve_action(Origin::signed(2), id, recycle));
+        assert_ok!(BatteryModule::decommission_battery(Origin::signed(OWNER), id, DecommissionReason::Recycled));
+        assert_eq!(BatteryModule::co_owner_threshold(id), None);
+    });
+}
+
+#[test]
+fn account_export_pages_through_everything_stored() {
+    with_externalities(&mut new_test_ext(), || {
+        let (_, modelled) = manufacturer_with_battery();
+        assert_ok!(BatteryModule::set_tradable(Origin::signed(MANUFACTURER), modelled, true));
+        assert_ok!(BatteryModule::trade_battery(Origin::signed(STATION), modelled, OWNER, None));
+        assert_ok!(BatteryModule::recall_battery(Origin::signed(MANUFACTURER), modelled, 50));
+        assert_ok!(BatteryModule::register_battery(Origin::signed(STATION), OWNER));
+        let co_owned = BatteryModule::battery_by_index(1);
+        assert_ok!(BatteryModule::add_co_owner(Origin::signed(OWNER), co_owned, 2));
+        assert_ok!(BatteryModule::set_co_owner_threshold(Origin::signed(OWNER), co_owned, 1));
+        approve_listing(co_owned, &[2]);
+        assert_ok!(BatteryModule::set_auto_relist(Origin::signed(OWNER), co_owned, true));
+        assert_ok!(BatteryModule::set_station_location(Origin::signed(STATION), 1, 2));
+        assert_ok!(BatteryModule::set_swap_capacity(Origin::signed(STATION), 6));
+
+        let mut exported = Vec::new();
+        let mut cursor = Some(0);
+        while let Some(start) = cursor {
+            let page = BatteryModule::export_account_data(&OWNER, start, 1);
+            assert_eq!(page.batteries.len(), 1);
+            assert_eq!(page.records.is_empty(), start != 0);
+            exported.extend(page.batteries);
+            cursor = page.next_cursor;
+        }
+        assert_eq!(exported.len() as u64, BatteryModule::owned_batteries_count(OWNER));
+        for battery in &exported {
+            let id = battery.passport.id;
+            assert_eq!(Some(battery.passport.clone()), BatteryModule::battery_passport(id));
+            assert_eq!(battery.co_owners, BatteryModule::co_owners(id));
+            assert_eq!(battery.co_owner_threshold, BatteryModule::co_owner_threshold(id));
+            assert_eq!(battery.auto_relist, BatteryModule::auto_relist(id));
+            assert_eq!(battery.recall_compensation, BatteryModule::compensation(id).map(|c| c.amount));
+            assert_eq!(battery.warranty, BatteryModule::warranty_cert((id, OWNER)));
+        }
+        assert!(exported.iter().any(|battery| battery.recall_compensation == Some(50)));
+        assert!(exported.iter().any(|battery| battery.auto_relist && battery.co_owners == vec![2]));
+
+        let records = BatteryModule::export_account_data(&STATION, 0, 10).records;
+        let record = |name: &str| {
+            records.iter().find(|(stored, _)| stored.as_slice() == name.as_bytes()).map(|(_, value)| value.clone())
+        };
+        assert_eq!(record("StationLocation"), Some(Some((1i64, 2i64)).encode()));
+        assert_eq!(record("StationSwapCapacity"), Some(Some(6u32).encode()));
+        assert_eq!(record("StationStatuses"), Some(BatteryModule::station_status(STATION).encode()));
+        assert_eq!(record("OwnedBatteriesArray"), None);
+    });
+}
+
+#[test]
+fn every_storage_keyed_by_account_is_exported() {
+    use support::dispatch::DecodeDifferent;
+    use support::storage::generator::StorageFunctionType;
+
+    let registered: Vec<&str> = BatteryModule::account_exporters().into_iter().map(|(name, _)| name).collect();
+    let keyed_by_account: Vec<&str> = BatteryModule::store_metadata_functions()
+        .iter()
+        .filter_map(|function| match (&function.name, &function.ty) {
+            (DecodeDifferent::Encode(name), StorageFunctionType::Map { key: DecodeDifferent::Encode(key), .. })
+                if key.contains("AccountId") => Some(*name),
+            _ => None,
+        })
+        .collect();
+    assert!(keyed_by_account.contains(&"LastActivity"));
+    for name in keyed_by_account {
+        assert!(registered.contains(&name), "{} is keyed by account but not in account_exporters", name);
+    }
+}
+
+fn abandoned_owner_with_batteries(count: usize) -> Vec<H256> {
+    System::set_block_number(1);
+    assert_ok!(BatteryModule::register_station(Origin::signed(STATION)));
+    for _ in 0..count {
+        assert_ok!(BatteryModule::register_battery(Origin::signed(STATION), OWNER));
+    }
+    assert_ok!(BatteryModule::set_tradable(Origin::signed(OWNER), BatteryModule::battery_by_index(0), true));
+    System::set_block_number(1 + ABANDONMENT_PERIOD);
+    (0..count as u64).map(BatteryModule::battery_by_index).collect()
+}
+
+#[test]
+fn escheat_hands_batteries_to_pool_in_chunks() {
+    with_externalities(&mut new_test_ext(), || {
+        let ids = abandoned_owner_with_batteries(3);
+        assert!(BatteryModule::begin_escheat(Origin::signed(STATION), OWNER).is_err());
+        System::set_block_number(ABANDONMENT_PERIOD);
+        assert_noop!(BatteryModule::begin_escheat(system::RawOrigin::Root.into(), OWNER), "Account is not abandoned");
+        System::set_block_number(1 + ABANDONMENT_PERIOD);
+        assert_ok!(BatteryModule::begin_escheat(system::RawOrigin::Root.into(), OWNER));
+        let ends_at = 1 + ABANDONMENT_PERIOD + ESCHEAT_NOTICE_PERIOD;
+        assert_eq!(battery_events().last(), Some(&RawEvent::EscheatNoticed(OWNER, ends_at)));
+        assert_noop!(
+            BatteryModule::complete_escheat(system::RawOrigin::Root.into(), OWNER, 2),
+            "Escheat notice period is not over"
+        );
+
+        System::set_block_number(ends_at);
+        assert_ok!(BatteryModule::complete_escheat(system::RawOrigin::Root.into(), OWNER, 2));
+        assert_eq!(BatteryModule::owned_batteries_count(OWNER), 1);
+        assert_eq!(BatteryModule::owned_batteries_count(ESCHEAT_POOL), 2);
+        assert_ok!(BatteryModule::complete_escheat(system::RawOrigin::Root.into(), OWNER, 2));
+        assert_eq!(battery_events().last(), Some(&RawEvent::BatteriesEscheated(OWNER, 1)));
+        assert_eq!(BatteryModule::escheat_notice(OWNER), None);
+
+        for id in &ids {
+            assert_eq!(BatteryModule::batteries(*id).owner(), &ESCHEAT_POOL);
+            assert_eq!(BatteryModule::batteries(*id).station(), Some(&STATION));
+        }
+        assert!(!BatteryModule::batteries(ids[0]).is_tradable());
+        assert_noop!(
+            BatteryModule::complete_escheat(system::RawOrigin::Root.into(), OWNER, 2),
+            "No escheat notice for this account"
+        );
+    });
+}
+
+#[test]
+fn escheat_hooks_run_once_the_whole_chunk_moved() {
+    with_externalities(&mut new_test_ext(), || {
+        let ids = abandoned_owner_with_batteries(2);
+        assert_ok!(BatteryModule::begin_escheat(system::RawOrigin::Root.into(), OWNER));
+        System::set_block_number(1 + ABANDONMENT_PERIOD + ESCHEAT_NOTICE_PERIOD);
+
+        // The chunk is taken from the end, so the hook of `ids[1]` runs first and moves
+        // `ids[0]` on behalf of the pool.
+        watch_sibling(ids[0]);
+        assert_ok!(BatteryModule::complete_escheat(system::RawOrigin::Root.into(), OWNER, 2));
+        assert_eq!(sibling_results(), vec![Ok(())]);
+        assert_eq!(BatteryModule::batteries(ids[0]).station(), None);
+        assert_eq!(BatteryModule::owned_batteries_count(OWNER), 0);
+        assert_eq!(BatteryModule::owned_batteries_count(ESCHEAT_POOL), 2);
+        for id in &ids {
+            assert_eq!(BatteryModule::batteries(*id).owner(), &ESCHEAT_POOL);
+        }
+    });
+}
+
+#[test]
+fn owner_call_one_block_before_completion_cancels_escheat() {
+    with_externalities(&mut new_test_ext(), || {
+        let ids = abandoned_owner_with_batteries(1);
+        assert_ok!(BatteryModule::begin_escheat(system::RawOrigin::Root.into(), OWNER));
+        let ends_at = BatteryModule::escheat_notice(OWNER).unwrap();
+
+        System::set_block_number(ends_at - 1);
+        assert_ok!(BatteryModule::set_tradable(Origin::signed(OWNER), ids[0], false));
+        assert_eq!(battery_events().last(), Some(&RawEvent::EscheatCancelled(OWNER)));
+        assert_eq!(BatteryModule::last_activity(OWNER), ends_at - 1);
+
+        System::set_block_number(ends_at);
+        assert_noop!(
+            BatteryModule::complete_escheat(system::RawOrigin::Root.into(), OWNER, 1),
+            "No escheat notice for this account"
+        );
+        assert_noop!(BatteryModule::begin_escheat(system::RawOrigin::Root.into(), OWNER), "Account is not abandoned");
+        assert_eq!(BatteryModule::batteries(ids[0]).owner(), &OWNER);
+    });
+}
+
+#[test]
+fn replayed_kiosk_calls_succeed_without_effect() {
+    with_externalities(&mut new_test_ext(), || {
+        const KEY: u64 = 20;
+        const RETRY: IdempotencyKey = [1u8; 16];
+        System::set_block_number(1);
+        let id = station_with_battery();
+        assert_ok!(BatteryModule::set_session_key(Origin::signed(STATION), KEY));
+        assert_ok!(BatteryModule::fetch_from_station(Origin::signed(OWNER), id));
+
+        assert_ok!(BatteryModule::store_to_station(Origin::signed(KEY), id, Some(RETRY)));
+        // The retry is scoped to the station, whichever account signs it.
+        assert_ok!(BatteryModule::store_to_station(Origin::signed(STATION), id, Some(RETRY)));
+        assert_eq!(battery_events().last(), Some(&RawEvent::IdempotentReplay(STATION, RETRY)));
+        assert_eq!(BatteryModule::batteries_count_in_station(STATION), 1);
+        assert_noop!(BatteryModule::store_to_station(Origin::signed(STATION), id, None), "Station of the battery must be None");
+
+        assert_ok!(BatteryModule::set_tradable(Origin::signed(OWNER), id, true));
+        assert_noop!(
+            BatteryModule::trade_battery(Origin::signed(STATION), id, 2, Some(RETRY)),
+            "Idempotency key already used for another call"
+        );
+
+        BatteryModule::prune_idempotency_keys(KEY_RETENTION_BLOCKS);
+        assert!(BatteryModule::processed_key((STATION, RETRY)).is_some());
+        BatteryModule::prune_idempotency_keys(1 + KEY_RETENTION_BLOCKS);
+        assert_eq!(BatteryModule::processed_key((STATION, RETRY)), None);
+
+        assert_ok!(BatteryModule::trade_battery(Origin::signed(STATION), id, 2, Some(RETRY)));
+        assert_ok!(BatteryModule::trade_battery(Origin::signed(STATION), id, 2, Some(RETRY)));
+        assert_eq!(BatteryModule::batteries(id).owner(), &2);
+        assert_eq!(BatteryModule::network_stats().trades, 1);
+    });
+}
+
+#[test]
+fn disabled_feature_rejects_entry_points_only() {
+    with_externalities(&mut new_test_ext(), || {
+        let id = station_with_battery();
+        assert!(BatteryModule::is_feature_enabled(Feature::Bridge));
+        assert!(BatteryModule::set_feature_enabled(Origin::signed(OWNER), Feature::Bridge, false).is_err());
+        assert_ok!(BatteryModule::set_feature_enabled(system::RawOrigin::Root.into(), Feature::Bridge, false));
+        assert_eq!(battery_events().last(), Some(&RawEvent::FeatureEnabledSet(Feature::Bridge, false)));
+
+        assert_noop!(BatteryModule::lock_for_bridge(Origin::signed(OWNER), id, 1, b"dest".to_vec()), "Feature is disabled");
+        assert_ok!(BatteryModule::fetch_from_station(Origin::signed(OWNER), id));
+        assert_ok!(BatteryModule::store_to_station(Origin::signed(STATION), id, None));
+        assert_ok!(BatteryModule::set_tradable(Origin::signed(OWNER), id, true));
+        assert_ok!(BatteryModule::trade_battery(Origin::signed(STATION), id, 2, None));
+        assert_ok!(BatteryModule::register_battery(Origin::signed(STATION), OWNER));
+
+        assert_ok!(BatteryModule::set_feature_enabled(system::RawOrigin::Root.into(), Feature::Bridge, true));
+        assert_ok!(BatteryModule::lock_for_bridge(Origin::signed(2), id, 1, b"dest".to_vec()));
+    });
+}
+
+/// Switches `features` on without depositing events, for tests of features that ship off.
+fn enable(features: &[Feature]) {
+    for feature in features {
+        <FeatureFlags<Test>>::insert(*feature, true);
+    }
+}
+
+#[test]
+fn features_added_after_the_flags_start_switched_off() {
+    with_externalities(&mut new_test_ext(), || {
+        let id = station_with_battery();
+        assert!(!BatteryModule::is_feature_enabled(Feature::StationReports));
+        assert!(!BatteryModule::is_feature_enabled(Feature::Notifications));
+        assert_noop!(report(OWNER, 10), "Feature is disabled");
+        assert_noop!(BatteryModule::link_identity_claim(Origin::signed(2), H256::from([3u8; 32])), "Feature is disabled");
+
+        // Buyers needn't link a claim they can't link, and no receipt is kept.
+        <RequireIdentityForTrade<Test>>::put(true);
+        assert_ok!(BatteryModule::set_tradable(Origin::signed(OWNER), id, true));
+        assert_ok!(BatteryModule::trade_battery(Origin::signed(STATION), id, 2, None));
+        assert_eq!(BatteryModule::verify_receipt(receipt_hash(id, OWNER, 2, System::block_number())), None);
+
+        assert_ok!(BatteryModule::set_feature_enabled(system::RawOrigin::Root.into(), Feature::StationReports, true));
+        assert_ok!(report(OWNER, 10));
+    });
+}
+
+fn report(reporter: u64, stake: u64) -> support::dispatch::Result {
+    BatteryModule::report_station(Origin::signed(reporter), STATION, ReportCategory::RefusedHandover, H256::from([7u8; 32]), stake)
+}
+
+#[test]
+fn reports_place_a_station_under_review_at_the_threshold() {
+    with_externalities(&mut new_test_ext(), || {
+        enable(&[Feature::StationReports]);
+        let id = station_with_battery();
+        assert_ok!(BatteryModule::set_tradable(Origin::signed(OWNER), id, true));
+        assert_noop!(report(STATION, 10), "Station can't report itself");
+        assert_noop!(report(OWNER, 0), "Report needs a stake");
+
+        assert_ok!(report(OWNER, 60));
+        assert_eq!(Balances::reserved_balance(OWNER), 60);
+        assert!(!BatteryModule::is_under_review(STATION));
+
+        assert_ok!(report(2, 40));
+        assert!(BatteryModule::is_under_review(STATION));
+        assert_eq!(battery_events().last(), Some(&RawEvent::StationUnderReview(STATION)));
+        assert_noop!(BatteryModule::trade_battery(Origin::signed(STATION), id, 2, None), "Station is under review");
+        assert_ok!(BatteryModule::fetch_from_station(Origin::signed(OWNER), id));
+    });
+}
+
+#[test]
+fn reported_stake_starts_over_each_era() {
+    with_externalities(&mut new_test_ext(), || {
+        enable(&[Feature::StationReports]);
+        station_with_battery();
+        assert_ok!(report(OWNER, 60));
+        System::set_block_number(REPORT_ERA_LENGTH);
+        assert_ok!(report(2, 60));
+        assert!(!BatteryModule::is_under_review(STATION));
+        assert_eq!(BatteryModule::station_reports(STATION).len(), 2);
+    });
+}
+
+#[test]
+fn reports_per_era_are_capped() {
+    with_externalities(&mut new_test_ext(), || {
+        enable(&[Feature::StationReports]);
+        station_with_battery();
+        for _ in 0..MAX_REPORTS_PER_ERA {
+            assert_ok!(report(OWNER, 1));
+        }
+        assert_noop!(report(OWNER, 1), "Too many reports this era");
+        System::set_block_number(REPORT_ERA_LENGTH);
+        assert_ok!(report(OWNER, 1));
+    });
+}
+
+#[test]
+fn upheld_reports_return_stakes_and_restart_probation() {
+    with_externalities(&mut new_test_ext(), || {
+        enable(&[Feature::StationReports]);
+        station_with_battery();
+        assert_ok!(BatteryModule::graduate_station(system::RawOrigin::Root.into(), STATION));
+        assert_ok!(report(OWNER, 60));
+        assert_ok!(report(2, 40));
+        assert!(BatteryModule::resolve_reports(Origin::signed(OWNER), STATION, true).is_err());
+
+        assert_ok!(BatteryModule::resolve_reports(system::RawOrigin::Root.into(), STATION, true));
+        assert_eq!(Balances::free_balance(OWNER), 1_000);
+        assert_eq!(Balances::free_balance(2), 1_000);
+        assert_eq!(Balances::reserved_balance(OWNER), 0);
+        assert!(!BatteryModule::is_under_review(STATION));
+        assert!(BatteryModule::station_reports(STATION).is_empty());
+        assert_eq!(BatteryModule::station_status(STATION), StationStatus::Probation { trades_completed: 0 });
+        assert_eq!(battery_events().last(), Some(&RawEvent::ReportsResolved(STATION, true)));
+        assert_noop!(
+            BatteryModule::resolve_reports(system::RawOrigin::Root.into(), STATION, true),
+            "No reports against this station"
+        );
+    });
+}
+
+#[test]
+fn rejected_reports_slash_stakes() {
+    with_externalities(&mut new_test_ext(), || {
+        enable(&[Feature::StationReports]);
+        station_with_battery();
+        assert_ok!(BatteryModule::graduate_station(system::RawOrigin::Root.into(), STATION));
+        assert_ok!(report(OWNER, 60));
+        assert_ok!(report(2, 40));
+
+        assert_ok!(BatteryModule::resolve_reports(system::RawOrigin::Root.into(), STATION, false));
+        assert_eq!(Balances::free_balance(OWNER), 940);
+        assert_eq!(Balances::free_balance(2), 960);
+        assert_eq!(Balances::reserved_balance(OWNER), 0);
+        assert_eq!(Balances::reserved_balance(2), 0);
+        assert!(!BatteryModule::is_under_review(STATION));
+        assert_eq!(BatteryModule::station_status(STATION), StationStatus::Full);
+
+        // The cleared era total doesn't count towards the next review.
+        assert_ok!(report(OWNER, 60));
+        assert!(!BatteryModule::is_under_review(STATION));
+    });
+}
+
+fn photo(n: u8) -> H256 {
+    H256::from([n; 32])
+}
+
+#[test]
+fn documents_are_attributed_and_capped_per_custody_period() {
+    with_externalities(&mut new_test_ext(), || {
+        enable(&[Feature::Documents]);
+        let id = station_with_battery();
+        System::set_block_number(4);
+        assert_noop!(
+            BatteryModule::anchor_document(Origin::signed(2), id, photo(1), DocKind::ConditionPhoto),
+            "Sender is neither the owner nor the station of this battery"
+        );
+        assert_ok!(BatteryModule::anchor_document(Origin::signed(OWNER), id, photo(1), DocKind::ConditionPhoto));
+        assert_ok!(BatteryModule::anchor_document(Origin::signed(STATION), id, photo(2), DocKind::InspectionReport));
+        assert_eq!(battery_events().last(), Some(&RawEvent::DocumentAnchored(id, 1, STATION)));
+        assert_eq!(BatteryModule::document((id, 0)), Some(DocumentAnchor {
+            doc_hash: photo(1),
+            kind: DocKind::ConditionPhoto,
+            anchored_by: OWNER,
+            at: 4,
+        }));
+        assert_eq!(BatteryModule::document((id, 1)).unwrap().anchored_by, STATION);
+
+        for n in 2..MAX_DOCUMENTS_PER_CUSTODY {
+            assert_ok!(BatteryModule::anchor_document(Origin::signed(OWNER), id, photo(n as u8 + 1), DocKind::Other));
+        }
+        assert_noop!(
+            BatteryModule::anchor_document(Origin::signed(STATION), id, photo(9), DocKind::ConditionPhoto),
+            "Too many documents in this custody period"
+        );
+
+        // Handing the battery back to its owner starts a new custody period.
+        assert_ok!(BatteryModule::fetch_from_station(Origin::signed(OWNER), id));
+        assert_ok!(BatteryModule::anchor_document(Origin::signed(OWNER), id, photo(9), DocKind::ConditionPhoto));
+        assert_eq!(BatteryModule::document_count(id), MAX_DOCUMENTS_PER_CUSTODY + 1);
+        assert_noop!(
+            BatteryModule::anchor_document(Origin::signed(STATION), id, photo(10), DocKind::ConditionPhoto),
+            "Sender is neither the owner nor the station of this battery"
+        );
+    });
+}
+
+#[test]
+fn documents_stay_until_the_battery_is_decommissioned() {
+    with_externalities(&mut new_test_ext(), || {
+        enable(&[Feature::Documents]);
+        let id = station_with_battery();
+        assert_ok!(BatteryModule::anchor_document(Origin::signed(OWNER), id, photo(1), DocKind::ConditionPhoto));
+        let first = BatteryModule::document((id, 0));
+        assert_ok!(BatteryModule::anchor_document(Origin::signed(OWNER), id, photo(1), DocKind::Other));
+        assert_eq!(BatteryModule::document((id, 0)), first);
+        assert_eq!(BatteryModule::document((id, 1)).unwrap().kind, DocKind::Other);
+
+        assert_ok!(BatteryModule::decommission_battery(Origin::signed(OWNER), id, DecommissionReason::EndOfLife));
+        assert_eq!(BatteryModule::document_count(id), 0);
+        assert_eq!(BatteryModule::document((id, 0)), None);
+        assert_eq!(BatteryModule::document((id, 1)), None);
+    });
+}
+
+#[test]
+fn challenge_parties_cite_documents_as_evidence() {
+    with_externalities(&mut new_test_ext(), || {
+        enable(&[Feature::Documents]);
+        let id = station_with_battery();
+        assert_ok!(BatteryModule::anchor_document(Origin::signed(OWNER), id, photo(1), DocKind::ConditionPhoto));
+        assert_ok!(BatteryModule::challenge_battery_ownership(Origin::signed(2), id));
+        let challenge_id = last_challenge_id();
+
+        assert_noop!(BatteryModule::submit_evidence(Origin::signed(3), challenge_id, 0), "Sender is not a party to the challenge");
+        assert_noop!(BatteryModule::submit_evidence(Origin::signed(2), challenge_id, 1), "Document does not exist");
+        assert_ok!(BatteryModule::submit_evidence(Origin::signed(2), challenge_id, 0));
+        assert_eq!(battery_events().last(), Some(&RawEvent::EvidenceSubmitted(challenge_id, 0)));
+        assert_noop!(BatteryModule::submit_evidence(Origin::signed(OWNER), challenge_id, 0), "Document already submitted");
+        assert_eq!(BatteryModule::challenge_evidence(challenge_id), vec![0]);
+
+        assert_ok!(BatteryModule::resolve_challenge(system::RawOrigin::Root.into(), challenge_id, OWNER));
+        assert!(BatteryModule::challenge_evidence(challenge_id).is_empty());
+    });
+}
+
+fn receipt_hash(id: H256, from: u64, to: u64, block: u64) -> H256 {
+    BlakeTwo256::hash_of(&(id, from, to, block))
+}
+
+#[test]
+fn trade_receipt_is_reproducible_from_the_event() {
+    with_externalities(&mut new_test_ext(), || {
+        enable(&[Feature::Receipts]);
+        let id = station_with_battery();
+        System::set_block_number(3);
+        assert_ok!(BatteryModule::set_tradable(Origin::signed(OWNER), id, true));
+        assert_ok!(BatteryModule::trade_battery(Origin::signed(STATION), id, 2, None));
+
+        let receipt = match battery_events().last() {
+            Some(RawEvent::Trade(battery_id, from, to, _, receipt)) => {
+                assert_eq!(*receipt, receipt_hash(*battery_id, *from, *to, 3));
+                *receipt
+            }
+            _ => panic!("no trade"),
+        };
+        assert_eq!(BatteryModule::verify_receipt(receipt), Some(ReceiptInfo { battery_id: id, block: 3 }));
+        assert_eq!(BatteryModule::verify_receipt(receipt_hash(id, OWNER, 2, 4)), None);
+    });
+}
+
+#[test]
+fn receipts_are_pruned_after_the_retention_period() {
+    with_externalities(&mut new_test_ext(), || {
+        enable(&[Feature::Receipts]);
+        let id = station_with_battery();
+        System::set_block_number(3);
+        assert_ok!(BatteryModule::set_tradable(Origin::signed(OWNER), id, true));
+        assert_ok!(BatteryModule::trade_battery(Origin::signed(STATION), id, 2, None));
+        let receipt = receipt_hash(id, OWNER, 2, 3);
+
+        BatteryModule::prune_receipts(2 + RECEIPT_RETENTION_BLOCKS);
+        assert!(BatteryModule::verify_receipt(receipt).is_some());
+        BatteryModule::prune_receipts(3 + RECEIPT_RETENTION_BLOCKS);
+        assert_eq!(BatteryModule::verify_receipt(receipt), None);
+        assert!(BatteryModule::receipts_issued_at(3).is_empty());
+    });
+}
+
+#[test]
+fn trades_can_require_an_identity_claim() {
+    with_externalities(&mut new_test_ext(), || {
+        enable(&[Feature::IdentityClaims]);
+        let id = station_with_battery();
+        assert_ok!(BatteryModule::set_tradable(Origin::signed(OWNER), id, true));
+        <RequireIdentityForTrade<Test>>::put(true);
+        assert_noop!(
+            BatteryModule::trade_battery(Origin::signed(STATION), id, 2, None),
+            "Buyer has not linked an identity claim"
+        );
+
+        let claim = H256::from([5u8; 32]);
+        assert_ok!(BatteryModule::link_identity_claim(Origin::signed(2), claim));
+        assert_eq!(BatteryModule::identity_claim(2), Some(claim));
+        assert_eq!(battery_events().last(), Some(&RawEvent::IdentityClaimLinked(2, claim)));
+        assert_ok!(BatteryModule::trade_battery(Origin::signed(STATION), id, 2, None));
+    });
+}
+
+#[test]
+fn battery_nonce_moves_once_per_changing_call() {
+    with_externalities(&mut new_test_ext(), || {
+        let id = station_with_battery();
+        assert_ok!(BatteryModule::register_battery(Origin::signed(STATION), OWNER));
+        let other = BatteryModule::battery_by_index(1);
+        assert_eq!(BatteryModule::battery_nonce(id), 0);
+
+        assert_ok!(BatteryModule::set_tradable(Origin::signed(OWNER), id, true));
+        assert_eq!(BatteryModule::battery_nonce(id), 1);
+        assert!(BatteryModule::fetch_from_station(Origin::signed(2), id).is_err());
+        assert_eq!(BatteryModule::battery_nonce(id), 1);
+
+        assert_ok!(BatteryModule::trade_battery(Origin::signed(STATION), id, 2, Some([1u8; 16])));
+        assert_eq!(BatteryModule::battery_nonce(id), 2);
+        // A replay doesn't change the battery again.
+        assert_ok!(BatteryModule::trade_battery(Origin::signed(STATION), id, 2, Some([1u8; 16])));
+        assert_eq!(BatteryModule::battery_nonce(id), 2);
+
+        assert_ok!(BatteryModule::fetch_from_station(Origin::signed(2), id));
+        assert_eq!(BatteryModule::battery_nonce(id), 3);
+        assert_eq!(BatteryModule::battery_nonce(other), 0);
+    });
+}
+
+#[test]
+fn non_transferable_batteries_cannot_be_sold_until_lifted() {
+    with_externalities(&mut new_test_ext(), || {
+        assert_ok!(BatteryModule::register_station(Origin::signed(STATION)));
+        assert_ok!(BatteryModule::register_non_transferable_battery(Origin::signed(STATION), OWNER));
+        let id = BatteryModule::battery_by_index(0);
+        assert_eq!(battery_events().last(), Some(&RawEvent::NonTransferableRegistered(id)));
+        assert!(BatteryModule::battery_passport(id).unwrap().non_transferable);
+
+        assert_noop!(BatteryModule::set_tradable(Origin::signed(OWNER), id, true), "Battery is non-transferable");
+        assert_noop!(BatteryModule::switch_tradable(Origin::signed(OWNER), id), "Battery is non-transferable");
+        assert_noop!(BatteryModule::set_auto_relist(Origin::signed(OWNER), id, true), "Battery is non-transferable");
+        assert_noop!(BatteryModule::trade_battery(Origin::signed(STATION), id, 2, None), "Battery is non-transferable");
+        // Custody still changes hands.
+        assert_ok!(BatteryModule::fetch_from_station(Origin::signed(OWNER), id));
+        assert_ok!(BatteryModule::store_to_station(Origin::signed(STATION), id, None));
+
+        assert!(BatteryModule::lift_transfer_restriction(Origin::signed(OWNER), id).is_err());
+        let passport_hash = BatteryModule::passport_hash(id);
+        assert_ok!(BatteryModule::lift_transfer_restriction(system::RawOrigin::Root.into(), id));
+        assert_eq!(battery_events().last(), Some(&RawEvent::TransferRestrictionLifted(id)));
+        assert_ne!(BatteryModule::passport_hash(id), passport_hash);
+        assert_noop!(
+            BatteryModule::lift_transfer_restriction(system::RawOrigin::Root.into(), id),
+            "Battery is not non-transferable"
+        );
+        assert_ok!(BatteryModule::set_tradable(Origin::signed(OWNER), id, true));
+        assert_ok!(BatteryModule::trade_battery(Origin::signed(STATION), id, 2, None));
+    });
+}
+
+#[test]
+fn non_transferable_batteries_can_be_decommissioned() {
+    with_externalities(&mut new_test_ext(), || {
+        assert_ok!(BatteryModule::register_station(Origin::signed(STATION)));
+        assert_ok!(BatteryModule::register_non_transferable_battery(Origin::signed(STATION), OWNER));
+        let id = BatteryModule::battery_by_index(0);
+        assert_ok!(BatteryModule::decommission_battery(Origin::signed(OWNER), id, DecommissionReason::EndOfLife));
+        assert!(!BatteryModule::is_non_transferable(id));
+    });
+}
+
+#[test]
+fn community_votes_promote_a_basic_station() {
+    with_externalities(&mut new_test_ext(), || {
+        assert_ok!(BatteryModule::register_station(Origin::signed(STATION)));
+        assert_noop!(BatteryModule::vote_to_promote_station(Origin::signed(OWNER), STATION), "Feature is disabled");
+        assert_noop!(BatteryModule::retract_vote(Origin::signed(OWNER), STATION), "Feature is disabled");
+        enable(&[Feature::PromotionVotes]);
+        assert_noop!(BatteryModule::vote_to_promote_station(Origin::signed(OWNER), 2), "Not a station!");
+        assert_noop!(BatteryModule::vote_to_promote_station(Origin::signed(STATION), STATION), "Stations can't vote for themselves");
+        assert_noop!(BatteryModule::retract_vote(Origin::signed(OWNER), STATION), "No vote for this station");
+
+        assert_ok!(BatteryModule::vote_to_promote_station(Origin::signed(OWNER), STATION));
+        assert_eq!(battery_events().last(), Some(&RawEvent::VoteCast(OWNER, STATION)));
+        assert_noop!(BatteryModule::vote_to_promote_station(Origin::signed(OWNER), STATION), "Already voted for this station");
+        assert_ok!(BatteryModule::retract_vote(Origin::signed(OWNER), STATION));
+        assert_eq!(BatteryModule::votes(STATION), 0);
+
+        for voter in 1..=PROMOTION_THRESHOLD {
+            assert_ok!(BatteryModule::vote_to_promote_station(Origin::signed(voter), STATION));
+        }
+        assert_eq!(BatteryModule::trust_level(STATION), TrustLevel::Basic);
+        assert_ok!(BatteryModule::vote_to_promote_station(Origin::signed(PROMOTION_THRESHOLD + 1), STATION));
+        assert_eq!(battery_events().last(), Some(&RawEvent::StationPromoted(STATION)));
+        assert_eq!(BatteryModule::trust_level(STATION), TrustLevel::Verified);
+
+        // Retracting doesn't demote.
+        assert_ok!(BatteryModule::retract_vote(Origin::signed(OWNER), STATION));
+        assert_eq!(BatteryModule::trust_level(STATION), TrustLevel::Verified);
+    });
+}
+
+/// Registers `STATION` and `OTHER_STATION` as stations and `count` batteries in `STATION`'s own pool.
+fn two_stations_with_pool(count: u64) -> Vec<H256> {
+    assert_ok!(BatteryModule::register_station(Origin::signed(STATION)));
+    assert_ok!(BatteryModule::register_station(Origin::signed(OTHER_STATION)));
+    (0..count)
+        .map(|index| {
+            assert_ok!(BatteryModule::register_battery(Origin::signed(STATION), STATION));
+            BatteryModule::battery_by_index(index)
+        })
+        .collect()
+}
+
+#[test]
+fn rebalance_moves_every_battery_or_none() {
+    with_externalities(&mut new_test_ext(), || {
+        enable(&[Feature::Rebalancing]);
+        System::set_block_number(1);
+        let ids = two_stations_with_pool(2);
+        assert_ok!(BatteryModule::register_battery(Origin::signed(STATION), OWNER));
+        let customer_battery = BatteryModule::battery_by_index(2);
+        assert_noop!(
+            BatteryModule::propose_rebalance(Origin::signed(STATION), OTHER_STATION, vec![ids[0], customer_battery]),
+            "Battery is not in the station's pool"
+        );
+        assert_noop!(
+            BatteryModule::propose_rebalance(Origin::signed(STATION), OTHER_STATION, vec![ids[0], ids[0]]),
+            "Duplicate battery in rebalance"
+        );
+
+        assert_ok!(BatteryModule::propose_rebalance(Origin::signed(STATION), OTHER_STATION, ids.clone()));
+        let proposal_id = match battery_events().last() {
+            Some(&RawEvent::RebalanceProposed(proposal_id, STATION, OTHER_STATION)) => proposal_id,
+            other => panic!("unexpected event {:?}", other),
+        };
+        assert_noop!(BatteryModule::accept_rebalance(Origin::signed(STATION), proposal_id), "Sender is not the destination station");
+
+        // One battery left the pool since, so nothing moves.
+        assert_ok!(BatteryModule::fetch_from_station(Origin::signed(STATION), ids[1]));
+        assert_noop!(BatteryModule::accept_rebalance(Origin::signed(OTHER_STATION), proposal_id), "Battery is not in the station's pool");
+        assert_eq!(BatteryModule::batteries(ids[0]).station(), Some(&STATION));
+
+        assert_ok!(BatteryModule::store_to_station(Origin::signed(STATION), ids[1], None));
+        assert_ok!(BatteryModule::accept_rebalance(Origin::signed(OTHER_STATION), proposal_id));
+        assert_eq!(battery_events().last(), Some(&RawEvent::RebalanceAccepted(proposal_id)));
+        for id in &ids {
+            assert_eq!(BatteryModule::batteries(id).station(), Some(&OTHER_STATION));
+        }
+        assert_eq!(BatteryModule::batteries_count_in_station(STATION), 1);
+        assert_eq!(BatteryModule::batteries_count_in_station(OTHER_STATION), 2);
+        assert_eq!(BatteryModule::rebalance_proposal(proposal_id), None);
+    });
+}
+
+#[test]
+fn rebalance_proposals_expire_or_can_be_rejected() {
+    with_externalities(&mut new_test_ext(), || {
+        enable(&[Feature::Rebalancing]);
+        System::set_block_number(1);
+        let ids = two_stations_with_pool(1);
+        assert_ok!(BatteryModule::propose_rebalance(Origin::signed(STATION), OTHER_STATION, ids.clone()));
+        let first = BatteryModule::rebalances_expiring_at(1 + REBALANCE_EXPIRY_BLOCKS)[0];
+        assert_ok!(BatteryModule::reject_rebalance(Origin::signed(OTHER_STATION), first));
+        assert_eq!(battery_events().last(), Some(&RawEvent::RebalanceRejected(first)));
+
+        System::set_block_number(2);
+        assert_ok!(BatteryModule::propose_rebalance(Origin::signed(STATION), OTHER_STATION, ids.clone()));
+        let second = BatteryModule::rebalances_expiring_at(2 + REBALANCE_EXPIRY_BLOCKS)[0];
+        BatteryModule::expire_rebalances(1 + REBALANCE_EXPIRY_BLOCKS);
+        assert!(BatteryModule::rebalance_proposal(second).is_some());
+        BatteryModule::expire_rebalances(2 + REBALANCE_EXPIRY_BLOCKS);
+        assert_eq!(battery_events().last(), Some(&RawEvent::RebalanceExpired(second)));
+        assert_noop!(BatteryModule::accept_rebalance(Origin::signed(OTHER_STATION), second), "Rebalance proposal does not exist");
+        assert!(BatteryModule::rebalances_expiring_at(2 + REBALANCE_EXPIRY_BLOCKS).is_empty());
+    });
+}
+
+#[test]
+fn battery_storage_key_is_where_the_battery_is_stored() {
+    with_externalities(&mut new_test_ext(), || {
+        assert_ok!(BatteryModule::register_station(Origin::signed(STATION)));
+        for owner in 1..4 {
+            assert_ok!(BatteryModule::register_battery(Origin::signed(STATION), owner));
+        }
+        for index in 0..3 {
+            let id = BatteryModule::battery_by_index(index);
+            let key = BatteryModule::battery_storage_key(id);
+            assert_eq!(runtime_io::storage(&key), Some(BatteryModule::batteries(id).encode()));
+        }
+        assert_eq!(runtime_io::storage(&BatteryModule::battery_storage_key(H256::from([7u8; 32]))), None);
+    });
+}
+
+#[test]
+fn sampled_stations_must_answer_their_audit() {
+    with_externalities(&mut new_test_ext(), || {
+        enable(&[Feature::Audits]);
+        <StationBond<Test>>::put(100);
+        let stations = [STATION, OTHER_STATION, 12];
+        for station in stations.iter() {
+            let _ = Balances::deposit_creating(station, 1_000);
+            assert_ok!(BatteryModule::register_station(Origin::signed(*station)));
+            assert_ok!(BatteryModule::register_battery(Origin::signed(*station), OWNER));
+            assert_eq!(BatteryModule::bond_of(station), 100);
+        }
+        BatteryModule::start_audit_round(AUDIT_INTERVAL - 1);
+        BatteryModule::submit_audits(AUDIT_INTERVAL - 1);
+        assert!(submitted_audits().is_empty());
+
+        System::set_block_number(AUDIT_INTERVAL);
+        BatteryModule::start_audit_round(AUDIT_INTERVAL);
+        BatteryModule::submit_audits(AUDIT_INTERVAL);
+        let drawn = submitted_audits();
+        assert_eq!(drawn, BatteryModule::audit_sample());
+        assert_eq!(drawn.len(), 2);
+        let skipped = *stations.iter().find(|station| !drawn.contains(station)).unwrap();
+        assert!(!is_valid(BatteryModule::validate_unsigned(&Call::schedule_audit(skipped))));
+        assert_noop!(
+            BatteryModule::schedule_audit(system::RawOrigin::Inherent.into(), skipped),
+            "Station was not drawn for an audit"
+        );
+        assert!(BatteryModule::schedule_audit(Origin::signed(drawn[0]), drawn[0]).is_err());
+        for station in &drawn {
+            assert!(is_valid(BatteryModule::validate_unsigned(&Call::schedule_audit(*station))));
+            assert_ok!(BatteryModule::schedule_audit(system::RawOrigin::Inherent.into(), *station));
+            assert!(!is_valid(BatteryModule::validate_unsigned(&Call::schedule_audit(*station))));
+        }
+
+        let audited: Vec<(u64, H256)> = drawn
+            .iter()
+            .filter_map(|station| BatteryModule::open_audit(station).map(|audit_id| (*station, audit_id)))
+            .collect();
+        assert_eq!(audited.len(), 2);
+        let (answering, audit_id) = audited[0];
+        let (silent, missed_id) = audited[1];
+        let own = BatteryModule::battery_of_station_by_index((answering, 0));
+        let foreign = BatteryModule::battery_of_station_by_index((silent, 0));
+
+        assert_noop!(BatteryModule::submit_audit_results(Origin::signed(silent), audit_id, vec![]), "Sender is not the audited station");
+        assert_noop!(
+            BatteryModule::submit_audit_results(Origin::signed(answering), audit_id, vec![(foreign, 90)]),
+            "Battery is not held by the station"
+        );
+        assert_noop!(
+            BatteryModule::submit_audit_results(Origin::signed(answering), audit_id, vec![(own, 101)]),
+            "Health must be a percentage"
+        );
+        assert_ok!(BatteryModule::submit_audit_results(Origin::signed(answering), audit_id, vec![(own, 90)]));
+        assert_eq!(battery_events().last(), Some(&RawEvent::AuditSubmitted(audit_id, answering)));
+        assert_eq!(BatteryModule::audit_reports(audit_id), vec![(own, 90)]);
+        assert_eq!(BatteryModule::battery_health(own), Some(90));
+        // An answered audit isn't opened again in the same round.
+        assert_noop!(
+            BatteryModule::schedule_audit(system::RawOrigin::Inherent.into(), answering),
+            "Station was already audited this round"
+        );
+
+        BatteryModule::expire_audits(AUDIT_INTERVAL + AUDIT_DEADLINE_BLOCKS);
+        assert!(battery_events().contains(&RawEvent::AuditMissed(missed_id, silent)));
+        assert!(battery_events().contains(&RawEvent::StationBondSlashed(silent, 100)));
+        assert_eq!((Balances::free_balance(silent), Balances::reserved_balance(silent)), (900, 0));
+        assert_eq!(BatteryModule::bond_of(silent), 0);
+        assert_eq!(Balances::reserved_balance(answering), 100);
+        assert!(BatteryModule::is_under_review(silent));
+        assert!(!BatteryModule::is_under_review(answering));
+        assert_eq!(BatteryModule::open_audit(silent), None);
+
+        assert_ok!(BatteryModule::resolve_reports(system::RawOrigin::Root.into(), silent, false));
+        assert!(!BatteryModule::is_under_review(silent));
+
+        // Once the round is over nothing more can be scheduled for it.
+        System::set_block_number(2 * AUDIT_INTERVAL);
+        assert!(BatteryModule::audit_sample().is_empty());
+    });
+}
+
+#[test]
+fn first_station_to_confirm_a_store_request_wins() {
+    with_externalities(&mut new_test_ext(), || {
+        enable(&[Feature::StoreRequests]);
+        let id = station_with_battery();
+        assert_ok!(BatteryModule::register_station(Origin::signed(OTHER_STATION)));
+        assert_noop!(BatteryModule::request_store(Origin::signed(OWNER), id, OTHER_STATION), "Battery is already in custody");
+        assert_ok!(BatteryModule::fetch_from_station(Origin::signed(OWNER), id));
+
+        assert_noop!(BatteryModule::request_store(Origin::signed(2), id, STATION), "You are not the owner of this battery");
+        assert_ok!(BatteryModule::request_store(Origin::signed(OWNER), id, STATION));
+        assert_ok!(BatteryModule::request_store(Origin::signed(OWNER), id, OTHER_STATION));
+        assert_eq!(battery_events().last(), Some(&RawEvent::StoreRequested(id, OTHER_STATION)));
+        assert_noop!(BatteryModule::request_store(Origin::signed(OWNER), id, STATION), "Store already requested at this station");
+        assert_eq!(BatteryModule::pending_store_stations(id), vec![STATION, OTHER_STATION]);
+
+        assert_ok!(BatteryModule::confirm_store(Origin::signed(OTHER_STATION), id));
+        assert_eq!(BatteryModule::batteries(id).station(), Some(&OTHER_STATION));
+        assert!(BatteryModule::pending_store_stations(id).is_empty());
+        assert_eq!(BatteryModule::pending_store((id, STATION)), None);
+        assert_noop!(BatteryModule::confirm_store(Origin::signed(STATION), id), "Battery is already in custody");
+    });
+}
+
+#[test]
+fn store_requests_can_be_cancelled_expire_and_are_wiped_by_a_direct_store() {
+    with_externalities(&mut new_test_ext(), || {
+        enable(&[Feature::StoreRequests]);
+        System::set_block_number(1);
+        let id = station_with_battery();
+        assert_ok!(BatteryModule::register_station(Origin::signed(OTHER_STATION)));
+        assert_ok!(BatteryModule::fetch_from_station(Origin::signed(OWNER), id));
+
+        assert_ok!(BatteryModule::request_store(Origin::signed(OWNER), id, STATION));
+        assert_ok!(BatteryModule::cancel_store_request(Origin::signed(OWNER), id, STATION));
+        assert_eq!(battery_events().last(), Some(&RawEvent::StoreRequestCancelled(id, STATION)));
+        assert_noop!(BatteryModule::confirm_store(Origin::signed(STATION), id), "No store request at this station");
+        assert_noop!(BatteryModule::cancel_store_request(Origin::signed(OWNER), id, STATION), "No store request at this station");
+
+        assert_ok!(BatteryModule::request_store(Origin::signed(OWNER), id, STATION));
+        System::set_block_number(2 + STORE_REQUEST_EXPIRY_BLOCKS);
+        assert_noop!(BatteryModule::confirm_store(Origin::signed(STATION), id), "Store request expired");
+
+        assert_ok!(BatteryModule::request_store(Origin::signed(OWNER), id, OTHER_STATION));
+        assert_ok!(BatteryModule::store_to_station(Origin::signed(STATION), id, None));
+        assert!(BatteryModule::pending_store_stations(id).is_empty());
+        assert_eq!(BatteryModule::pending_store((id, OTHER_STATION)), None);
+    });
+}
+
+#[test]
+fn accounts_choose_their_notifications() {
+    with_externalities(&mut new_test_ext(), || {
+        enable(&[Feature::Notifications]);
+        assert_eq!(BatteryModule::notification_prefs(OWNER), NotificationPrefs::default());
+        let prefs = NotificationPrefs { notify_on_trade: true, notify_on_recall: true, ..Default::default() };
+        assert_ok!(BatteryModule::set_notification_prefs(Origin::signed(OWNER), prefs));
+        assert_eq!(battery_events().last(), Some(&RawEvent::NotificationPrefsUpdated(OWNER)));
+        assert_eq!(BatteryModule::notification_prefs(OWNER), prefs);
+        assert_eq!(BatteryModule::notification_prefs(2), NotificationPrefs::default());
+    });
+}
@@ -0,0 +1,228 @@
+//! Listing and trading, co-owner approvals and idempotent station calls.
+
+use super::*;
+
+impl<T: Trait> Module<T> {
+    /// Checks `trade_battery` signed by `station` would pass, without changing anything.
+    pub fn can_trade(station: &T::AccountId, id: T::Hash, to: &T::AccountId) -> Result {
+        Self::ensure_migrated()?;
+        Self::ensure_not_in_flight(id)?;
+
+        ensure!(<StationsIndex<T>>::exists(station), "Sender is not a station");
+        ensure!(!Self::is_under_review(station), "Station is under review");
+        ensure!(<Batteries<T>>::exists(id), "Battery does not exist");
+        Self::ensure_transferable(id)?;
+        let battery = Self::batteries(id);
+        ensure!(battery.station() == Some(station), "Sender must be the station of this battery");
+        ensure!(battery.is_tradable(), "Battery must be tradable");
+        ensure!(!Self::is_frozen_by_challenge(id), "Battery ownership is being challenged");
+        ensure!(battery.owner != *to, "To account can't be the owner of this battery");
+        ensure!(T::AccountStatus::is_alive(to), "Owner account is inactive");
+        ensure!(
+            !Self::requires_identity_claim() || Self::identity_claim(to).is_some(),
+            "Buyer has not linked an identity claim"
+        );
+        Self::ensure_transfer_approved(id, to)
+    }
+
+    pub(super) fn do_set_tradable(sender: T::AccountId, id: T::Hash, tradable: bool) -> Result {
+        Self::ensure_not_in_flight(id)?;
+        ensure!(<Batteries<T>>::exists(id), "Id does not exist");
+        let mut battery = Self::batteries(id);
+        ensure!(Self::is_owner_or_co_owner(&battery, id, &sender), "You are not the owner of this battery");
+        let station = battery.station().cloned().ok_or("Battery must be in station")?;
+        let owner = battery.owner.clone();
+        ensure!(!tradable || T::AccountStatus::is_alive(&owner), "Owner account is inactive");
+        if tradable {
+            Self::ensure_transferable(id)?;
+        }
+
+        if tradable && !battery.is_tradable() {
+            let action_hash = Self::listing_action_hash(id);
+            Self::ensure_approved(id, action_hash)?;
+            Self::consume_approvals(id, action_hash);
+        }
+
+        if battery.is_tradable() != tradable {
+            let before = battery.custody.clone();
+            battery.custody = if tradable { Custody::Tradable(station.clone()) } else { Custody::Station(station.clone()) };
+            Self::note_custody_change(&before, &battery.custody);
+            Self::put_battery(id, battery);
+            Self::audit(&station, Some(sender.clone()), StationAction::TradableSet(id, tradable));
+            if tradable {
+                Self::stop_staking(id, &owner);
+            } else {
+                Self::start_staking(id);
+            }
+        }
+        Self::bump_nonce(id);
+
+        Self::deposit_event(RawEvent::SwitchTradable(id, tradable));
+        Ok(())
+    }
+
+    /// The action hash co-owners approve to let battery `id` pass to `to`.
+    pub fn transfer_action_hash(id: T::Hash, to: &T::AccountId) -> T::Hash {
+        (&b"transfer"[..], id, to).using_encoded(<T as system::Trait>::Hashing::hash)
+    }
+
+    /// Whether `key` was already used by `station` for the call hashed as `payload`.
+    /// Replays are reported; reusing the key for another call is an error.
+    pub(super) fn is_replay(
+        station: &T::AccountId,
+        key: Option<IdempotencyKey>,
+        payload: T::Hash,
+    ) -> rstd::result::Result<bool, &'static str> {
+        let key = match key {
+            Some(key) => key,
+            None => return Ok(false),
+        };
+        match Self::processed_key((station.clone(), key)) {
+            Some(processed) if processed == payload => {
+                Self::deposit_event(RawEvent::IdempotentReplay(station.clone(), key));
+                Ok(true)
+            }
+            Some(_) => Err("Idempotency key already used for another call"),
+            None => Ok(false),
+        }
+    }
+
+    pub(super) fn record_key(station: &T::AccountId, key: Option<IdempotencyKey>, payload: T::Hash) {
+        if let Some(key) = key {
+            <ProcessedKeys<T>>::insert((station.clone(), key), payload);
+            <KeysProcessedAt<T>>::mutate(<system::Module<T>>::block_number(), |keys| keys.push((station.clone(), key)));
+        }
+    }
+
+    pub(super) fn prune_idempotency_keys(n: T::BlockNumber) {
+        let retention = Self::key_retention_blocks();
+        if n < retention {
+            return;
+        }
+        for key in <KeysProcessedAt<T>>::take(n - retention) {
+            <ProcessedKeys<T>>::remove(key);
+        }
+    }
+
+    /// Buyers only have to link an identity claim while they can link one.
+    fn requires_identity_claim() -> bool {
+        Self::require_identity_for_trade() && Self::is_feature_enabled(Feature::IdentityClaims)
+    }
+
+    /// Returns the receipt hash of trading `id` in the current block,
+    /// `Hashing::hash_of(&(id, from, to, block_number))`, and remembers it while
+    /// receipts are switched on.
+    pub(super) fn issue_receipt(id: T::Hash, from: &T::AccountId, to: &T::AccountId) -> T::Hash {
+        let now = <system::Module<T>>::block_number();
+        let receipt = <T as system::Trait>::Hashing::hash_of(&(id, from, to, now));
+        if !Self::is_feature_enabled(Feature::Receipts) {
+            return receipt;
+        }
+        <Receipts<T>>::insert(receipt, ReceiptInfo { battery_id: id, block: now });
+        <ReceiptsIssuedAt<T>>::mutate(now, |receipts| receipts.push(receipt));
+        receipt
+    }
+
+    /// The manufacturer covering battery `id` and the block its warranty ends at, while
+    /// its model's warranty period since registration hasn't run out.
+    pub fn warranty_of(id: T::Hash) -> Option<(T::AccountId, T::BlockNumber)> {
+        let model_id = Self::battery_model(id)?;
+        let model = Self::model(model_id)?;
+        let expires_at = Self::batteries(id).registered_at + Self::model_warranty(model_id);
+        if <system::Module<T>>::block_number() < expires_at {
+            Some((model.manufacturer, expires_at))
+        } else {
+            None
+        }
+    }
+
+    /// Issues `to` a certificate from the manufacturer if battery `id` is under warranty.
+    /// The previous owner's certificate was already dropped by `remove_owned`.
+    pub(super) fn issue_warranty_certificate(id: T::Hash, to: &T::AccountId) {
+        if let Some((manufacturer, expires_at)) = Self::warranty_of(id) {
+            <WarrantyCertificates<T>>::insert((id, to.clone()), WarrantyCertificate {
+                battery: id,
+                holder: to.clone(),
+                manufacturer,
+                issued_at: <system::Module<T>>::block_number(),
+                expires_at,
+            });
+            Self::deposit_event(RawEvent::WarrantyCertificateIssued(id, to.clone()));
+        }
+    }
+
+    /// The trade a receipt hash was issued for, unless it is older than `ReceiptRetentionBlocks`.
+    pub fn verify_receipt(receipt: T::Hash) -> Option<ReceiptInfo<T::Hash, T::BlockNumber>> {
+        Self::receipt(receipt)
+    }
+
+    pub(super) fn prune_receipts(n: T::BlockNumber) {
+        let retention = Self::receipt_retention_blocks();
+        if n < retention {
+            return;
+        }
+        for receipt in <ReceiptsIssuedAt<T>>::take(n - retention) {
+            <Receipts<T>>::remove(receipt);
+        }
+    }
+
+    pub(super) fn notify_transfer(id: T::Hash, from: &T::AccountId, to: &T::AccountId) {
+        Self::run_hooks(id, || T::OnBatteryTransfer::on_battery_transfer(&id, from, to));
+    }
+
+    pub(super) fn is_frozen_by_challenge(id: T::Hash) -> bool {
+        Self::challenge_of(id)
+            .and_then(Self::challenge)
+            .map_or(false, |challenge| <system::Module<T>>::block_number() < challenge.ends_at)
+    }
+
+    pub(super) fn is_owner_or_co_owner(battery: &Battery<T::AccountId, T::BlockNumber>, id: T::Hash, who: &T::AccountId) -> bool {
+        battery.owner == *who || Self::co_owners(id).contains(who)
+    }
+
+    pub(super) fn ensure_transferable(id: T::Hash) -> Result {
+        ensure!(!Self::is_non_transferable(id), "Battery is non-transferable");
+        Ok(())
+    }
+
+    pub(super) fn ensure_transfer_approved(id: T::Hash, to: &T::AccountId) -> Result {
+        Self::ensure_approved(id, Self::transfer_action_hash(id, to))
+    }
+
+    /// Checks enough co-owners of battery `id` approve `action_hash`. The threshold is
+    /// capped at the number of co-owners; batteries without co-owners need none.
+    pub(super) fn ensure_approved(id: T::Hash, action_hash: T::Hash) -> Result {
+        let co_owners = Self::co_owners(id);
+        if co_owners.is_empty() {
+            return Ok(());
+        }
+        let threshold = Self::co_owner_threshold(id).unwrap_or_else(Self::co_owner_approval_threshold);
+        let required = rstd::cmp::min(threshold as usize, co_owners.len());
+        let approved = Self::approvals(id).iter().filter(|(_, hash)| *hash == action_hash).count();
+        ensure!(approved >= required, "Not enough co-owner approvals");
+        Ok(())
+    }
+
+    /// Drops the approvals of an action that was carried out, so they can't be replayed.
+    pub(super) fn consume_approvals(id: T::Hash, action_hash: T::Hash) {
+        if <Approvals<T>>::exists(id) {
+            <Approvals<T>>::mutate(id, |approvals| approvals.retain(|(_, hash)| *hash != action_hash));
+        }
+    }
+
+    /// The action hash co-owners approve to let battery `id` be listed for trade.
+    pub fn listing_action_hash(id: T::Hash) -> T::Hash {
+        (&b"list"[..], id).using_encoded(<T as system::Trait>::Hashing::hash)
+    }
+
+    /// The action hash co-owners approve to let battery `id` be decommissioned for `reason`.
+    pub fn decommission_action_hash(id: T::Hash, reason: DecommissionReason) -> T::Hash {
+        (&b"decommission"[..], id, reason).using_encoded(<T as system::Trait>::Hashing::hash)
+    }
+
+    pub(super) fn clear_co_owners(id: T::Hash) {
+        <CoOwners<T>>::remove(id);
+        <Approvals<T>>::remove(id);
+        <CoOwnerThreshold<T>>::remove(id);
+    }
+}
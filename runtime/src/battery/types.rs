@@ -0,0 +1,614 @@
+//! Types stored and returned by the battery module.
+
+use rstd::prelude::*;
+use parity_codec::{Encode, Decode};
+
+/// Where a battery currently is. Being tradable only makes sense while a station
+/// holds the battery, so the flag is folded into the custody itself.
+#[derive(Encode, Decode, Clone, PartialEq)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub enum Custody<AccountId> {
+    /// With its owner, outside of any station.
+    Owner,
+    /// Stored in the given station.
+    Station(AccountId),
+    /// Stored in the given station and open for trade.
+    Tradable(AccountId),
+}
+
+impl<AccountId> Default for Custody<AccountId> {
+    fn default() -> Self {
+        Custody::Owner
+    }
+}
+
+/// A registered battery. The id is the key it is stored under, so it is not
+/// repeated in the value.
+#[derive(Encode, Decode, Default, Clone, PartialEq)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub struct Battery<AccountId, BlockNumber> {
+    pub(super) owner: AccountId,
+    pub(super) custody: Custody<AccountId>,
+    #[codec(compact)]
+    pub(super) registered_at: BlockNumber,
+}
+
+impl<AccountId, BlockNumber: Copy> Battery<AccountId, BlockNumber> {
+    pub fn owner(&self) -> &AccountId {
+        &self.owner
+    }
+
+    /// The station holding the battery, if any.
+    pub fn station(&self) -> Option<&AccountId> {
+        match self.custody {
+            Custody::Owner => None,
+            Custody::Station(ref station) | Custody::Tradable(ref station) => Some(station),
+        }
+    }
+
+    pub fn is_tradable(&self) -> bool {
+        match self.custody {
+            Custody::Tradable(_) => true,
+            _ => false,
+        }
+    }
+
+    /// Block in which the battery was registered. For batteries migrated from the
+    /// legacy layout this is the migration block, see `legacy_registry_time`.
+    pub fn registered_at(&self) -> BlockNumber {
+        self.registered_at
+    }
+}
+
+/// Battery layout up to storage version 0, kept for the migration only.
+#[derive(Encode, Decode)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub(super) struct LegacyBattery<Hash, Moment, AccountId> {
+    pub(super) id: Hash,
+    pub(super) owner: AccountId,
+    pub(super) station: Option<AccountId>,
+    pub(super) tradable: bool,
+    pub(super) registry_time: Moment,
+}
+
+/// Custody follows from the legacy station and tradable flag. The legacy registration
+/// time is a moment rather than a block, so `registered_at` is left for the migration
+/// to fill in.
+impl<Hash, Moment, AccountId, BlockNumber: Default> From<LegacyBattery<Hash, Moment, AccountId>>
+    for Battery<AccountId, BlockNumber>
+{
+    fn from(old: LegacyBattery<Hash, Moment, AccountId>) -> Self {
+        let custody = match old.station {
+            Some(station) if old.tradable => Custody::Tradable(station),
+            Some(station) => Custody::Station(station),
+            None => Custody::Owner,
+        };
+        Battery { owner: old.owner, custody, registered_at: Default::default() }
+    }
+}
+
+/// A battery model published by its manufacturer.
+#[derive(Encode, Decode, Default, Clone, PartialEq)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub struct BatteryModel<AccountId> {
+    pub manufacturer: AccountId,
+    pub name: Vec<u8>,
+}
+
+/// Proof that `holder` is covered by the manufacturer's warranty on `battery` until
+/// `expires_at`. Issued when a battery under warranty is traded.
+#[derive(Encode, Decode, Default, Clone, PartialEq)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub struct WarrantyCertificate<Hash, AccountId, BlockNumber> {
+    pub battery: Hash,
+    pub holder: AccountId,
+    pub manufacturer: AccountId,
+    pub issued_at: BlockNumber,
+    pub expires_at: BlockNumber,
+}
+
+/// Funds a buyer locked up for a battery of a model that isn't produced yet.
+#[derive(Encode, Decode, Default, Clone, PartialEq)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub struct PreOrder<AccountId, Hash, Balance, BlockNumber> {
+    pub buyer: AccountId,
+    pub model_id: Hash,
+    pub locked_funds: Balance,
+    /// From this block on the buyer may cancel and get the funds back.
+    pub unlock_at: BlockNumber,
+}
+
+/// Funds a manufacturer locked up for the owner of a battery it recalled.
+#[derive(Encode, Decode, Default, Clone, PartialEq)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub struct RecallCompensation<AccountId, Balance> {
+    pub manufacturer: AccountId,
+    pub amount: Balance,
+}
+
+/// Extrinsics kept only for clients that still encode them, reported by
+/// `DeprecatedCallUsed` so we can tell when they are safe to remove.
+#[derive(Encode, Decode, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub enum DeprecatedCall {
+    /// `registry_battery`, replaced by `register_battery`.
+    RegistryBattery,
+    /// `switch_tradable`, replaced by `set_tradable`.
+    SwitchTradable,
+}
+
+/// How much the network trusts a station, granted by root.
+#[derive(Encode, Decode, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub enum TrustLevel {
+    Basic,
+    Verified,
+    /// Allowed to refurbish batteries.
+    Premium,
+}
+
+impl Default for TrustLevel {
+    fn default() -> Self {
+        TrustLevel::Basic
+    }
+}
+
+/// Newly registered stations start on probation and graduate once they completed
+/// `ProbationTradeThreshold` trades.
+#[derive(Encode, Decode, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub enum StationStatus {
+    Probation { trades_completed: u32 },
+    Full,
+}
+
+/// Stations registered before probation existed have no status stored and count as full.
+impl Default for StationStatus {
+    fn default() -> Self {
+        StationStatus::Full
+    }
+}
+
+/// Most batteries a station on probation may hold.
+pub const PROBATION_INVENTORY_CAP: u64 = 10;
+
+/// What a station is reported for.
+#[derive(Encode, Decode, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub enum ReportCategory {
+    RefusedHandover,
+    FalsifiedChargeData,
+}
+
+/// A misconduct report awaiting a ruling. The evidence itself is kept off chain.
+#[derive(Encode, Decode, Clone, PartialEq)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub struct StationReport<AccountId, Hash, Balance> {
+    pub reporter: AccountId,
+    pub category: ReportCategory,
+    pub evidence_hash: Hash,
+    pub stake: Balance,
+}
+
+/// Most reports one account may file per report era.
+pub const MAX_REPORTS_PER_ERA: u32 = 3;
+/// Most reports against one station that may await a ruling.
+pub const MAX_PENDING_REPORTS: usize = 50;
+
+/// A compliance check a station was randomly selected for. The station must
+/// answer it with its batteries' health by the end of block `deadline`.
+#[derive(Encode, Decode, Clone, PartialEq)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub struct AuditSchedule<AccountId, BlockNumber> {
+    pub station: AccountId,
+    pub scheduled_at: BlockNumber,
+    pub deadline: BlockNumber,
+}
+
+/// Most battery health reports one audit answer may carry.
+pub const MAX_AUDIT_REPORTS: usize = 64;
+
+/// Condition class of a battery, from the health its station last reported in an
+/// audit. Ordered worst to best; batteries nobody reported on are `C`.
+#[derive(Encode, Decode, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub enum BatteryGrade {
+    C,
+    B,
+    A,
+}
+
+impl BatteryGrade {
+    pub fn from_health(health: u8) -> Self {
+        match health {
+            90..=100 => BatteryGrade::A,
+            70..=89 => BatteryGrade::B,
+            _ => BatteryGrade::C,
+        }
+    }
+}
+
+/// Batteries a station offered to move from its own pool to another station.
+/// The destination can accept it until block `expires_at`, inclusive.
+#[derive(Encode, Decode, Clone, PartialEq)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub struct RebalanceProposal<AccountId, Hash, BlockNumber> {
+    pub from: AccountId,
+    pub to: AccountId,
+    pub ids: Vec<Hash>,
+    pub expires_at: BlockNumber,
+}
+
+/// Most batteries one rebalance proposal may move.
+pub const MAX_REBALANCE_BATTERIES: usize = 32;
+
+/// What happened to or at a station, as kept in its audit log.
+#[derive(Encode, Decode, Clone, PartialEq)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub enum StationAction<Hash> {
+    Registered,
+    BatteryRegistered(Hash),
+    BatteryStored(Hash),
+    BatteryFetched(Hash),
+    BatteryTraded(Hash),
+    /// The battery left the station without being fetched, e.g. for the bridge or
+    /// because it was decommissioned.
+    BatteryRemoved(Hash),
+    TradableSet(Hash, bool),
+    TargetSocSet(Hash, u8),
+    ModelUpgraded(Hash),
+    LocationSet,
+    OpeningHoursSet,
+    /// Oldest battery age in days the station accepts.
+    AgePolicySet(u64),
+    SwapCapacitySet(u32),
+    SessionKeySet,
+    TrustLevelSet(TrustLevel),
+    Graduated,
+    PlacedUnderReview,
+    /// Whether the reports were upheld.
+    ReportsResolved(bool),
+    AuditSubmitted,
+    AuditMissed,
+}
+
+/// A trade receipt as the chain remembers it, see `Module::verify_receipt`.
+#[derive(Encode, Decode, Clone, PartialEq)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub struct ReceiptInfo<Hash, BlockNumber> {
+    pub battery_id: Hash,
+    pub block: BlockNumber,
+}
+
+/// One entry of a station's audit log. `actor` is the account that signed the
+/// extrinsic, or `None` for root and automatic changes. The block number and
+/// `extrinsic_index` together identify the extrinsic.
+#[derive(Encode, Decode, Clone, PartialEq)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub struct StationAuditEntry<AccountId, BlockNumber, Hash> {
+    pub actor: Option<AccountId>,
+    pub at: BlockNumber,
+    pub extrinsic_index: u32,
+    pub action: StationAction<Hash>,
+}
+
+/// Work a station carried out on a battery.
+#[derive(Encode, Decode, Clone, PartialEq)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub enum MaintenanceKind<Hash> {
+    /// The cell pack was replaced, changing the model from the first to the second.
+    ModelUpgrade(Option<Hash>, Hash),
+}
+
+#[derive(Encode, Decode, Clone, PartialEq)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub struct MaintenanceRecord<AccountId, Hash, BlockNumber> {
+    pub station: AccountId,
+    pub at: BlockNumber,
+    pub kind: MaintenanceKind<Hash>,
+}
+
+/// What an anchored document shows.
+#[derive(Encode, Decode, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub enum DocKind {
+    /// Photos of the battery's condition, usually taken at a handover.
+    ConditionPhoto,
+    InspectionReport,
+    Other,
+}
+
+/// Content hash of a document kept off chain, and who anchored it when.
+#[derive(Encode, Decode, Clone, PartialEq)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub struct DocumentAnchor<Hash, AccountId, BlockNumber> {
+    pub doc_hash: Hash,
+    pub kind: DocKind,
+    pub anchored_by: AccountId,
+    pub at: BlockNumber,
+}
+
+/// Most documents anchored for a battery while it stays with the same owner and station.
+pub const MAX_DOCUMENTS_PER_CUSTODY: u32 = 4;
+/// Most documents anchored for a battery over its lifetime.
+pub const MAX_DOCUMENTS_PER_BATTERY: u32 = 64;
+/// Most documents cited as evidence in one challenge.
+pub const MAX_CHALLENGE_EVIDENCE: usize = 8;
+
+/// Why a battery was taken out of service.
+#[derive(Encode, Decode, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub enum DecommissionReason {
+    EndOfLife,
+    Damaged,
+    Recycled,
+    Lost,
+    /// Registered twice by mistake. Only root removes duplicates, and no tombstone is kept.
+    Duplicate,
+}
+
+/// The full public record of a battery. Its hash is kept in `PassportHash` so it can
+/// be quoted off chain and checked against a recomputation.
+#[derive(Encode, Decode, Clone, PartialEq)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub struct BatteryPassport<Hash, AccountId, BlockNumber> {
+    pub id: Hash,
+    pub owner: AccountId,
+    pub custody: Custody<AccountId>,
+    pub registered_at: BlockNumber,
+    pub model: Option<Hash>,
+    pub non_transferable: bool,
+    pub maintenance: Vec<MaintenanceRecord<AccountId, Hash, BlockNumber>>,
+}
+
+/// A claim that a battery was stolen from `challenger`.
+#[derive(Encode, Decode, Clone, PartialEq)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub struct OwnershipChallenge<Hash, AccountId, BlockNumber> {
+    pub battery_id: Hash,
+    pub challenger: AccountId,
+    /// Trading and storing the battery are frozen until this block.
+    pub ends_at: BlockNumber,
+}
+
+/// A battery locked here while a wrapped token represents it on another chain.
+#[derive(Encode, Decode, Clone, PartialEq)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub struct BridgeRequest<Hash, AccountId, ChainId, Address> {
+    pub battery_id: Hash,
+    pub locker: AccountId,
+    pub dest_chain_id: ChainId,
+    pub dest_address: Address,
+    /// Hash of the minting transaction on the destination chain, once a relayer confirmed it.
+    pub confirmation: Option<Address>,
+}
+
+/// Longest destination address accepted by `lock_for_bridge`.
+pub const MAX_BRIDGE_ADDRESS_LEN: usize = 64;
+
+/// Most stations a battery can have open store requests at.
+pub const MAX_PENDING_STORES: usize = 4;
+
+/// Most co-owners a battery can have besides its owner.
+pub const MAX_CO_OWNERS: usize = 4;
+
+/// Most opening windows a station can declare per day.
+pub const MAX_OPENING_WINDOWS_PER_DAY: usize = 4;
+
+pub(super) const MINUTES_PER_DAY: u32 = 24 * 60;
+
+/// Longest model name accepted by `register_model`.
+pub const MAX_MODEL_NAME_LEN: usize = 64;
+
+/// Levels of the ownership tree below its root, enough for 2^32 batteries.
+pub const OWNERSHIP_TREE_DEPTH: u32 = 32;
+
+/// Current layout version of `Batteries`.
+pub const STORAGE_VERSION: u32 = 1;
+
+/// Batteries converted to the compact layout per block while the migration runs.
+pub const MIGRATION_BATCH_SIZE: u64 = 50;
+
+/// Most blocks ahead of the current one a signed inquiry may be valid until, so a
+/// leaked signature stops revealing the owner soon after it was made.
+pub const MAX_INQUIRY_VALIDITY_BLOCKS: u64 = 60;
+
+/// What any account may learn about a battery sitting in a station. The owner
+/// is only filled in when the viewer has proven to be that owner.
+#[derive(Encode, Decode, Default, Clone, PartialEq)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub struct BatteryPublicInfo<Hash, BlockNumber, AccountId> {
+    pub id: Hash,
+    pub station: AccountId,
+    pub tradable: bool,
+    pub registered_at: BlockNumber,
+    pub owner: Option<AccountId>,
+}
+
+/// Everything an explorer shows on an account page. Fields for features the chain
+/// doesn't have yet stay zero.
+#[derive(Encode, Decode, Default, Clone, PartialEq)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub struct AccountOverview<Balance> {
+    /// Batteries the account owns.
+    pub owned: u64,
+    /// Owned batteries currently stored in a station.
+    pub in_stations: u64,
+    /// Owned batteries open for trade.
+    pub listed: u64,
+    /// Offers to buy batteries the account has outstanding.
+    pub offers_made: u64,
+    pub loyalty_points: u64,
+    /// Batteries the account holds as a station.
+    pub held_as_station: u64,
+    /// What the account earned as a station.
+    pub station_earnings: Balance,
+}
+
+/// One of an account's batteries in an `AccountExport`.
+#[derive(Encode, Decode, Clone, PartialEq)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub struct BatteryExport<Hash, AccountId, BlockNumber, Balance> {
+    pub passport: BatteryPassport<Hash, AccountId, BlockNumber>,
+    pub co_owners: Vec<AccountId>,
+    pub co_owner_threshold: Option<u32>,
+    pub auto_relist: bool,
+    /// Unclaimed compensation for a recall of the battery.
+    pub recall_compensation: Option<Balance>,
+    pub documents: Vec<DocumentAnchor<Hash, AccountId, BlockNumber>>,
+    /// The owner's warranty on the battery, if it came with one.
+    pub warranty: Option<WarrantyCertificate<Hash, AccountId, BlockNumber>>,
+    /// Stations asked to take the battery in, with the block each request lapses at.
+    pub pending_stores: Vec<(AccountId, BlockNumber)>,
+}
+
+/// A page of everything the module stores about an account, for moving to another
+/// wallet. Pass `next_cursor` back to `export_account_data` to get the next page.
+#[derive(Encode, Decode, Clone, PartialEq)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub struct AccountExport<Hash, AccountId, BlockNumber, Balance> {
+    pub batteries: Vec<BatteryExport<Hash, AccountId, BlockNumber, Balance>>,
+    pub next_cursor: Option<u64>,
+    /// The account's entry in every other per-account storage, as the storage name and
+    /// its SCALE-encoded value. Only the first page carries them.
+    pub records: Vec<(Vec<u8>, Vec<u8>)>,
+}
+
+/// Whether each `Feature` is on while governance hasn't set its flag. The ones
+/// that predate the flags stay on so existing deployments keep working; the
+/// ones added since ship switched off until governance turns them on.
+pub const DEFAULT_PRE_ORDERS_ENABLED: bool = true;
+pub const DEFAULT_BRIDGE_ENABLED: bool = true;
+pub const DEFAULT_CHARGING_ENABLED: bool = true;
+pub const DEFAULT_RECALLS_ENABLED: bool = true;
+pub const DEFAULT_ESCHEAT_ENABLED: bool = true;
+pub const DEFAULT_STATION_REPORTS_ENABLED: bool = false;
+pub const DEFAULT_DOCUMENTS_ENABLED: bool = false;
+pub const DEFAULT_RECEIPTS_ENABLED: bool = false;
+pub const DEFAULT_IDENTITY_CLAIMS_ENABLED: bool = false;
+pub const DEFAULT_REBALANCING_ENABLED: bool = false;
+pub const DEFAULT_AUDITS_ENABLED: bool = false;
+pub const DEFAULT_STORE_REQUESTS_ENABLED: bool = false;
+pub const DEFAULT_NOTIFICATIONS_ENABLED: bool = false;
+pub const DEFAULT_WARRANTIES_ENABLED: bool = false;
+pub const DEFAULT_STATION_AGE_POLICIES_ENABLED: bool = false;
+pub const DEFAULT_PROMOTION_VOTES_ENABLED: bool = false;
+
+/// Optional parts of the module that governance can switch off. Only their entry
+/// points are gated, so users can still leave a switched-off feature: pre-orders
+/// can be cancelled, bridged batteries unlocked, compensations claimed and
+/// reports ruled on. Registering, storing, fetching and trading batteries can't
+/// be switched off. New variants go at the end so stored flags keep their meaning.
+#[derive(Encode, Decode, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub enum Feature {
+    PreOrders,
+    Bridge,
+    Charging,
+    Recalls,
+    Escheat,
+    StationReports,
+    Documents,
+    Receipts,
+    IdentityClaims,
+    Rebalancing,
+    Audits,
+    StoreRequests,
+    Notifications,
+    Warranties,
+    StationAgePolicies,
+    PromotionVotes,
+}
+
+impl Feature {
+    /// Whether the feature is on while governance hasn't set its flag.
+    pub fn enabled_by_default(self) -> bool {
+        match self {
+            Feature::PreOrders => DEFAULT_PRE_ORDERS_ENABLED,
+            Feature::Bridge => DEFAULT_BRIDGE_ENABLED,
+            Feature::Charging => DEFAULT_CHARGING_ENABLED,
+            Feature::Recalls => DEFAULT_RECALLS_ENABLED,
+            Feature::Escheat => DEFAULT_ESCHEAT_ENABLED,
+            Feature::StationReports => DEFAULT_STATION_REPORTS_ENABLED,
+            Feature::Documents => DEFAULT_DOCUMENTS_ENABLED,
+            Feature::Receipts => DEFAULT_RECEIPTS_ENABLED,
+            Feature::IdentityClaims => DEFAULT_IDENTITY_CLAIMS_ENABLED,
+            Feature::Rebalancing => DEFAULT_REBALANCING_ENABLED,
+            Feature::Audits => DEFAULT_AUDITS_ENABLED,
+            Feature::StoreRequests => DEFAULT_STORE_REQUESTS_ENABLED,
+            Feature::Notifications => DEFAULT_NOTIFICATIONS_ENABLED,
+            Feature::Warranties => DEFAULT_WARRANTIES_ENABLED,
+            Feature::StationAgePolicies => DEFAULT_STATION_AGE_POLICIES_ENABLED,
+            Feature::PromotionVotes => DEFAULT_PROMOTION_VOTES_ENABLED,
+        }
+    }
+}
+
+/// Alerts an account opted in to. Off-chain notifiers check these before alerting the
+/// account. Health alerts are for a feature the chain doesn't have yet.
+#[derive(Encode, Decode, Default, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub struct NotificationPrefs {
+    pub notify_on_health_drop: bool,
+    pub notify_on_warranty_expiry: bool,
+    pub notify_on_trade: bool,
+    pub notify_on_recall: bool,
+}
+
+/// Chosen by a kiosk per submitted call so retries of it can be told apart from new calls.
+pub type IdempotencyKey = [u8; 16];
+
+/// Most batteries one `complete_escheat` call hands over.
+pub const MAX_ESCHEAT_CHUNK: u32 = 20;
+
+/// Most batteries one page of `export_account_data` holds.
+pub const MAX_EXPORT_PAGE: u64 = 50;
+
+/// Stations this close to a candidate count towards the demand `recommend_store_station`
+/// expects there.
+pub const DEMAND_RADIUS_METERS: u64 = 25_000;
+
+/// Score `recommend_store_station` takes off a candidate for each swap this hour near it
+/// that no tradable battery nearby covers, up to the swaps the candidate has left.
+pub const UNMET_DEMAND_WEIGHT: u64 = 10;
+
+/// Network-wide totals for dashboards, kept up to date by the calls that change them.
+#[derive(Encode, Decode, Default, Clone, PartialEq)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub struct NetworkStats {
+    pub stations: u64,
+    /// Live batteries, i.e. not decommissioned.
+    pub batteries: u64,
+    /// Batteries stored in a station, listed or not.
+    pub in_custody: u64,
+    pub listed: u64,
+    /// Decommissioned batteries that left a tombstone.
+    pub decommissioned: u64,
+    pub trades: u64,
+}
+
+impl NetworkStats {
+    pub(super) fn add_battery<AccountId>(&mut self, custody: &Custody<AccountId>) {
+        self.batteries += 1;
+        match custody {
+            Custody::Owner => (),
+            Custody::Station(_) => self.in_custody += 1,
+            Custody::Tradable(_) => {
+                self.in_custody += 1;
+                self.listed += 1;
+            }
+        }
+    }
+
+    pub(super) fn remove_battery<AccountId>(&mut self, custody: &Custody<AccountId>) {
+        self.batteries = self.batteries.saturating_sub(1);
+        match custody {
+            Custody::Owner => (),
+            Custody::Station(_) => self.in_custody = self.in_custody.saturating_sub(1),
+            Custody::Tradable(_) => {
+                self.in_custody = self.in_custody.saturating_sub(1);
+                self.listed = self.listed.saturating_sub(1);
+            }
+        }
+    }
+}
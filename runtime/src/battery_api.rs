@@ -0,0 +1,84 @@
+//! Runtime API exposing read-only battery queries to the node and its clients.
+
+use client::decl_runtime_apis;
+use rstd::prelude::*;
+use crate::{AccountId, AccountSignature, Balance, BlockNumber, Hash};
+
+pub use crate::battery::{AccountExport, AccountOverview, BatteryGrade, BatteryPassport, BatteryPublicInfo, NetworkStats, NotificationPrefs, ReceiptInfo, StationAuditEntry};
+
+decl_runtime_apis! {
+	/// Queries into the battery module, used by station kiosks and explorers.
+	pub trait BatteryApi {
+		/// Public information about battery `id` held by `station`, without the owner.
+		fn cross_station_battery_inquiry(station: AccountId, id: Hash) -> Option<BatteryPublicInfo<Hash, BlockNumber, AccountId>>;
+		/// Same as `cross_station_battery_inquiry`, but the owner is revealed when `signature`
+		/// is `viewer`'s signature over the encoded `(station, id, valid_until)` and `viewer`
+		/// owns the battery. Signatures are only honoured up to block `valid_until`, which may
+		/// be at most `MAX_INQUIRY_VALIDITY_BLOCKS` ahead.
+		fn cross_station_battery_inquiry_signed(
+			station: AccountId,
+			id: Hash,
+			valid_until: BlockNumber,
+			viewer: AccountId,
+			signature: AccountSignature,
+		) -> Option<BatteryPublicInfo<Hash, BlockNumber, AccountId>>;
+		/// Up to `max_results` stations holding a tradable battery of `min_grade` or better
+		/// charged to at least `min_soc` percent and not running at their declared swap
+		/// capacity, as `(station, distance_meters)` from the given point in microdegrees,
+		/// nearest first.
+		fn find_stations_with_tradable_batteries(
+			min_grade: BatteryGrade,
+			min_soc: u8,
+			user_lat: i64,
+			user_lon: i64,
+			max_results: u8,
+		) -> Vec<(AccountId, u64)>;
+		/// Battery counts for an account page, covering both its owner and station roles.
+		fn account_overview(account: AccountId) -> AccountOverview<Balance>;
+		/// Which of `candidate_stations` a fleet operator should send battery `id` to for its
+		/// next swap, weighing distance, spare capacity, utilization and the demand nearby.
+		fn recommend_store_station(id: Hash, candidate_stations: Vec<AccountId>) -> Option<AccountId>;
+		/// Every battery of `model_id` owned by `owner`, e.g. a customer's LiFePO4 packs.
+		fn get_batteries_by_owner_and_model(owner: AccountId, model_id: Hash) -> Vec<Hash>;
+		/// A page of `owner`'s batteries, oldest acquisition first, so lists don't reshuffle.
+		fn owned_batteries_sorted(owner: AccountId, start: u64, limit: u64) -> Vec<Hash>;
+		/// Proof that battery `id` is included in the battery Merkle root.
+		fn battery_inclusion_proof(id: Hash) -> Option<Vec<Hash>>;
+		/// Whether `proof` shows battery `id` is included in the battery Merkle root.
+		fn verify_battery_inclusion(id: Hash, proof: Vec<Hash>) -> bool;
+		/// Alerts `account` opted in to, for notifiers to check before alerting it.
+		fn notification_prefs(account: AccountId) -> NotificationPrefs;
+		/// Network-wide totals for governance dashboards.
+		fn network_stats() -> NetworkStats;
+		/// Swaps per hour all stations together declared they can handle.
+		fn network_swap_capacity() -> u32;
+		/// Whether `store_to_station` signed by `who` would succeed, or the error it would fail with.
+		fn can_store(who: AccountId, id: Hash) -> Result<(), Vec<u8>>;
+		/// Whether `fetch_from_station` signed by `who` would succeed, or the error it would fail with.
+		fn can_fetch(who: AccountId, id: Hash) -> Result<(), Vec<u8>>;
+		/// Whether `trade_battery` signed by `station` would succeed, or the error it would fail with.
+		fn can_trade(station: AccountId, id: Hash, to: AccountId) -> Result<(), Vec<u8>>;
+		/// Hash of battery `id`'s passport, for quoting in off-chain documents.
+		fn battery_passport_hash(id: Hash) -> Hash;
+		/// The passport itself, to recompute and check `battery_passport_hash` against.
+		fn battery_passport(id: Hash) -> Option<BatteryPassport<Hash, AccountId, BlockNumber>>;
+		/// Bumped by every call that changes battery `id`. Embed it in off-chain signed
+		/// payloads about the battery, so they go stale once the battery changes.
+		fn battery_nonce(id: Hash) -> u64;
+		/// State trie key battery `id` is stored under. Light clients ask a full node for a
+		/// read proof of this key and check it against a finalized state root, rather than
+		/// trusting `battery_passport`.
+		fn battery_storage_key(id: Hash) -> Vec<u8>;
+		/// Merkle path of battery `id` in the ownership tree taken at `checkpoint`, if `owner`
+		/// owned it then.
+		fn ownership_proof(checkpoint: u64, id: Hash, owner: AccountId) -> Option<Vec<Hash>>;
+		/// Whether `proof` shows `owner` owned battery `id` at ownership checkpoint `checkpoint`.
+		fn verify_ownership(checkpoint: u64, id: Hash, owner: AccountId, proof: Vec<Hash>) -> bool;
+		/// A page of everything stored about `account`, starting at battery `cursor`.
+		fn export_account_data(account: AccountId, cursor: u64, limit: u64) -> AccountExport<Hash, AccountId, BlockNumber, Balance>;
+		/// Up to `limit` entries of `station`'s audit log, oldest first, starting at entry `offset`.
+		fn get_station_audit_log(station: AccountId, offset: u64, limit: u64) -> Vec<StationAuditEntry<AccountId, BlockNumber, Hash>>;
+		/// The trade a printed receipt hash was issued for, if it is recent enough to be remembered.
+		fn verify_receipt(receipt: Hash) -> Option<ReceiptInfo<Hash, BlockNumber>>;
+	}
+}
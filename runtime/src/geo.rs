@@ -0,0 +1,118 @@
+//! Integer-only geodesy helpers. Coordinates are given in microdegrees, since the
+//! runtime has no floating point maths available when compiled to Wasm.
+
+/// Fixed-point scale used for all intermediate values.
+const SCALE: i128 = 1_000_000_000_000;
+/// π scaled by `SCALE`.
+const PI: i128 = 3_141_592_653_590;
+/// Mean earth radius in meters.
+const EARTH_RADIUS_METERS: i128 = 6_371_000;
+const MICRODEGREES_PER_HALF_TURN: i128 = 180_000_000;
+
+fn mul(a: i128, b: i128) -> i128 {
+	a * b / SCALE
+}
+
+fn to_radians(microdegrees: i128) -> i128 {
+	microdegrees * PI / MICRODEGREES_PER_HALF_TURN
+}
+
+/// Taylor series, accurate for `|x| <= π/2`.
+fn sin(x: i128) -> i128 {
+	let x2 = mul(x, x);
+	let mut term = x;
+	let mut sum = x;
+	for n in 1..8 {
+		term = -mul(term, x2) / ((2 * n) * (2 * n + 1));
+		sum += term;
+	}
+	sum
+}
+
+/// Taylor series, accurate for `|x| <= π/2`.
+fn cos(x: i128) -> i128 {
+	let x2 = mul(x, x);
+	let mut term = SCALE;
+	let mut sum = SCALE;
+	for n in 1..8 {
+		term = -mul(term, x2) / ((2 * n - 1) * (2 * n));
+		sum += term;
+	}
+	sum
+}
+
+fn sqrt(x: i128) -> i128 {
+	if x <= 0 {
+		return 0;
+	}
+	let square = x * SCALE;
+	let mut root = square;
+	let mut next = (root + 1) / 2;
+	while next < root {
+		root = next;
+		next = (root + square / root) / 2;
+	}
+	root
+}
+
+/// Taylor series, only used for `0 <= x <= 1/2` where it converges quickly.
+fn asin_small(x: i128) -> i128 {
+	let x2 = mul(x, x);
+	let mut power = x;
+	let mut sum = x;
+	for n in 1..20 {
+		power = mul(power, x2) * (2 * n - 1) / (2 * n);
+		sum += power / (2 * n + 1);
+	}
+	sum
+}
+
+/// Arcsine for `0 <= x <= 1`, using `asin(x) = π/2 - 2 asin(sqrt((1 - x) / 2))`
+/// to stay in the fast converging range.
+fn asin(x: i128) -> i128 {
+	if x <= SCALE / 2 {
+		asin_small(x)
+	} else {
+		PI / 2 - 2 * asin_small(sqrt((SCALE - x) / 2))
+	}
+}
+
+/// Great-circle distance in meters between two points, using the haversine formula.
+pub fn distance_meters(lat1: i64, lon1: i64, lat2: i64, lon2: i64) -> u64 {
+	let mut delta_lon = (lon2 as i128 - lon1 as i128) % (2 * MICRODEGREES_PER_HALF_TURN);
+	if delta_lon > MICRODEGREES_PER_HALF_TURN {
+		delta_lon -= 2 * MICRODEGREES_PER_HALF_TURN;
+	} else if delta_lon < -MICRODEGREES_PER_HALF_TURN {
+		delta_lon += 2 * MICRODEGREES_PER_HALF_TURN;
+	}
+	let phi1 = to_radians(lat1 as i128);
+	let phi2 = to_radians(lat2 as i128);
+
+	let sin_half_lat = sin((phi2 - phi1) / 2);
+	let sin_half_lon = sin(to_radians(delta_lon) / 2);
+	let a = mul(sin_half_lat, sin_half_lat) + mul(mul(cos(phi1), cos(phi2)), mul(sin_half_lon, sin_half_lon));
+	let a = if a < 0 { 0 } else if a > SCALE { SCALE } else { a };
+
+	let central_angle = 2 * asin(sqrt(a));
+	(EARTH_RADIUS_METERS * central_angle / SCALE) as u64
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn same_point_is_zero_meters() {
+		assert_eq!(distance_meters(48_856_600, 2_352_200, 48_856_600, 2_352_200), 0);
+	}
+
+	#[test]
+	fn matches_known_distances() {
+		// Paris to London.
+		assert_eq!(distance_meters(48_856_600, 2_352_200, 51_507_400, -127_800) / 1000, 343);
+		// A quarter of the equator.
+		assert_eq!(distance_meters(0, 0, 0, 90_000_000), 10_007_543);
+		// Across the antimeridian is the short way around.
+		assert_eq!(distance_meters(0, 179_000_000, 0, -179_000_000), 222_389);
+	}
+}
@@ -55,11 +55,23 @@ pub type BlockNumber = u64;
 /// Index of an account's extrinsic in the chain.
 pub type Nonce = u64;
 
+/// A timestamp: seconds since the unix epoch.
+pub type Moment = u64;
+
+/// Balance of an account.
+pub type Balance = u128;
+
 /// Used for the module template in `./template.rs`
 mod template;
 
 mod battery;
 
+/// Fixed-point distance maths used by the battery module.
+mod geo;
+
+/// Runtime API for querying the battery module.
+pub mod battery_api;
+
 /// Opaque types. These are used by the CLI to instantiate machinery that don't need to know
 /// the specifics of the runtime. They can then be made to be agnostic over specific formats
 /// of data like extrinsics, allowing for them to continue syncing the network through upgrades
@@ -97,8 +109,8 @@ pub const VERSION: RuntimeVersion = RuntimeVersion {
 	spec_name: create_runtime_str!("fuelow"),
 	impl_name: create_runtime_str!("fuelow"),
 	authoring_version: 3,
-	spec_version: 4,
-	impl_version: 4,
+	spec_version: 5,
+	impl_version: 5,
 	apis: RUNTIME_API_VERSIONS,
 };
 
@@ -164,13 +176,13 @@ impl indices::Trait for Runtime {
 
 impl timestamp::Trait for Runtime {
 	/// A timestamp: seconds since the unix epoch.
-	type Moment = u64;
+	type Moment = Moment;
 	type OnTimestampSet = Aura;
 }
 
 impl balances::Trait for Runtime {
 	/// The type for recording an account's balance.
-	type Balance = u128;
+	type Balance = Balance;
 	/// What to do if an account's free balance gets zeroed.
 	type OnFreeBalanceZero = ();
 	/// What to do if a new account is created.
@@ -196,6 +208,26 @@ impl template::Trait for Runtime {
 
 impl battery::Trait for Runtime {
 	type Event = Event;
+	type Currency = Balances;
+	type GovCurrency = Balances;
+	type AccountStatus = ();
+	type ArbitratorOrigin = system::EnsureRoot<AccountId>;
+	type BridgeOrigin = system::EnsureRoot<AccountId>;
+	type AdminOrigin = system::EnsureRoot<AccountId>;
+	type OnDecommission = ();
+	type OnBatteryTransfer = ();
+	type IdGenerator = ();
+	type AuditSubmitter = UnsignedAudits;
+}
+
+/// Hands the off-chain worker's `schedule_audit` calls to the transaction pool, where
+/// `BatteryModule::validate_unsigned` checks them.
+pub struct UnsignedAudits;
+impl battery::SubmitAudit<AccountId> for UnsignedAudits {
+	fn submit_schedule_audit(station: AccountId) {
+		let call = Call::BatteryModule(battery::Call::schedule_audit(station));
+		runtime_io::submit_extrinsic(&UncheckedExtrinsic::new_unsigned(call));
+	}
 }
 
 construct_runtime!(
@@ -213,7 +245,7 @@ construct_runtime!(
 		Sudo: sudo,
 		// Used for the module template in `./template.rs`
 		TemplateModule: template::{Module, Call, Storage, Event<T>},
-		BatteryModule: battery::{Module, Call, Storage, Event<T>},
+		BatteryModule: battery::{Module, Call, Storage, Event<T>, Config<T>},
 	}
 );
 
@@ -284,6 +316,11 @@ impl_runtime_apis! {
 
 	impl runtime_api::TaggedTransactionQueue<Block> for Runtime {
 		fn validate_transaction(tx: <Block as BlockT>::Extrinsic) -> TransactionValidity {
+			if tx.signature.is_none() {
+				if let Call::BatteryModule(ref call) = tx.function {
+					return BatteryModule::validate_unsigned(call);
+				}
+			}
 			Executive::validate_transaction(tx)
 		}
 	}
@@ -305,4 +342,124 @@ impl_runtime_apis! {
 			Consensus::authorities()
 		}
 	}
+
+	impl battery_api::BatteryApi<Block> for Runtime {
+		fn cross_station_battery_inquiry(station: AccountId, id: Hash) -> Option<battery::BatteryPublicInfo<Hash, BlockNumber, AccountId>> {
+			BatteryModule::cross_station_battery_inquiry(&station, id, None)
+		}
+
+		fn cross_station_battery_inquiry_signed(
+			station: AccountId,
+			id: Hash,
+			valid_until: BlockNumber,
+			viewer: AccountId,
+			signature: AccountSignature,
+		) -> Option<battery::BatteryPublicInfo<Hash, BlockNumber, AccountId>> {
+			let verified = BatteryModule::is_inquiry_signature_current(valid_until)
+				&& (&station, &id, valid_until).using_encoded(|payload| signature.verify(payload, &viewer));
+			BatteryModule::cross_station_battery_inquiry(&station, id, if verified { Some(&viewer) } else { None })
+		}
+
+		fn find_stations_with_tradable_batteries(
+			min_grade: battery::BatteryGrade,
+			min_soc: u8,
+			user_lat: i64,
+			user_lon: i64,
+			max_results: u8,
+		) -> Vec<(AccountId, u64)> {
+			BatteryModule::find_stations_with_tradable_batteries(min_grade, min_soc, user_lat, user_lon, max_results)
+		}
+
+		fn account_overview(account: AccountId) -> battery::AccountOverview<Balance> {
+			BatteryModule::account_overview(&account)
+		}
+
+		fn recommend_store_station(id: Hash, candidate_stations: Vec<AccountId>) -> Option<AccountId> {
+			BatteryModule::recommend_store_station(id, candidate_stations)
+		}
+
+		fn get_batteries_by_owner_and_model(owner: AccountId, model_id: Hash) -> Vec<Hash> {
+			BatteryModule::batteries_by_owner_and_model(&owner, model_id)
+		}
+
+		fn owned_batteries_sorted(owner: AccountId, start: u64, limit: u64) -> Vec<Hash> {
+			BatteryModule::owned_batteries_sorted(&owner, start, limit)
+		}
+
+		fn battery_inclusion_proof(id: Hash) -> Option<Vec<Hash>> {
+			BatteryModule::battery_inclusion_proof(id)
+		}
+
+		fn verify_battery_inclusion(id: Hash, proof: Vec<Hash>) -> bool {
+			BatteryModule::verify_battery_inclusion(id, proof)
+		}
+
+		fn notification_prefs(account: AccountId) -> battery::NotificationPrefs {
+			BatteryModule::notification_prefs(account)
+		}
+
+		fn network_stats() -> battery::NetworkStats {
+			BatteryModule::network_stats()
+		}
+
+		fn network_swap_capacity() -> u32 {
+			BatteryModule::network_swap_capacity()
+		}
+
+		fn can_store(who: AccountId, id: Hash) -> Result<(), Vec<u8>> {
+			BatteryModule::can_store(&who, id).map(|_| ()).map_err(|e| e.as_bytes().to_vec())
+		}
+
+		fn can_fetch(who: AccountId, id: Hash) -> Result<(), Vec<u8>> {
+			BatteryModule::can_fetch(&who, id).map(|_| ()).map_err(|e| e.as_bytes().to_vec())
+		}
+
+		fn can_trade(station: AccountId, id: Hash, to: AccountId) -> Result<(), Vec<u8>> {
+			BatteryModule::can_trade(&station, id, &to).map_err(|e| e.as_bytes().to_vec())
+		}
+
+		fn battery_passport_hash(id: Hash) -> Hash {
+			BatteryModule::passport_hash(id)
+		}
+
+		fn battery_nonce(id: Hash) -> u64 {
+			BatteryModule::battery_nonce(id)
+		}
+
+		fn battery_storage_key(id: Hash) -> Vec<u8> {
+			BatteryModule::battery_storage_key(id)
+		}
+
+		fn battery_passport(id: Hash) -> Option<battery::BatteryPassport<Hash, AccountId, BlockNumber>> {
+			BatteryModule::battery_passport(id)
+		}
+
+		fn ownership_proof(checkpoint: u64, id: Hash, owner: AccountId) -> Option<Vec<Hash>> {
+			BatteryModule::ownership_proof(checkpoint, id, &owner)
+		}
+
+		fn verify_ownership(checkpoint: u64, id: Hash, owner: AccountId, proof: Vec<Hash>) -> bool {
+			BatteryModule::verify_ownership(checkpoint, id, &owner, proof)
+		}
+
+		fn export_account_data(
+			account: AccountId,
+			cursor: u64,
+			limit: u64,
+		) -> battery::AccountExport<Hash, AccountId, BlockNumber, Balance> {
+			BatteryModule::export_account_data(&account, cursor, limit)
+		}
+
+		fn get_station_audit_log(
+			station: AccountId,
+			offset: u64,
+			limit: u64,
+		) -> Vec<battery::StationAuditEntry<AccountId, BlockNumber, Hash>> {
+			BatteryModule::station_audit_log(&station, offset, limit)
+		}
+
+		fn verify_receipt(receipt: Hash) -> Option<battery::ReceiptInfo<Hash, BlockNumber>> {
+			BatteryModule::verify_receipt(receipt)
+		}
+	}
 }
@@ -1,7 +1,7 @@
 use primitives::{ed25519, sr25519, Pair};
 use fuelow_runtime::{
 	AccountId, GenesisConfig, ConsensusConfig, TimestampConfig, BalancesConfig,
-	SudoConfig, IndicesConfig,
+	SudoConfig, IndicesConfig, BatteryModuleConfig,
 };
 use substrate_service;
 
@@ -113,7 +113,31 @@ fn testnet_genesis(initial_authorities: Vec<AuthorityId>, endowed_accounts: Vec<
 			vesting: vec![],
 		}),
 		sudo: Some(SudoConfig {
-			key: root_key,
+			key: root_key.clone(),
+		}),
+		battery_module: Some(BatteryModuleConfig {
+			preorder_lock_period: 17_280, // Two days of 10 second blocks.
+			rewards_per_block: 1_000,
+			co_owner_approval_threshold: 2,
+			challenge_period_blocks: 60_480, // A week of 10 second blocks.
+			probation_trade_threshold: 20,
+			blocks_per_checkpoint: 8_640, // A day of 10 second blocks.
+			abandonment_period: 9_460_800, // Three years of 10 second blocks.
+			escheat_notice_period: 259_200, // A month of 10 second blocks.
+			escheat_pool: root_key,
+			key_retention_blocks: 8_640, // A day of 10 second blocks.
+			receipt_retention_blocks: 259_200, // A month of 10 second blocks.
+			require_identity_for_trade: false,
+			report_era_length: 60_480, // A week of 10 second blocks.
+			review_stake_threshold: 100_000,
+			blocks_per_day: 8_640, // 10 second blocks.
+			promotion_threshold: 100,
+			rebalance_expiry_blocks: 8_640, // A day of 10 second blocks.
+			audit_interval: 60_480, // A week of 10 second blocks.
+			audit_sample_size: 5,
+			audit_deadline_blocks: 17_280, // Two days of 10 second blocks.
+			station_bond: 10_000,
+			store_request_expiry_blocks: 360, // An hour of 10 second blocks.
 		}),
 	}
 }